@@ -335,6 +335,7 @@ fn build_guest_package<P>(
     guest_build_env: &GuestBuildEnv,
     features: Vec<String>,
     std: bool,
+    patch_sha2: bool,
 ) where
     P: AsRef<Path>,
 {
@@ -364,6 +365,11 @@ fn build_guest_package<P>(
         args.push("--features");
         args.push(&features_str);
     }
+    let patches = if patch_sha2 { sha2_patches() } else { vec![] };
+    for patch in patches.iter() {
+        args.push("--config");
+        args.push(patch);
+    }
     println!("Building guest package: {cargo} {}", args.join(" "));
     // The RISC0_STANDARD_LIB variable can be set for testing purposes
     // to override the downloaded standard library.  It should point
@@ -419,6 +425,22 @@ fn build_guest_package<P>(
     }
 }
 
+// Returns the cargo configuration which replaces the `sha2` crate with the
+// accelerated drop-in replacement in this repository.
+fn sha2_patches() -> Vec<String> {
+    // The replacement is not published, so it can only be found next to this
+    // crate in a checkout of the repository.
+    let local = Path::new(env!("CARGO_MANIFEST_DIR")).join("../sha2");
+    match local.canonicalize() {
+        Ok(path) => vec![format!("patch.crates-io.sha2.path='{}'", path.display())],
+        Err(err) => panic!(
+            "GuestOptions::patch_sha2 requires risc0-build from a checkout of the risc0 \
+             repository, but the sha2 crate was not found at {}: {err}",
+            local.display()
+        ),
+    }
+}
+
 /// Options defining how to embed a guest package in
 /// [`embed_methods_with_options`].
 pub struct GuestOptions {
@@ -427,6 +449,14 @@ pub struct GuestOptions {
 
     /// Enable standard library support
     pub std: bool,
+
+    /// Replace the `sha2` crate with a drop-in version which uses the SHA-256
+    /// accelerator, so that dependencies which use `sha2` run faster in the
+    /// guest without any changes.
+    ///
+    /// The replacement crate is not published, so this is only available
+    /// when risc0-build is used from a checkout of the risc0 repository.
+    pub patch_sha2: bool,
 }
 
 impl Default for GuestOptions {
@@ -434,6 +464,7 @@ impl Default for GuestOptions {
         GuestOptions {
             features: vec![],
             std: true,
+            patch_sha2: false,
         }
     }
 }
@@ -471,6 +502,7 @@ pub fn embed_methods_with_options(mut guest_pkg_to_options: HashMap<&str, GuestO
             &guest_build_env,
             guest_options.features,
             guest_options.std,
+            guest_options.patch_sha2,
         );
 
         for method in guest_methods(&guest_pkg, &out_dir) {
@@ -371,6 +371,8 @@ pub struct ExtElem([Elem; EXT_SIZE]);
 /// Alias for the Baby Bear [ExtElem]
 pub type BabyBearExtElem = ExtElem;
 
+field::encoding::impl_encodings!(Elem, ExtElem, u32, P);
+
 impl Default for ExtElem {
     fn default() -> Self {
         Self::ZERO
@@ -705,6 +707,23 @@ mod tests {
         field::tests::test_field_ops::<Elem>(P_U64);
     }
 
+    #[test]
+    pub fn text() {
+        field::encoding::tests::test_text::<Elem, ExtElem>(P_U64);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    pub fn serde() {
+        field::encoding::tests::test_serde::<Elem, ExtElem>(P_U64);
+    }
+
+    #[test]
+    pub fn batch_inv() {
+        field::tests::test_batch_inv::<Elem>();
+        field::tests::test_batch_inv::<ExtElem>();
+    }
+
     #[test]
     pub fn linear() {
         let x = ExtElem::new(
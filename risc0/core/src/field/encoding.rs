@@ -0,0 +1,298 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Text and serde encodings of field elements.
+//!
+//! Base field elements are written as their canonical value, never the
+//! internal (e.g. Montgomery) representation: in decimal with [Display], or in
+//! hex with [LowerHex](core::fmt::LowerHex) and
+//! [UpperHex](core::fmt::UpperHex). [FromStr](core::str::FromStr) accepts
+//! either decimal or `0x`-prefixed hex, so use `{:#x}` to write hex which can
+//! be parsed back. Extension field elements are written as a list of their
+//! subfield components, such as `[1, 2, 3, 4]`.
+//!
+//! With the `serde` feature, base field elements are serialized as their
+//! canonical value, and extension field elements as a tuple of components.
+//! Parsing and deserializing reject values which are not reduced modulo the
+//! field's prime, so that every element has exactly one encoding.
+
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+
+/// An error returned when parsing a field element from text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseElemError {
+    /// The text is not a decimal or `0x`-prefixed hex number.
+    InvalidNumber,
+
+    /// The value is not reduced modulo the field's prime.
+    NotReduced,
+
+    /// An extension field element has the wrong number of components.
+    WrongLength {
+        /// The number of components of the extension field.
+        expected: usize,
+        /// The number of components found.
+        actual: usize,
+    },
+}
+
+impl Display for ParseElemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseElemError::InvalidNumber => write!(f, "Invalid field element"),
+            ParseElemError::NotReduced => write!(f, "Field element is not reduced"),
+            ParseElemError::WrongLength { expected, actual } => write!(
+                f,
+                "Expected {expected} extension field components, found {actual}"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseElemError {}
+
+/// Parses a canonical value less than `modulus`, in decimal or `0x`-prefixed
+/// hex.
+pub(crate) fn parse_canonical(s: &str, modulus: u64) -> Result<u64, ParseElemError> {
+    let s = s.trim();
+    let value = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|_| ParseElemError::InvalidNumber)?;
+    if value >= modulus {
+        return Err(ParseElemError::NotReduced);
+    }
+    Ok(value)
+}
+
+/// Splits an extension field element written as `[a, b, ...]` into the text of
+/// its `ext_size` components.
+pub(crate) fn split_ext(s: &str, ext_size: usize) -> Result<Vec<&str>, ParseElemError> {
+    let parts: Vec<&str> = s
+        .trim()
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .ok_or(ParseElemError::InvalidNumber)?
+        .split(',')
+        .collect();
+    if parts.len() != ext_size {
+        return Err(ParseElemError::WrongLength {
+            expected: ext_size,
+            actual: parts.len(),
+        });
+    }
+    Ok(parts)
+}
+
+/// Writes the components of an extension field element as `[a, b, ...]`,
+/// formatting each with `fmt`, which passes on the flags of `f`.
+pub(crate) fn write_ext<T>(
+    f: &mut fmt::Formatter<'_>,
+    elems: &[T],
+    fmt: fn(&T, &mut fmt::Formatter<'_>) -> fmt::Result,
+) -> fmt::Result {
+    write!(f, "[")?;
+    for (i, elem) in elems.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        fmt(elem, f)?;
+    }
+    write!(f, "]")
+}
+
+/// Implements the text and serde encodings for a base field element `$elem`,
+/// whose canonical value is a `$int` less than `$modulus`, and its extension
+/// field element `$ext`.
+macro_rules! impl_encodings {
+    ($elem:ident, $ext:ident, $int:ty, $modulus:expr) => {
+        impl core::fmt::Display for $elem {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                core::fmt::Display::fmt(&<$int>::from(*self), f)
+            }
+        }
+
+        impl core::fmt::LowerHex for $elem {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                core::fmt::LowerHex::fmt(&<$int>::from(*self), f)
+            }
+        }
+
+        impl core::fmt::UpperHex for $elem {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                core::fmt::UpperHex::fmt(&<$int>::from(*self), f)
+            }
+        }
+
+        impl core::str::FromStr for $elem {
+            type Err = $crate::field::ParseElemError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                let value = $crate::field::encoding::parse_canonical(s, $modulus as u64)?;
+                Ok(<$elem>::from(value as $int))
+            }
+        }
+
+        impl core::fmt::Display for $ext {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                $crate::field::encoding::write_ext(f, &self.0, core::fmt::Display::fmt)
+            }
+        }
+
+        impl core::fmt::LowerHex for $ext {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                $crate::field::encoding::write_ext(f, &self.0, core::fmt::LowerHex::fmt)
+            }
+        }
+
+        impl core::fmt::UpperHex for $ext {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                $crate::field::encoding::write_ext(f, &self.0, core::fmt::UpperHex::fmt)
+            }
+        }
+
+        impl core::str::FromStr for $ext {
+            type Err = $crate::field::ParseElemError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                let parts = $crate::field::encoding::split_ext(s, EXT_SIZE)?;
+                let mut elems = [<$elem as $crate::field::Elem>::ZERO; EXT_SIZE];
+                for (elem, part) in elems.iter_mut().zip(parts) {
+                    *elem = part.parse()?;
+                }
+                Ok($ext(elems))
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $elem {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serde::Serialize::serialize(&<$int>::from(*self), serializer)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $elem {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let value = <$int as serde::Deserialize>::deserialize(deserializer)?;
+                if value as u64 >= $modulus as u64 {
+                    return Err(serde::de::Error::custom(
+                        $crate::field::ParseElemError::NotReduced,
+                    ));
+                }
+                Ok(<$elem>::from(value))
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $ext {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serde::Serialize::serialize(&self.0, serializer)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $ext {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                <[$elem; EXT_SIZE] as serde::Deserialize>::deserialize(deserializer).map($ext)
+            }
+        }
+    };
+}
+
+pub(crate) use impl_encodings;
+
+#[cfg(test)]
+pub mod tests {
+    use alloc::{format, string::ToString};
+    use core::str::FromStr;
+
+    use super::ParseElemError;
+    use crate::field::{Elem, ExtElem};
+
+    /// Checks that elements round trip through their text encodings, and that
+    /// values which are not canonical are rejected.
+    pub fn test_text<E, X>(modulus: u64)
+    where
+        E: Elem + FromStr<Err = ParseElemError> + core::fmt::Display + core::fmt::LowerHex,
+        X: ExtElem<SubElem = E>
+            + FromStr<Err = ParseElemError>
+            + core::fmt::Display
+            + core::fmt::LowerHex,
+    {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let x = E::random(&mut rng);
+            assert_eq!(x.to_string().parse::<E>().unwrap(), x);
+            assert_eq!(format!("{x:#x}").parse::<E>().unwrap(), x);
+            let y = X::random(&mut rng);
+            assert_eq!(y.to_string().parse::<X>().unwrap(), y);
+            assert_eq!(format!("{y:#x}").parse::<X>().unwrap(), y);
+        }
+        assert_eq!("12".parse::<E>().unwrap(), E::from_u64(12));
+        assert_eq!("0x1f".parse::<E>().unwrap(), E::from_u64(31));
+        assert_eq!(E::from_u64(31).to_string(), "31");
+        assert_eq!(format!("{:x}", E::from_u64(31)), "1f");
+        assert_eq!(
+            (modulus - 1).to_string().parse::<E>().unwrap(),
+            E::ZERO - E::ONE
+        );
+        assert_eq!(
+            modulus.to_string().parse::<E>(),
+            Err(ParseElemError::NotReduced)
+        );
+        assert_eq!(
+            format!("{:#x}", modulus).parse::<E>(),
+            Err(ParseElemError::NotReduced)
+        );
+        assert_eq!("-1".parse::<E>(), Err(ParseElemError::InvalidNumber));
+        assert_eq!("one".parse::<E>(), Err(ParseElemError::InvalidNumber));
+        assert_eq!(
+            "[1]".parse::<X>(),
+            Err(ParseElemError::WrongLength {
+                expected: X::EXT_SIZE,
+                actual: 1,
+            })
+        );
+        assert_eq!("1".parse::<X>(), Err(ParseElemError::InvalidNumber));
+    }
+
+    /// Checks that elements round trip through JSON, and that values which are
+    /// not canonical are rejected.
+    #[cfg(feature = "serde")]
+    pub fn test_serde<E, X>(modulus: u64)
+    where
+        E: Elem + serde::Serialize + serde::de::DeserializeOwned + core::fmt::Debug,
+        X: ExtElem<SubElem = E> + serde::Serialize + serde::de::DeserializeOwned + core::fmt::Debug,
+    {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let x = E::random(&mut rng);
+            let json = serde_json::to_string(&x).unwrap();
+            assert_eq!(serde_json::from_str::<E>(&json).unwrap(), x);
+            let y = X::random(&mut rng);
+            let json = serde_json::to_string(&y).unwrap();
+            assert_eq!(serde_json::from_str::<X>(&json).unwrap(), y);
+        }
+        assert_eq!(serde_json::to_string(&E::from_u64(7)).unwrap(), "7");
+        assert_eq!(serde_json::from_str::<E>("7").unwrap(), E::from_u64(7));
+        assert!(serde_json::from_str::<E>(&modulus.to_string()).is_err());
+        assert!(serde_json::from_str::<E>("-1").is_err());
+        let too_long = format!("[{}]", ["1"; 5].join(","));
+        assert!(serde_json::from_str::<X>(&too_long).is_err());
+    }
+}
@@ -24,6 +24,15 @@ use bytemuck::{Pod, Zeroable};
 
 use crate::field::{self, Elem as FieldElem};
 
+/// Definition of this field for operations that operate on the Goldilocks
+/// field and its 2nd degree extension.
+pub struct Goldilocks;
+
+impl field::Field for Goldilocks {
+    type Elem = Elem;
+    type ExtElem = ExtElem;
+}
+
 /// The Goldilocks class is an element of the finite field F_p, where P is the
 /// prime number 2^64 - 2^32 + 1. Here we implement integer
 /// arithmetic modulo P for both Goldilocks and for a field extension of
@@ -335,6 +344,8 @@ pub struct ExtElem([Elem; EXT_SIZE]);
 /// Alias for the Goldilocks [ExtElem]
 pub type GoldilocksExtElem = ExtElem;
 
+field::encoding::impl_encodings!(Elem, ExtElem, u64, P);
+
 impl Default for ExtElem {
     fn default() -> Self {
         Self::ZERO
@@ -605,6 +616,23 @@ mod tests {
         field::tests::test_field_ops::<Elem>(P);
     }
 
+    #[test]
+    pub fn text() {
+        field::encoding::tests::test_text::<Elem, ExtElem>(P);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    pub fn serde() {
+        field::encoding::tests::test_serde::<Elem, ExtElem>(P);
+    }
+
+    #[test]
+    pub fn batch_inv() {
+        field::tests::test_batch_inv::<Elem>();
+        field::tests::test_batch_inv::<ExtElem>();
+    }
+
     #[test]
     pub fn create_element_no_wrap() {
         let test_element = Elem::from(P - 1u64);
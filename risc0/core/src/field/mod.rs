@@ -21,8 +21,11 @@ use alloc::vec::Vec;
 use core::{cmp, fmt::Debug, ops};
 
 pub mod baby_bear;
+mod encoding;
 pub mod goldilocks;
 
+pub use encoding::ParseElemError;
+
 /// A pair of fields, one of which is an extension field of the other.
 pub trait Field {
     /// An element of the base field
@@ -210,6 +213,31 @@ pub trait ExtElem:
     fn subelems(&self) -> &[Self::SubElem];
 }
 
+/// Inverts every element of `elems` in place, using Montgomery's trick so that
+/// only a single field inversion is needed.
+///
+/// As with [Elem::inv], zero is mapped to zero.
+pub fn batch_inv<E: Elem>(elems: &mut [E]) {
+    // prefix[i] is the product of the nonzero elements before index i.
+    let mut prefix = Vec::with_capacity(elems.len());
+    let mut acc = E::ONE;
+    for elem in elems.iter() {
+        prefix.push(acc);
+        if *elem != E::ZERO {
+            acc *= *elem;
+        }
+    }
+    // Walk back, peeling one element at a time off the inverse of the product.
+    let mut inv = acc.inv();
+    for (elem, prefix) in elems.iter_mut().zip(prefix).rev() {
+        if *elem != E::ZERO {
+            let next = inv * *elem;
+            *elem = inv * prefix;
+            inv = next;
+        }
+    }
+}
+
 /// Roots of unity for the field whose elements are represented by [ExtElem] and
 /// whose subfield elements are represented by [Elem]
 pub trait RootsOfUnity: Sized + 'static {
@@ -231,11 +259,12 @@ pub trait RootsOfUnity: Sized + 'static {
 
 #[cfg(test)]
 mod tests {
+    use alloc::vec::Vec;
     use core::fmt::Debug;
 
     use rand::Rng;
 
-    use super::{Elem, RootsOfUnity};
+    use super::{batch_inv, Elem, RootsOfUnity};
 
     pub fn test_roots_of_unity<F: Elem + RootsOfUnity + Debug>() {
         let mut cur: Option<F> = None;
@@ -255,6 +284,20 @@ mod tests {
         }
     }
 
+    pub fn test_batch_inv<F: Elem>() {
+        let mut rng = rand::thread_rng();
+        for len in [0, 1, 2, 7, 100] {
+            let mut elems: Vec<F> = (0..len).map(|_| F::random(&mut rng)).collect();
+            // Sprinkle in some zeros, which must be left alone.
+            for i in (0..len).step_by(3) {
+                elems[i] = F::ZERO;
+            }
+            let expected: Vec<F> = elems.iter().map(|x| x.inv()).collect();
+            batch_inv(&mut elems);
+            assert_eq!(elems, expected);
+        }
+    }
+
     fn non_zero_rand<F: Elem>(r: &mut impl Rng) -> F {
         loop {
             let val = F::random(r);
@@ -69,6 +69,11 @@ struct Args {
     /// to read it.
     #[clap(long)]
     pprof_out: Option<PathBuf>,
+
+    /// Arguments to pass to the guest, after `--`. The guest's first argument
+    /// is the ELF file.
+    #[clap(last = true)]
+    args: Vec<String>,
 }
 
 fn read_image_id(verbose: u8, elf_file: &Path, image_id_file: Option<&Path>) -> Option<Digest> {
@@ -176,6 +181,10 @@ fn main() {
         opts = opts.with_env_var(varname, val);
     }
 
+    opts = opts.with_args(
+        std::iter::once(args.elf.display().to_string()).chain(args.args.iter().cloned()),
+    );
+
     if args.pprof_out.is_some() {
         guest_prof = Some(Profiler::new(args.elf.to_str().unwrap(), &elf_contents).unwrap());
     }
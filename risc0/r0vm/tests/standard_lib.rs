@@ -113,3 +113,24 @@ fn stdio_outputs_in_receipt_without_seal() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn args_passed_to_guest() -> Result<()> {
+    let mut cmd = Command::cargo_bin("r0vm")?;
+    cmd.arg("--elf")
+        .arg(risc0_zkvm_methods::STANDARD_LIB_PATH)
+        .arg("--skip-seal")
+        .arg("--env")
+        .arg("TEST_MODE=ARGS")
+        .arg("--")
+        .arg("--flag")
+        .arg("two words");
+    cmd.assert()
+        .stdout(format!(
+            "{}\n--flag\ntwo words\n",
+            risc0_zkvm_methods::STANDARD_LIB_PATH
+        ))
+        .success();
+
+    Ok(())
+}
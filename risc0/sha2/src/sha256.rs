@@ -0,0 +1,121 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use digest::{generic_array::GenericArray, typenum::U64};
+
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "zkvm")] {
+        use accel::compress;
+    } else {
+        use soft::compress;
+    }
+}
+
+/// Raw SHA-256 compression function.
+///
+/// This is a low-level "hazmat" API which provides direct access to the core
+/// functionality of SHA-256. Inside the zkVM, it runs on the SHA-256
+/// accelerator.
+pub fn compress256(state: &mut [u32; 8], blocks: &[GenericArray<u8, U64>]) {
+    // SAFETY: GenericArray<u8, U64> and [u8; 64] have exactly the same memory
+    // layout.
+    let blocks = unsafe { core::slice::from_raw_parts(blocks.as_ptr().cast(), blocks.len()) };
+    compress(state, blocks)
+}
+
+#[cfg(target_os = "zkvm")]
+mod accel {
+    use risc0_zkvm_platform::syscall::sys_sha_buffer;
+
+    /// The number of blocks copied at once when the input isn't word aligned.
+    const COPY_BLOCKS: usize = 4;
+
+    pub(super) fn compress(state: &mut [u32; 8], blocks: &[[u8; 64]]) {
+        if blocks.is_empty() {
+            return;
+        }
+        // The accelerator reads and writes the state as big-endian words.
+        let mut digest = state.map(u32::to_be);
+        if blocks.as_ptr().align_offset(core::mem::align_of::<u32>()) == 0 {
+            compress_aligned(&mut digest, blocks.as_ptr().cast(), blocks.len());
+        } else {
+            // The accelerator can only read whole words, so unaligned input
+            // has to be copied first.
+            let mut buf = [[0u32; 16]; COPY_BLOCKS];
+            for chunk in blocks.chunks(COPY_BLOCKS) {
+                for (dst, src) in buf.iter_mut().zip(chunk) {
+                    for (word, bytes) in dst.iter_mut().zip(src.chunks_exact(4)) {
+                        *word = u32::from_ne_bytes(bytes.try_into().unwrap());
+                    }
+                }
+                compress_aligned(&mut digest, buf.as_ptr().cast(), chunk.len());
+            }
+        }
+        *state = digest.map(u32::from_be);
+    }
+
+    fn compress_aligned(digest: &mut [u32; 8], blocks: *const u8, count: usize) {
+        let digest: *mut [u32; 8] = digest;
+        // SAFETY: blocks is word aligned and points at count blocks, and the
+        // accelerator allows the input and output states to alias.
+        unsafe { sys_sha_buffer(digest, digest, blocks, count as u32) }
+    }
+}
+
+#[cfg(not(target_os = "zkvm"))]
+mod soft {
+    use crate::consts::K32;
+
+    pub(super) fn compress(state: &mut [u32; 8], blocks: &[[u8; 64]]) {
+        for block in blocks {
+            let mut w = [0u32; 64];
+            for (word, bytes) in w.iter_mut().zip(block.chunks_exact(4)) {
+                *word = u32::from_be_bytes(bytes.try_into().unwrap());
+            }
+            for i in 16..64 {
+                let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+                let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+                w[i] = w[i - 16]
+                    .wrapping_add(s0)
+                    .wrapping_add(w[i - 7])
+                    .wrapping_add(s1);
+            }
+
+            let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+            for i in 0..64 {
+                let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+                let ch = (e & f) ^ (!e & g);
+                let t1 = h
+                    .wrapping_add(s1)
+                    .wrapping_add(ch)
+                    .wrapping_add(K32[i])
+                    .wrapping_add(w[i]);
+                let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+                let maj = (a & b) ^ (a & c) ^ (b & c);
+                let t2 = s0.wrapping_add(maj);
+                h = g;
+                g = f;
+                f = e;
+                e = d.wrapping_add(t1);
+                d = c;
+                c = b;
+                b = a;
+                a = t1.wrapping_add(t2);
+            }
+            for (s, v) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+                *s = s.wrapping_add(v);
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compares two IOP transcripts.
+//!
+//! Transcripts are recorded by the prover with
+//! `ProverOpts::with_iop_transcript` and by the verifier with
+//! `Receipt::verify_with_transcript`. When a seal fails to verify, this tool
+//! reports the first event at which the two disagree, e.g. a Fiat-Shamir
+//! mismatch or a read past the end of the seal.
+
+use std::{fs, process::exit};
+
+use clap::Parser;
+use risc0_zkp::transcript::{diff, Entry, Transcript};
+
+/// Finds the first difference between two IOP transcripts.
+#[derive(Parser)]
+#[clap(about, version, author)]
+struct Args {
+    /// The first transcript, typically recorded by the prover.
+    lhs: String,
+
+    /// The second transcript, typically recorded by the verifier.
+    rhs: String,
+}
+
+fn load(path: &str) -> Vec<Entry> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) => {
+            eprintln!("{path}: {err}");
+            exit(2);
+        }
+    };
+    match Transcript::parse(&text) {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!("{path}: {err}");
+            exit(2);
+        }
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    let lhs = load(&args.lhs);
+    let rhs = load(&args.rhs);
+    match diff(&lhs, &rhs) {
+        Some(mismatch) => {
+            println!("{mismatch}");
+            exit(1);
+        }
+        None => println!("transcripts match"),
+    }
+}
@@ -31,3 +31,33 @@ fn run_make_id() {
     make_id_cmd.arg(elf_path).arg(id_path);
     make_id_cmd.assert().success();
 }
+
+#[test]
+fn run_iop_diff() {
+    let temp_dir = tempdir().unwrap();
+    let lhs = temp_dir.path().join("prover.txt");
+    let rhs = temp_dir.path().join("verifier.txt");
+    std::fs::write(
+        &lhs,
+        "0 params data 2 0123456789abcdef\n1 fri random_bits(4) 3\n",
+    )
+    .unwrap();
+
+    std::fs::write(
+        &rhs,
+        "0 params data 2 0123456789abcdef\n1 fri random_bits(4) 3\n",
+    )
+    .unwrap();
+    let mut cmd = Command::cargo_bin("iop_diff").unwrap();
+    cmd.arg(&lhs).arg(&rhs);
+    cmd.assert().success();
+
+    std::fs::write(
+        &rhs,
+        "0 params data 2 0123456789abcdef\n1 fri random_bits(4) 5\n",
+    )
+    .unwrap();
+    let mut cmd = Command::cargo_bin("iop_diff").unwrap();
+    cmd.arg(&lhs).arg(&rhs);
+    cmd.assert().failure().code(1);
+}
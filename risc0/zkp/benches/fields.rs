@@ -0,0 +1,81 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compares the BabyBear and Goldilocks fields on the operations which
+//! dominate proving.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::thread_rng;
+use risc0_core::field::{baby_bear::BabyBear, goldilocks::Goldilocks, Elem, Field};
+use risc0_zkp::{
+    core::{config::HashSuiteSha256, sha_cpu},
+    hal::{cpu::CpuHal, Buffer, Hal},
+};
+
+type Sha256CpuHal<F> = CpuHal<F, HashSuiteSha256<F, sha_cpu::Impl>>;
+
+fn elem_mul<F: Field>(c: &mut Criterion, name: &str) {
+    let mut group = c.benchmark_group("elem_mul");
+    let mut rng = thread_rng();
+    let xs: Vec<F::Elem> = (0..1024).map(|_| F::Elem::random(&mut rng)).collect();
+    group.bench_function(BenchmarkId::new(name, "base"), |b| {
+        b.iter(|| xs.iter().fold(F::Elem::ONE, |acc, x| acc * *x));
+    });
+    let ys: Vec<F::ExtElem> = (0..1024).map(|_| F::ExtElem::random(&mut rng)).collect();
+    group.bench_function(BenchmarkId::new(name, "ext"), |b| {
+        b.iter(|| ys.iter().fold(F::ExtElem::ONE, |acc, y| acc * *y));
+    });
+}
+
+fn hal_evaluate_ntt<F: Field>(c: &mut Criterion, name: &str) {
+    let mut group = c.benchmark_group("fields_hal_evaluate_ntt");
+    // Scalar arithmetic, so that both fields take the same code path.
+    let hal = Sha256CpuHal::<F>::new().with_simd(false);
+    for n in [10, 15, 20].iter() {
+        group.bench_function(BenchmarkId::new(name, n), |b| {
+            let size = 1 << n;
+            let mut rng = thread_rng();
+            let buf = hal.alloc_elem("io", size);
+            buf.view_mut(|buf| buf.fill(F::Elem::random(&mut rng)));
+            b.iter(|| hal.batch_evaluate_ntt(&buf, 1, 2));
+        });
+    }
+}
+
+fn hal_hash_rows<F: Field>(c: &mut Criterion, name: &str) {
+    let mut group = c.benchmark_group("fields_hal_hash_rows");
+    let hal = Sha256CpuHal::<F>::new();
+    let rows = 1 << 15;
+    for cols in [16, 64].iter() {
+        group.bench_function(BenchmarkId::new(name, cols), |b| {
+            let mut rng = thread_rng();
+            let matrix = hal.alloc_elem("matrix", rows * cols);
+            matrix.view_mut(|buf| buf.fill(F::Elem::random(&mut rng)));
+            let output = hal.alloc_digest("output", rows);
+            b.iter(|| hal.hash_rows(&output, black_box(&matrix)));
+        });
+    }
+}
+
+pub fn fields(c: &mut Criterion) {
+    elem_mul::<BabyBear>(c, "baby_bear");
+    elem_mul::<Goldilocks>(c, "goldilocks");
+    hal_evaluate_ntt::<BabyBear>(c, "baby_bear");
+    hal_evaluate_ntt::<Goldilocks>(c, "goldilocks");
+    hal_hash_rows::<BabyBear>(c, "baby_bear");
+    hal_hash_rows::<Goldilocks>(c, "goldilocks");
+}
+
+criterion_group!(benches, fields);
+criterion_main!(benches);
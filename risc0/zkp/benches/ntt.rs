@@ -15,7 +15,13 @@
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
 use rand::thread_rng;
 use risc0_core::field::{baby_bear::BabyBearElem, Elem};
-use risc0_zkp::core::ntt::interpolate_ntt;
+use risc0_zkp::{
+    core::ntt::interpolate_ntt,
+    hal::{
+        cpu::{BabyBearSha256CpuHal, CpuHal},
+        Buffer, Hal,
+    },
+};
 
 pub fn ntt(c: &mut Criterion) {
     let mut group = c.benchmark_group("interpolate_ntt");
@@ -29,5 +35,22 @@ pub fn ntt(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, ntt);
+pub fn hal_evaluate_ntt(c: &mut Criterion) {
+    let mut group = c.benchmark_group("hal_evaluate_ntt");
+    for simd in [false, true] {
+        let hal: BabyBearSha256CpuHal = CpuHal::new().with_simd(simd);
+        let name = if simd { "simd" } else { "scalar" };
+        for n in [10, 15, 20].iter() {
+            group.bench_function(BenchmarkId::new(name, n), |b| {
+                let size = 1 << n;
+                let mut rng = thread_rng();
+                let buf = hal.alloc_elem("io", size);
+                buf.view_mut(|buf| buf.fill(BabyBearElem::random(&mut rng)));
+                b.iter(|| hal.batch_evaluate_ntt(&buf, 1, 2));
+            });
+        }
+    }
+}
+
+criterion_group!(benches, ntt, hal_evaluate_ntt);
 criterion_main!(benches);
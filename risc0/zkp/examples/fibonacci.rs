@@ -0,0 +1,493 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A hand-written Fibonacci circuit, proven and verified with the generic
+//! STARK API of `risc0-zkp`.
+//!
+//! The trace has one row per cycle. Row `i` holds the pair `(F(i), F(i+1))` in
+//! the data group, and the code group holds three selectors:
+//!
+//! * `first` is set on cycle 0, where the pair must equal the inputs `out[0]`
+//!   and `out[1]`;
+//! * `step` is set on cycles `1..=n`, where the pair must follow from the
+//!   previous row;
+//! * `last` is set on cycle `n`, where `F(n)` must equal the output `out[2]`.
+//!
+//! The code group of every circuit is zero on the cycles reserved for
+//! zero-knowledge, so gating each constraint by a selector from the code group
+//! keeps the constraints from applying to those cycles. Since the code depends
+//! on `n`, the verifier recomputes its Merkle root; see [code_root].
+//!
+//! The circuit is generic over the [Field], and `main` proves it over both
+//! BabyBear and Goldilocks to compare them.
+//!
+//! Run with `cargo run --release --example fibonacci -- <n>`.
+
+use std::time::Instant;
+
+use anyhow::{bail, Result};
+use risc0_core::field::{baby_bear::BabyBear, goldilocks::Goldilocks, Elem, ExtElem, Field};
+use risc0_zkp::{
+    adapter::{
+        poly_fp_from_ext, CircuitDef, CircuitInfo, CircuitStep, CircuitStepContext,
+        CircuitStepHandler, MixState, PolyExt, PolyFp, TapsProvider,
+    },
+    core::{digest::Digest, sha_cpu},
+    hal::{
+        cpu::{CpuEvalCheck, CpuHal, SyncSlice},
+        Hal,
+    },
+    params::ProofParams,
+    prove::{executor::Executor, poly_group::PolyGroup, Prover},
+    taps::{TapData, TapSet},
+    verify::{self, CpuVerifyHal, VerificationError},
+};
+
+type HashSuiteSha256<F> = risc0_zkp::core::config::HashSuiteSha256<F, sha_cpu::Impl>;
+
+// Columns of the code group.
+const FIRST: usize = 0;
+const STEP: usize = 1;
+const LAST: usize = 2;
+const CODE_SIZE: usize = 3;
+
+// Columns of the data group.
+const A: usize = 0;
+const B: usize = 1;
+
+// Indexes of the taps, in the order of [TAPSET], as passed to `poly_ext`.
+const TAP_FIRST: usize = 1;
+const TAP_STEP: usize = 2;
+const TAP_LAST: usize = 3;
+const TAP_A: usize = 4;
+const TAP_A_BACK: usize = 5;
+const TAP_B: usize = 6;
+const TAP_B_BACK: usize = 7;
+
+// Indexes of the globals, as passed to `poly_ext`.
+const GLOBAL_OUT: usize = 0;
+
+/// The taps of the circuit: every column is read on the current cycle, and the
+/// data columns are also read one cycle back.
+///
+/// Taps are sorted by group, offset and back. Registers which are read at the
+/// same set of backs share a combo, and `skip` is the number of taps of each
+/// register. The accum group is unused, but every group needs a column.
+const TAPSET: &TapSet = &TapSet::<'static> {
+    taps: &[
+        TapData {
+            offset: 0,
+            back: 0,
+            group: 0,
+            combo: 0,
+            skip: 1,
+        },
+        TapData {
+            offset: FIRST as u16,
+            back: 0,
+            group: 1,
+            combo: 0,
+            skip: 1,
+        },
+        TapData {
+            offset: STEP as u16,
+            back: 0,
+            group: 1,
+            combo: 0,
+            skip: 1,
+        },
+        TapData {
+            offset: LAST as u16,
+            back: 0,
+            group: 1,
+            combo: 0,
+            skip: 1,
+        },
+        TapData {
+            offset: A as u16,
+            back: 0,
+            group: 2,
+            combo: 1,
+            skip: 2,
+        },
+        TapData {
+            offset: A as u16,
+            back: 1,
+            group: 2,
+            combo: 1,
+            skip: 2,
+        },
+        TapData {
+            offset: B as u16,
+            back: 0,
+            group: 2,
+            combo: 1,
+            skip: 2,
+        },
+        TapData {
+            offset: B as u16,
+            back: 1,
+            group: 2,
+            combo: 1,
+            skip: 2,
+        },
+    ],
+    combo_taps: &[0, 0, 1],
+    combo_begin: &[0, 1, 3],
+    group_begin: &[0, 1, 4, 8],
+    combos_count: 2,
+    reg_count: 6,
+    tot_combo_backs: 3,
+    group_names: &["accum", "code", "data"],
+};
+
+pub struct Fibonacci;
+
+pub static CIRCUIT: Fibonacci = Fibonacci;
+
+impl CircuitInfo for Fibonacci {
+    // `F(0)`, `F(1)` and `F(n)`.
+    const OUTPUT_SIZE: usize = 3;
+    const MIX_SIZE: usize = 0;
+}
+
+impl TapsProvider for Fibonacci {
+    fn get_taps(&self) -> &'static TapSet<'static> {
+        TAPSET
+    }
+}
+
+impl<E: Elem> CircuitStep<E> for Fibonacci {
+    fn step_exec<S: CircuitStepHandler<E>>(
+        &self,
+        ctx: &CircuitStepContext,
+        _custom: &mut S,
+        args: &[SyncSlice<E>],
+    ) -> Result<E> {
+        let (code, io, data) = (&args[0], &args[1], &args[2]);
+        let row = |col: usize| col * ctx.size + ctx.cycle;
+        let prev = |col: usize| col * ctx.size + ctx.cycle - 1;
+        if code.get(row(FIRST)) == E::ONE {
+            data.set(row(A), io.get(0));
+            data.set(row(B), io.get(1));
+        } else if code.get(row(STEP)) == E::ONE {
+            data.set(row(A), data.get(prev(B)));
+            data.set(row(B), data.get(prev(A)) + data.get(prev(B)));
+        }
+        if code.get(row(LAST)) == E::ONE {
+            io.set(2, data.get(row(A)));
+            // Halt once the output is set.
+            return Ok(E::ZERO);
+        }
+        Ok(E::ONE)
+    }
+
+    fn step_verify_bytes<S: CircuitStepHandler<E>>(
+        &self,
+        _ctx: &CircuitStepContext,
+        _custom: &mut S,
+        _args: &[SyncSlice<E>],
+    ) -> Result<E> {
+        Ok(E::ZERO)
+    }
+
+    fn step_verify_mem<S: CircuitStepHandler<E>>(
+        &self,
+        _ctx: &CircuitStepContext,
+        _custom: &mut S,
+        _args: &[SyncSlice<E>],
+    ) -> Result<E> {
+        Ok(E::ZERO)
+    }
+
+    fn step_compute_accum<S: CircuitStepHandler<E>>(
+        &self,
+        _ctx: &CircuitStepContext,
+        _custom: &mut S,
+        _args: &[SyncSlice<E>],
+    ) -> Result<E> {
+        Ok(E::ZERO)
+    }
+
+    fn step_verify_accum<S: CircuitStepHandler<E>>(
+        &self,
+        _ctx: &CircuitStepContext,
+        _custom: &mut S,
+        _args: &[SyncSlice<E>],
+    ) -> Result<E> {
+        Ok(E::ZERO)
+    }
+}
+
+impl<F: Field> PolyExt<F> for Fibonacci {
+    fn poly_ext(
+        &self,
+        mix: &F::ExtElem,
+        u: &[F::ExtElem],
+        args: &[&[F::Elem]],
+    ) -> MixState<F::ExtElem> {
+        let out = |i: usize| F::ExtElem::from_subfield(&args[GLOBAL_OUT][i]);
+        let first = MixState::TRUE
+            .and_eqz(mix, u[TAP_A] - out(0))
+            .and_eqz(mix, u[TAP_B] - out(1));
+        let step = MixState::TRUE
+            .and_eqz(mix, u[TAP_A] - u[TAP_B_BACK])
+            .and_eqz(mix, u[TAP_B] - u[TAP_A_BACK] - u[TAP_B_BACK]);
+        let last = MixState::TRUE.and_eqz(mix, u[TAP_A] - out(2));
+        MixState::TRUE
+            .and_cond(u[TAP_FIRST], &first)
+            .and_cond(u[TAP_STEP], &step)
+            .and_cond(u[TAP_LAST], &last)
+    }
+}
+
+impl<F: Field> PolyFp<F> for Fibonacci {
+    fn poly_fp(
+        &self,
+        cycle: usize,
+        steps: usize,
+        mix: &F::ExtElem,
+        args: &[&[F::Elem]],
+    ) -> F::ExtElem {
+        poly_fp_from_ext::<F, _>(self, cycle, steps, mix, args)
+    }
+}
+
+impl<F: Field> CircuitDef<F> for Fibonacci {}
+
+/// This circuit has no extern calls.
+struct NoHandler;
+
+impl<E: Elem> CircuitStepHandler<E> for NoHandler {
+    fn call(
+        &mut self,
+        _cycle: usize,
+        name: &str,
+        _extra: &str,
+        _args: &[E],
+        _outs: &mut [E],
+    ) -> Result<()> {
+        bail!("Unknown extern: {name}")
+    }
+
+    fn sort(&mut self, _name: &str) {}
+
+    fn calc_prefix_products(&mut self) {}
+}
+
+/// The code row of `cycle` when computing `F(n)`.
+fn code_row<E: Elem>(cycle: usize, n: usize) -> [E; CODE_SIZE] {
+    let flag = |set: bool| if set { E::ONE } else { E::ZERO };
+    let mut row = [E::ZERO; CODE_SIZE];
+    row[FIRST] = flag(cycle == 0);
+    row[STEP] = flag(cycle > 0 && cycle <= n);
+    row[LAST] = flag(cycle == n);
+    row
+}
+
+/// Proves that `F(n)` is the `n`th element of the Fibonacci sequence starting
+/// with `f0` and `f1` in the field `F`, returning `F(n)` and the seal.
+pub fn prove<F: Field>(n: usize, f0: u64, f1: u64) -> Result<(F::Elem, Vec<u32>)> {
+    let io = [
+        F::Elem::from_u64(f0),
+        F::Elem::from_u64(f1),
+        F::Elem::INVALID,
+    ];
+    let mut executor = Executor::<F, _, _>::new(&CIRCUIT, NoHandler, 0, 20, &io);
+    let mut cycle = 0;
+    while executor.step(&code_row(cycle, n), 0)? {
+        cycle += 1;
+    }
+    executor.finalize();
+
+    let hal = CpuHal::<F, HashSuiteSha256<F>>::new();
+    let eval = CpuEvalCheck::new(&CIRCUIT);
+    let prover = Prover::new(&hal, CIRCUIT.get_taps());
+    let seal = prover.prove_circuit(&mut executor, &eval);
+    let result = executor.io.as_slice()[2];
+    Ok((result, seal))
+}
+
+/// Computes the Merkle root of the code group when computing `F(n)` with
+/// `2^po2` cycles.
+pub fn code_root<F: Field>(n: usize, po2: usize) -> Digest {
+    let hal = CpuHal::<F, HashSuiteSha256<F>>::new();
+    let steps = 1 << po2;
+    let mut code = vec![F::Elem::ZERO; steps * CODE_SIZE];
+    // Rows past `n`, including those reserved for zero-knowledge, are zero.
+    for cycle in 0..=n.min(steps - 1) {
+        for (col, value) in code_row(cycle, n).into_iter().enumerate() {
+            code[col * steps + cycle] = value;
+        }
+    }
+    let coeffs = hal.copy_from_elem("code", &code);
+    hal.batch_interpolate_ntt(&coeffs, CODE_SIZE);
+    hal.zk_shift(&coeffs, CODE_SIZE);
+    let group = PolyGroup::new(
+        &hal,
+        coeffs,
+        CODE_SIZE,
+        steps,
+        &ProofParams::default(),
+        "code",
+    );
+    *group.merkle.root()
+}
+
+/// Verifies that `seal` proves that `result` is `F(n)` for the Fibonacci
+/// sequence starting with `f0` and `f1` in the field `F`.
+pub fn verify<F: Field>(n: usize, f0: u64, f1: u64, result: F::Elem, seal: &[u32]) -> Result<()> {
+    let hal = CpuVerifyHal::<F, HashSuiteSha256<F>, _>::new(&CIRCUIT);
+    let check_params = |params: &ProofParams| {
+        params.check_security(ProofParams::default().security_bits())?;
+        if *params != ProofParams::default() {
+            // The code root is only computed for the default parameters.
+            return Err(VerificationError::InvalidProofParams);
+        }
+        Ok(())
+    };
+    let check_code = |po2: u32, root: &Digest| {
+        if code_root::<F>(n, po2 as usize) != *root {
+            return Err(VerificationError::ControlVerificationError);
+        }
+        Ok(())
+    };
+    let check_globals = |out: &[F::Elem]| {
+        if out != [F::Elem::from_u64(f0), F::Elem::from_u64(f1), result] {
+            return Err(VerificationError::ImageVerificationError);
+        }
+        Ok(())
+    };
+    verify::verify(
+        &hal,
+        &CIRCUIT,
+        seal,
+        check_params,
+        check_code,
+        check_globals,
+    )
+    .map_err(|err| anyhow::anyhow!("Verification failed: {err}"))
+}
+
+/// Proves and verifies `F(n)` over the field `F`, reporting the timings.
+fn run<F: Field>(name: &str, n: usize) {
+    let start = Instant::now();
+    let (result, seal) = prove::<F>(n, 0, 1).unwrap();
+    let prove_time = start.elapsed();
+    let start = Instant::now();
+    verify::<F>(n, 0, 1, result, &seal).unwrap();
+    let verify_time = start.elapsed();
+    println!(
+        "{name}: F({n}) mod p = {result:?}, seal: {} bytes, prove: {prove_time:?}, verify: {verify_time:?}",
+        seal.len() * 4
+    );
+}
+
+fn main() {
+    let n = std::env::args()
+        .nth(1)
+        .map(|arg| arg.parse().expect("n must be a number"))
+        .unwrap_or(1000);
+    run::<BabyBear>("BabyBear", n);
+    run::<Goldilocks>("Goldilocks", n);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fib<E: Elem>(n: usize, f0: u64, f1: u64) -> E {
+        let (mut a, mut b) = (E::from_u64(f0), E::from_u64(f1));
+        for _ in 0..n {
+            (a, b) = (b, a + b);
+        }
+        a
+    }
+
+    fn prove_and_verify<F: Field>() {
+        for n in [1, 10, 100] {
+            let (result, seal) = prove::<F>(n, 0, 1).unwrap();
+            assert_eq!(result, fib(n, 0, 1));
+            verify::<F>(n, 0, 1, result, &seal).unwrap();
+        }
+    }
+
+    fn wrong_output<F: Field>() {
+        let (result, seal) = prove::<F>(20, 2, 3).unwrap();
+        verify::<F>(20, 2, 3, result, &seal).unwrap();
+        assert!(verify::<F>(20, 2, 3, result + F::Elem::ONE, &seal).is_err());
+        assert!(verify::<F>(20, 1, 3, result, &seal).is_err());
+        assert!(verify::<F>(21, 2, 3, result, &seal).is_err());
+    }
+
+    fn constraints_match<F: Field>() {
+        // The trace rows satisfy the constraints, and a bad row does not.
+        let mix = F::ExtElem::from_subelems(
+            (0..F::ExtElem::EXT_SIZE).map(|i| F::Elem::from_u64(3 + 2 * i as u64)),
+        );
+        let out: Vec<F::Elem> = vec![F::Elem::from_u64(2), F::Elem::from_u64(3), fib(5, 2, 3)];
+        let ext = |x: u64| F::ExtElem::from_subfield(&F::Elem::from_u64(x));
+        let step = [0, 0, 1, 0, 8, 5, 13, 8].map(ext);
+        assert_eq!(
+            PolyExt::<F>::poly_ext(&CIRCUIT, &mix, &step, &[&out, &[]]).tot,
+            F::ExtElem::ZERO
+        );
+        let bad = [0, 0, 1, 0, 8, 5, 14, 8].map(ext);
+        assert_ne!(
+            PolyExt::<F>::poly_ext(&CIRCUIT, &mix, &bad, &[&out, &[]]).tot,
+            F::ExtElem::ZERO
+        );
+    }
+
+    #[test]
+    fn baby_bear_prove_and_verify() {
+        prove_and_verify::<BabyBear>();
+    }
+
+    #[test]
+    fn goldilocks_prove_and_verify() {
+        prove_and_verify::<Goldilocks>();
+    }
+
+    #[test]
+    fn baby_bear_wrong_output() {
+        wrong_output::<BabyBear>();
+    }
+
+    #[test]
+    fn goldilocks_wrong_output() {
+        wrong_output::<Goldilocks>();
+    }
+
+    #[test]
+    fn baby_bear_constraints_match() {
+        constraints_match::<BabyBear>();
+    }
+
+    #[test]
+    fn goldilocks_constraints_match() {
+        constraints_match::<Goldilocks>();
+    }
+
+    #[test]
+    fn fields_differ() {
+        // F(60) fits in the Goldilocks field but wraps around in BabyBear.
+        const F60: u64 = 1548008755920;
+        let (bb, _) = prove::<BabyBear>(60, 0, 1).unwrap();
+        let (gl, _) = prove::<Goldilocks>(60, 0, 1).unwrap();
+        assert_eq!(gl, <Goldilocks as Field>::Elem::from_u64(F60));
+        assert_eq!(bb, <BabyBear as Field>::Elem::from_u64(F60));
+        assert_ne!(bb.to_u32_words()[0] as u64, F60);
+    }
+}
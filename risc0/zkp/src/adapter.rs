@@ -13,6 +13,31 @@
 // limitations under the License.
 
 //! Interface between the circuit and prover/verifier
+//!
+//! A circuit is described by a set of traits which together make up
+//! [CircuitDef]:
+//!
+//! * [TapsProvider] gives the [TapSet]: the columns of each register group
+//!   (accum, code and data) and the cycles, relative to the current one, at
+//!   which the constraints read them.
+//! * [CircuitInfo] gives the number of outputs (globals) and accum mix values.
+//! * [CircuitStep] computes the execution trace one cycle at a time, driven by
+//!   [Executor](crate::prove::executor::Executor).
+//! * [PolyExt] evaluates the constraints, mixed together with [MixState], from
+//!   the values of the taps. The verifier evaluates it at a single point.
+//! * [PolyFp] evaluates the same constraints for the prover on every point of
+//!   the domain of the check polynomial. Hand-written circuits can implement it
+//!   with [poly_fp_from_ext].
+//!
+//! The code group is zero on the last [ZK_CYCLES](crate::ZK_CYCLES) cycles,
+//! where the data group is random, so every constraint should be conditioned
+//! on a column of the code group. The verifier is responsible for checking the
+//! Merkle root of the code group (see [verify](crate::verify::verify)).
+//!
+//! A circuit is proven with
+//! [Prover::prove_circuit](crate::prove::Prover::prove_circuit) and verified
+//! with [verify](crate::verify::verify). See `examples/fibonacci.rs` for a
+//! complete hand-written circuit.
 
 use alloc::vec::Vec;
 
@@ -21,6 +46,7 @@ use risc0_core::field::{Elem, ExtElem, Field};
 
 use crate::hal::cpu::SyncSlice;
 use crate::taps::TapSet;
+use crate::INV_RATE;
 
 // TODO: Remove references to these constants so we don't depend on a
 // fixed set of register groups.
@@ -28,12 +54,40 @@ pub const REGISTER_GROUP_ACCUM: usize = 0;
 pub const REGISTER_GROUP_CODE: usize = 1;
 pub const REGISTER_GROUP_DATA: usize = 2;
 
+/// A set of constraints mixed into a single value: each constraint is
+/// multiplied by a different power of the mix, so the total is zero if and
+/// only if (with high probability) every constraint is zero.
 #[derive(Clone, Copy)]
 pub struct MixState<EE: ExtElem> {
     pub tot: EE,
     pub mul: EE,
 }
 
+impl<EE: ExtElem> MixState<EE> {
+    /// The empty set of constraints.
+    pub const TRUE: Self = Self {
+        tot: EE::ZERO,
+        mul: EE::ONE,
+    };
+
+    /// Adds the constraint that `val` is zero.
+    pub fn and_eqz(self, mix: &EE, val: EE) -> Self {
+        Self {
+            tot: self.tot + self.mul * val,
+            mul: self.mul * *mix,
+        }
+    }
+
+    /// Adds the constraints of `inner`, conditioned on `cond`: they only
+    /// apply where `cond` is nonzero.
+    pub fn and_cond(self, cond: EE, inner: &Self) -> Self {
+        Self {
+            tot: self.tot + cond * inner.tot * self.mul,
+            mul: self.mul * inner.mul,
+        }
+    }
+}
+
 pub trait CircuitStepHandler<E: Elem> {
     fn call(
         &mut self,
@@ -122,6 +176,40 @@ pub trait CircuitInfo {
     const MIX_SIZE: usize;
 }
 
+/// Evaluates the constraints given by [PolyExt] at `cycle` of the domain of
+/// the check polynomial, which has `steps` points, as required by
+/// [PolyFp::poly_fp].
+///
+/// `args` are the register groups and globals in the order used by the
+/// prover: code, out, data, mix and accum. This reads every tap and so is
+/// slower than a generated implementation, but lets a hand-written circuit
+/// define its constraints once.
+pub fn poly_fp_from_ext<F, C>(
+    circuit: &C,
+    cycle: usize,
+    steps: usize,
+    mix: &F::ExtElem,
+    args: &[&[F::Elem]],
+) -> F::ExtElem
+where
+    F: Field,
+    C: PolyExt<F> + TapsProvider,
+{
+    // The position of each register group in `args`.
+    const GROUP_ARGS: [usize; 3] = [4, 0, 2];
+    let u: Vec<F::ExtElem> = circuit
+        .get_taps()
+        .taps()
+        .map(|tap| {
+            // Each cycle spans INV_RATE points of the domain.
+            let back = tap.back() * INV_RATE % steps;
+            let idx = tap.offset() * steps + (cycle + steps - back) % steps;
+            F::ExtElem::from_subfield(&args[GROUP_ARGS[tap.group()]][idx])
+        })
+        .collect();
+    circuit.poly_ext(mix, &u, &[args[1], args[3]]).tot
+}
+
 pub trait CircuitDef<F: Field>:
     CircuitInfo + CircuitStep<F::Elem> + PolyFp<F> + PolyExt<F> + TapsProvider + Sync
 {
@@ -177,27 +265,13 @@ impl PolyExtStep {
                 fp_vars.push(fp_vars[*x1] * fp_vars[*x2]);
             }
             PolyExtStep::True => {
-                mix_vars.push(MixState {
-                    tot: F::ExtElem::ZERO,
-                    mul: F::ExtElem::ONE,
-                });
+                mix_vars.push(MixState::TRUE);
             }
             PolyExtStep::AndEqz(x, val) => {
-                let x = mix_vars[*x];
-                let val = fp_vars[*val];
-                mix_vars.push(MixState {
-                    tot: x.tot + x.mul * val,
-                    mul: x.mul * *mix,
-                });
+                mix_vars.push(mix_vars[*x].and_eqz(mix, fp_vars[*val]));
             }
             PolyExtStep::AndCond(x, cond, inner) => {
-                let x = mix_vars[*x];
-                let cond = fp_vars[*cond];
-                let inner = mix_vars[*inner];
-                mix_vars.push(MixState {
-                    tot: x.tot + cond * inner.tot * x.mul,
-                    mul: x.mul * inner.mul,
-                });
+                mix_vars.push(mix_vars[*x].and_cond(fp_vars[*cond], &mix_vars[*inner]));
             }
         }
     }
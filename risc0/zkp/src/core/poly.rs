@@ -12,11 +12,27 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-//! Polynomial utilities (currently only those used in polynomial evaluation).
+//! Polynomial utilities.
+//!
+//! Polynomials are given by their coefficients, starting with the constant
+//! term. Besides the simple routines used by the prover and verifier, this
+//! includes NTT-based multiplication and division, multi-point evaluation, and
+//! evaluation from the values on a coset of the roots of unity, which are
+//! useful when writing custom circuits and verifiers.
 
-use alloc::vec;
+use alloc::{vec, vec::Vec};
 
-use risc0_core::field::ExtElem;
+use risc0_core::field::{batch_inv, Elem, ExtElem, RootsOfUnity};
+
+use super::{
+    log2_ceil,
+    ntt::{bit_reverse, evaluate_ntt, interpolate_ntt},
+};
+
+// Below these sizes, the quadratic algorithms beat the NTT-based ones.
+const MUL_THRESHOLD: usize = 32;
+const DIV_THRESHOLD: usize = 32;
+const EVAL_THRESHOLD: usize = 64;
 
 /// Evaluate a polynomial whose coefficients are in the extension field at a
 /// point.
@@ -87,3 +103,377 @@ pub fn poly_divide<E: ExtElem>(p: &mut [E], z: E) -> E {
     }
     cur
 }
+
+/// Multiplies two polynomials, using the NTT for all but small inputs.
+pub fn poly_mul<E>(a: &[E], b: &[E]) -> Vec<E>
+where
+    E: ExtElem,
+    E::SubElem: RootsOfUnity,
+{
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let len = a.len() + b.len() - 1;
+    if a.len().min(b.len()) <= MUL_THRESHOLD {
+        let mut out = vec![E::ZERO; len];
+        for (i, x) in a.iter().enumerate() {
+            for (j, y) in b.iter().enumerate() {
+                out[i + j] += *x * *y;
+            }
+        }
+        return out;
+    }
+    let size = 1 << log2_ceil(len);
+    let mut fa = to_evals(a, size);
+    let fb = to_evals(b, size);
+    for (x, y) in fa.iter_mut().zip(fb) {
+        *x *= y;
+    }
+    interpolate_ntt::<E::SubElem, E>(&mut fa);
+    bit_reverse(&mut fa);
+    fa.truncate(len);
+    fa
+}
+
+// Evaluates `coeffs` on the `size`th roots of unity, in natural order.
+fn to_evals<E>(coeffs: &[E], size: usize) -> Vec<E>
+where
+    E: ExtElem,
+    E::SubElem: RootsOfUnity,
+{
+    let mut evals = coeffs.to_vec();
+    evals.resize(size, E::ZERO);
+    // The NTT takes its coefficients in bit reversed order.
+    bit_reverse(&mut evals);
+    evaluate_ntt::<E::SubElem, E>(&mut evals, 0);
+    evals
+}
+
+/// Divides `num` by `den`, returning the quotient and the remainder.
+///
+/// The remainder has `den.len() - 1` coefficients, after dropping any leading
+/// zeros of `den`. Panics if `den` is zero.
+pub fn poly_div_rem<E>(num: &[E], den: &[E]) -> (Vec<E>, Vec<E>)
+where
+    E: ExtElem,
+    E::SubElem: RootsOfUnity,
+{
+    let den_len = den
+        .iter()
+        .rposition(|x| *x != E::ZERO)
+        .expect("Division by the zero polynomial")
+        + 1;
+    let den = &den[..den_len];
+    if num.len() < den_len {
+        let mut rem = num.to_vec();
+        rem.resize(den_len - 1, E::ZERO);
+        return (Vec::new(), rem);
+    }
+    let quot_len = num.len() - den_len + 1;
+    let quot = if quot_len.min(den_len) <= DIV_THRESHOLD {
+        long_divide(num, den)
+    } else {
+        // Reversing the coefficients turns the quotient into a power series,
+        // which is found by multiplying by the inverse of the reversed divisor.
+        let mut rev_num = num.to_vec();
+        rev_num.reverse();
+        rev_num.truncate(quot_len);
+        let mut rev_den = den.to_vec();
+        rev_den.reverse();
+        let mut quot = poly_mul(&rev_num, &series_inv(&rev_den, quot_len));
+        quot.truncate(quot_len);
+        quot.reverse();
+        quot
+    };
+    let mut rem = num[..den_len - 1].to_vec();
+    for (r, x) in rem.iter_mut().zip(poly_mul(&quot, den)) {
+        *r -= x;
+    }
+    (quot, rem)
+}
+
+// Computes the quotient of `num` by `den` by schoolbook long division.
+fn long_divide<E: ExtElem>(num: &[E], den: &[E]) -> Vec<E> {
+    let mut rem = num.to_vec();
+    let quot_len = num.len() - den.len() + 1;
+    let mut quot = vec![E::ZERO; quot_len];
+    let lead_inv = den[den.len() - 1].inv();
+    for i in (0..quot_len).rev() {
+        let q = rem[i + den.len() - 1] * lead_inv;
+        for (j, d) in den.iter().enumerate() {
+            rem[i + j] -= q * *d;
+        }
+        quot[i] = q;
+    }
+    quot
+}
+
+// Computes the first `len` coefficients of the power series `1 / f` by Newton
+// iteration, doubling the precision each step.
+fn series_inv<E>(f: &[E], len: usize) -> Vec<E>
+where
+    E: ExtElem,
+    E::SubElem: RootsOfUnity,
+{
+    let mut inv = vec![f[0].inv()];
+    let mut prec = 1;
+    while prec < len {
+        prec = (2 * prec).min(len);
+        // inv = inv * (2 - f * inv) mod x^prec
+        let mut err = poly_mul(&f[..prec.min(f.len())], &inv);
+        err.truncate(prec);
+        for x in err.iter_mut() {
+            *x = -*x;
+        }
+        err[0] += E::ONE + E::ONE;
+        inv = poly_mul(&inv, &err);
+        inv.truncate(prec);
+    }
+    inv
+}
+
+/// Computes the monic polynomial whose roots are `roots`, i.e. the vanishing
+/// polynomial of that set.
+pub fn poly_from_roots<E>(roots: &[E]) -> Vec<E>
+where
+    E: ExtElem,
+    E::SubElem: RootsOfUnity,
+{
+    match roots {
+        [] => vec![E::ONE],
+        [root] => vec![-*root, E::ONE],
+        _ => {
+            let (lhs, rhs) = roots.split_at(roots.len() / 2);
+            poly_mul(&poly_from_roots(lhs), &poly_from_roots(rhs))
+        }
+    }
+}
+
+/// Evaluates a polynomial at each of `xs`.
+///
+/// Large batches are evaluated with a subproduct tree: the polynomial is
+/// reduced modulo the vanishing polynomial of each half of the points, and so
+/// on down to the individual points.
+pub fn poly_eval_many<E>(coeffs: &[E], xs: &[E]) -> Vec<E>
+where
+    E: ExtElem,
+    E::SubElem: RootsOfUnity,
+{
+    if xs.len() < EVAL_THRESHOLD || coeffs.len() < EVAL_THRESHOLD {
+        return xs.iter().map(|x| poly_eval(coeffs, *x)).collect();
+    }
+    // tree[0] holds the factors (x - x_i), and each level above holds the
+    // products of adjacent pairs of the level below, carrying an odd one out up
+    // unchanged.
+    let mut tree: Vec<Vec<Vec<E>>> = vec![xs.iter().map(|x| vec![-*x, E::ONE]).collect()];
+    while tree[tree.len() - 1].len() > 1 {
+        let next = tree[tree.len() - 1]
+            .chunks(2)
+            .map(|pair| match pair {
+                [lhs, rhs] => poly_mul(lhs, rhs),
+                _ => pair[0].clone(),
+            })
+            .collect();
+        tree.push(next);
+    }
+    let mut rems = vec![poly_div_rem(coeffs, &tree[tree.len() - 1][0]).1];
+    for level in tree.iter().rev().skip(1) {
+        rems = level
+            .chunks(2)
+            .zip(rems)
+            .flat_map(|(nodes, rem)| {
+                nodes
+                    .iter()
+                    .map(|node| poly_div_rem(&rem, node).1)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+    }
+    rems.into_iter().map(|rem| rem[0]).collect()
+}
+
+/// Returns the vanishing polynomial `x^(2^po2) - shift^(2^po2)` of the coset
+/// `shift * H`, where `H` is the group of `2^po2`th roots of unity.
+pub fn coset_vanishing_poly<E: ExtElem>(shift: E, po2: usize) -> Vec<E> {
+    let size = 1 << po2;
+    let mut coeffs = vec![E::ZERO; size + 1];
+    coeffs[0] = -shift.pow(size);
+    coeffs[size] = E::ONE;
+    coeffs
+}
+
+/// Evaluates the vanishing polynomial of the coset `shift * H` at `x`; see
+/// [coset_vanishing_poly].
+pub fn coset_vanishing_eval<E: ExtElem>(shift: E, po2: usize, x: E) -> E {
+    let size = 1 << po2;
+    x.pow(size) - shift.pow(size)
+}
+
+/// Evaluates at `x` the polynomial of degree less than `evals.len()` whose
+/// values on the coset `shift * H` are `evals`, without interpolating it.
+///
+/// `H` is the group of `evals.len()`th roots of unity, and `evals[i]` is the
+/// value at `shift * w^i`, where `w` is the generator `ROU_FWD[po2]`.
+pub fn barycentric_eval<E>(evals: &[E], shift: E, x: E) -> E
+where
+    E: ExtElem,
+    E::SubElem: RootsOfUnity,
+{
+    let size = evals.len();
+    let po2 = log2_ceil(size);
+    assert_eq!(1 << po2, size, "The domain size must be a power of two");
+    let gen = <E::SubElem as RootsOfUnity>::ROU_FWD[po2];
+    let mut points = Vec::with_capacity(size);
+    let mut point = shift;
+    for _ in 0..size {
+        points.push(point);
+        point *= gen;
+    }
+    let mut denoms: Vec<E> = points.iter().map(|point| x - *point).collect();
+    if let Some(i) = denoms.iter().position(|denom| *denom == E::ZERO) {
+        return evals[i];
+    }
+    batch_inv(&mut denoms);
+    // With Z(x) = x^n - shift^n, the Lagrange basis polynomial for x_i is
+    // Z(x) * x_i / (n * shift^n * (x - x_i)).
+    let shift_n = shift.pow(size);
+    let scale = coset_vanishing_eval(shift, po2, x)
+        * (E::from_subfield(&E::SubElem::from_u64(size as u64)) * shift_n).inv();
+    let sum = evals
+        .iter()
+        .zip(points)
+        .zip(denoms)
+        .fold(E::ZERO, |acc, ((eval, point), denom)| {
+            acc + *eval * point * denom
+        });
+    scale * sum
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use rand::{thread_rng, Rng};
+    use risc0_core::field::{
+        baby_bear::BabyBearExtElem, goldilocks::GoldilocksExtElem, Elem, ExtElem, RootsOfUnity,
+    };
+
+    use super::*;
+
+    fn random_poly<E: ExtElem>(rng: &mut impl Rng, len: usize) -> Vec<E> {
+        (0..len).map(|_| E::random(rng)).collect()
+    }
+
+    fn naive_mul<E: ExtElem>(a: &[E], b: &[E]) -> Vec<E> {
+        let mut out = vec![E::ZERO; a.len() + b.len() - 1];
+        for (i, x) in a.iter().enumerate() {
+            for (j, y) in b.iter().enumerate() {
+                out[i + j] += *x * *y;
+            }
+        }
+        out
+    }
+
+    // Checks the utilities against naive implementations on random inputs.
+    fn check_poly_utils<E>()
+    where
+        E: ExtElem,
+        E::SubElem: RootsOfUnity,
+    {
+        let mut rng = thread_rng();
+        // The sizes cover both the quadratic and the NTT-based algorithms.
+        for (a_len, b_len, xs_len) in [(1, 1, 1), (40, 5, 10), (100, 33, 64), (200, 150, 300)] {
+            let a: Vec<E> = random_poly(&mut rng, a_len);
+            let b: Vec<E> = random_poly(&mut rng, b_len);
+
+            // Multiplication
+            let prod = poly_mul(&a, &b);
+            assert_eq!(prod, naive_mul(&a, &b));
+
+            // Division: num = quot * den + rem, with rem shorter than den.
+            let (quot, rem) = poly_div_rem(&a, &b);
+            assert_eq!(rem.len(), b_len - 1);
+            let mut recon = if quot.is_empty() {
+                vec![E::ZERO; a_len]
+            } else {
+                naive_mul(&quot, &b)
+            };
+            recon.resize(a_len.max(recon.len()), E::ZERO);
+            for (r, x) in recon.iter_mut().zip(rem.iter()) {
+                *r += *x;
+            }
+            recon.truncate(a_len);
+            assert_eq!(recon, a);
+            let (quot, rem) = poly_div_rem(&prod, &b);
+            assert_eq!(quot, a);
+            assert!(rem.iter().all(|x| *x == E::ZERO));
+
+            // Multi-point evaluation
+            let xs: Vec<E> = random_poly(&mut rng, xs_len);
+            let expected: Vec<E> = xs.iter().map(|x| poly_eval(&a, *x)).collect();
+            assert_eq!(poly_eval_many(&a, &xs), expected);
+
+            // Vanishing polynomials
+            let roots_len = rng.gen_range(0..100);
+            let roots: Vec<E> = random_poly(&mut rng, roots_len);
+            let zeros = poly_from_roots(&roots);
+            assert_eq!(zeros.len(), roots.len() + 1);
+            for root in roots.iter() {
+                assert_eq!(poly_eval(&zeros, *root), E::ZERO);
+            }
+        }
+    }
+
+    fn check_coset_utils<E>()
+    where
+        E: ExtElem,
+        E::SubElem: RootsOfUnity,
+    {
+        let mut rng = thread_rng();
+        let shift = E::from_subfield(&E::SubElem::from_u64(3));
+        for po2 in [0, 1, 5, 8] {
+            let size = 1 << po2;
+            let gen = E::from_subfield(&E::SubElem::ROU_FWD[po2]);
+            let coset: Vec<E> = (0..size).map(|i| shift * gen.pow(i)).collect();
+
+            let vanishing = coset_vanishing_poly(shift, po2);
+            assert_eq!(vanishing, poly_from_roots(&coset));
+            let x = E::random(&mut rng);
+            assert_eq!(
+                coset_vanishing_eval(shift, po2, x),
+                poly_eval(&vanishing, x)
+            );
+
+            let coeffs: Vec<E> = random_poly(&mut rng, size);
+            let evals: Vec<E> = coset.iter().map(|x| poly_eval(&coeffs, *x)).collect();
+            assert_eq!(barycentric_eval(&evals, shift, x), poly_eval(&coeffs, x));
+            for (point, eval) in coset.iter().zip(evals.iter()) {
+                assert_eq!(barycentric_eval(&evals, shift, *point), *eval);
+            }
+        }
+    }
+
+    #[test]
+    fn baby_bear_poly_utils() {
+        check_poly_utils::<BabyBearExtElem>();
+        check_coset_utils::<BabyBearExtElem>();
+    }
+
+    #[test]
+    fn goldilocks_poly_utils() {
+        check_poly_utils::<GoldilocksExtElem>();
+        check_coset_utils::<GoldilocksExtElem>();
+    }
+
+    #[test]
+    fn divide_by_linear() {
+        // The general division agrees with the in-place division by (x - z).
+        let mut rng = thread_rng();
+        let mut p: Vec<BabyBearExtElem> = random_poly(&mut rng, 50);
+        let z = BabyBearExtElem::random(&mut rng);
+        let (quot, rem) = poly_div_rem(&p, &[-z, BabyBearExtElem::ONE]);
+        let expected_rem = poly_divide(&mut p, z);
+        assert_eq!(rem, vec![expected_rem]);
+        assert_eq!(quot[..], p[..49]);
+    }
+}
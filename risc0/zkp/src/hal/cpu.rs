@@ -24,10 +24,15 @@ use std::{cell::RefCell, rc::Rc};
 use bytemuck::Pod;
 use ndarray::{ArrayView, ArrayViewMut, Axis};
 use rayon::prelude::*;
-use risc0_core::field::{baby_bear::BabyBear, Elem, ExtElem, Field};
+use risc0_core::field::{
+    baby_bear::{BabyBear, BabyBearElem, BabyBearExtElem},
+    goldilocks::Goldilocks,
+    Elem, ExtElem, Field, RootsOfUnity,
+};
 
-use super::{Buffer, Hal};
+use super::{simd, Buffer, EvalCheck, Hal};
 use crate::{
+    adapter::{PolyFp, REGISTER_GROUP_ACCUM, REGISTER_GROUP_CODE, REGISTER_GROUP_DATA},
     core::{
         blake2b::HashSuiteBlake2bCpu,
         config::{ConfigHash, HashSuite, HashSuitePoseidon, HashSuiteSha256},
@@ -36,25 +41,47 @@ use crate::{
         ntt::{bit_rev_32, bit_reverse, evaluate_ntt, expand, interpolate_ntt},
         sha_cpu,
     },
-    FRI_FOLD,
+    INV_RATE,
 };
 
 pub struct CpuHal<F: Field, HS: HashSuite<F>> {
+    simd: bool,
     phantom: PhantomData<(F, HS)>,
 }
 
 pub type BabyBearSha256CpuHal = CpuHal<BabyBear, HashSuiteSha256<BabyBear, sha_cpu::Impl>>;
 pub type BabyBearPoseidonCpuHal = CpuHal<BabyBear, HashSuitePoseidon>;
 pub type BabyBearBlake2bCpuHal = CpuHal<BabyBear, HashSuiteBlake2bCpu>;
+pub type GoldilocksSha256CpuHal = CpuHal<Goldilocks, HashSuiteSha256<Goldilocks, sha_cpu::Impl>>;
 
 impl<F: Field, HS: HashSuite<F>> CpuHal<F, HS> {
+    /// Creates a CPU HAL, using vectorized BabyBear arithmetic if the CPU
+    /// supports it.
     pub fn new() -> Self {
         CpuHal {
+            simd: simd::available(),
             phantom: PhantomData,
         }
     }
+
+    /// Enables or disables vectorized arithmetic. Vectorized kernels are only
+    /// available for [BabyBear] on x86_64 CPUs with AVX2, and are never used
+    /// otherwise.
+    ///
+    /// Both modes produce identical results; the scalar mode is mostly useful
+    /// for testing and benchmarking.
+    pub fn with_simd(self, simd: bool) -> Self {
+        Self {
+            simd: simd && simd::available(),
+            ..self
+        }
+    }
 }
 
+/// The number of elements processed by each task in vectorized element-wise
+/// operations.
+const SIMD_CHUNK: usize = 1 << 14;
+
 #[derive(Debug, Clone)]
 struct Region(usize, usize);
 
@@ -304,11 +331,19 @@ impl<F: Field, HS: HashSuite<F>> Hal for CpuHal<F, HS> {
     fn batch_evaluate_ntt(&self, io: &Self::BufferElem, count: usize, expand_bits: usize) {
         let row_size = io.size() / count;
         assert_eq!(row_size * count, io.size());
-        io.as_slice_mut()
-            .par_chunks_exact_mut(row_size)
-            .for_each(|row| {
-                evaluate_ntt::<Self::Elem, Self::Elem>(row, expand_bits);
-            });
+        let mut io = io.as_slice_mut();
+        if self.simd {
+            if let Some(io) = simd::cast_if_mut::<_, BabyBearElem>(&mut io) {
+                let twiddles = simd::NttTwiddles::new(log2_ceil(row_size));
+                io.par_chunks_exact_mut(row_size).for_each(|row| {
+                    simd::evaluate_ntt(row, expand_bits, &twiddles);
+                });
+                return;
+            }
+        }
+        io.par_chunks_exact_mut(row_size).for_each(|row| {
+            evaluate_ntt::<Self::Elem, Self::Elem>(row, expand_bits);
+        });
     }
 
     #[tracing::instrument(skip_all)]
@@ -415,6 +450,29 @@ impl<F: Field, HS: HashSuite<F>> Hal for CpuHal<F, HS> {
         let mix_pows: &[Self::ExtElem] = mix_pows.as_slice();
         let input: &[Self::Elem] = &input.as_slice();
 
+        if self.simd {
+            let mut output = output.as_slice_mut();
+            if let (Some(output), Some(mix_pows), Some(input)) = (
+                simd::cast_if_mut::<_, BabyBearExtElem>(&mut output),
+                simd::cast_if::<_, BabyBearExtElem>(mix_pows),
+                simd::cast_if::<_, BabyBearElem>(input),
+            ) {
+                output
+                    .par_chunks_exact_mut(count)
+                    .enumerate()
+                    .for_each(|(id, out_chunk)| {
+                        for i in 0..input_size {
+                            if combos[i] != id as u32 {
+                                continue;
+                            }
+                            let input = &input[count * i..count * (i + 1)];
+                            simd::mul_add_ext(out_chunk, &mix_pows[i], input);
+                        }
+                    });
+                return;
+            }
+        }
+
         output
             .as_slice_mut()
             .par_chunks_exact_mut(count)
@@ -443,6 +501,20 @@ impl<F: Field, HS: HashSuite<F>> Hal for CpuHal<F, HS> {
         let mut output = output.as_slice_mut();
         let input1 = input1.as_slice();
         let input2 = input2.as_slice();
+        if self.simd {
+            if let (Some(output), Some(input1), Some(input2)) = (
+                simd::cast_if_mut::<_, BabyBearElem>(&mut output),
+                simd::cast_if::<_, BabyBearElem>(&input1),
+                simd::cast_if::<_, BabyBearElem>(&input2),
+            ) {
+                output
+                    .par_chunks_mut(SIMD_CHUNK)
+                    .zip(input1.par_chunks(SIMD_CHUNK))
+                    .zip(input2.par_chunks(SIMD_CHUNK))
+                    .for_each(|((output, input1), input2)| simd::add(output, input1, input2));
+                return;
+            }
+        }
         (&mut output[..], &input1[..], &input2[..])
             .into_par_iter()
             .for_each(|(o, a, b)| {
@@ -456,6 +528,31 @@ impl<F: Field, HS: HashSuite<F>> Hal for CpuHal<F, HS> {
         let to_add = input.size() / count;
         assert_eq!(output.size(), count * Self::ExtElem::EXT_SIZE);
         assert_eq!(input.size(), count * to_add);
+        if self.simd {
+            let input = input.as_slice();
+            if let Some(input) = simd::cast_if::<_, BabyBearExtElem>(&input) {
+                // Sum the rows as flat vectors of words, then scatter the sums.
+                let input: &[BabyBearElem] = bytemuck::cast_slice(input);
+                let row_size = count * Self::ExtElem::EXT_SIZE;
+                let mut sums = vec![BabyBearElem::ZERO; row_size];
+                sums.par_chunks_mut(SIMD_CHUNK)
+                    .enumerate()
+                    .for_each(|(chunk, sums)| {
+                        let offset = chunk * SIMD_CHUNK;
+                        for row in input.chunks_exact(row_size) {
+                            simd::add_assign(sums, &row[offset..offset + sums.len()]);
+                        }
+                    });
+                let sums: &[Self::Elem] = simd::cast_if(&sums).unwrap();
+                let mut output = output.as_slice_mut();
+                for (idx, sum) in sums.chunks_exact(Self::ExtElem::EXT_SIZE).enumerate() {
+                    for i in 0..Self::ExtElem::EXT_SIZE {
+                        output[i * count + idx] = sum[i];
+                    }
+                }
+                return;
+            }
+        }
         let mut output = output.as_slice_mut();
         let mut output =
             ArrayViewMut::from_shape((Self::ExtElem::EXT_SIZE, count), &mut output).unwrap();
@@ -491,19 +588,34 @@ impl<F: Field, HS: HashSuite<F>> Hal for CpuHal<F, HS> {
     fn fri_fold(&self, output: &Self::BufferElem, input: &Self::BufferElem, mix: &Self::ExtElem) {
         let count = output.size() / Self::ExtElem::EXT_SIZE;
         assert_eq!(output.size(), count * Self::ExtElem::EXT_SIZE);
-        assert_eq!(input.size(), output.size() * FRI_FOLD);
+        // The folding factor is implied by the relative buffer sizes.
+        let fold = input.size() / output.size();
+        assert!(fold.is_power_of_two());
+        assert_eq!(input.size(), output.size() * fold);
+        let fold_po2 = log2_ceil(fold);
         let mut output = output.as_slice_mut();
         let input = input.as_slice();
 
+        if self.simd {
+            if let (Some(output), Some(input), Some(mix)) = (
+                simd::cast_if_mut::<_, BabyBearElem>(&mut output),
+                simd::cast_if::<_, BabyBearElem>(&input),
+                simd::cast_ref_if::<_, BabyBearExtElem>(mix),
+            ) {
+                simd::fri_fold(output, input, mix);
+                return;
+            }
+        }
+
         // TODO: parallelize
         for idx in 0..count {
             let mut tot = Self::ExtElem::ZERO;
             let mut cur_mix = Self::ExtElem::ONE;
-            for i in 0..FRI_FOLD {
-                let rev_i = bit_rev_32(i as u32) >> (32 - log2_ceil(FRI_FOLD));
+            for i in 0..fold {
+                let rev_i = bit_rev_32(i as u32) >> (32 - fold_po2);
                 let rev_idx = rev_i as usize * count + idx;
                 let factor = Self::ExtElem::from_subelems(
-                    (0..Self::ExtElem::EXT_SIZE).map(|i| input[i * count * FRI_FOLD + rev_idx]),
+                    (0..Self::ExtElem::EXT_SIZE).map(|i| input[i * count * fold + rev_idx]),
                 );
                 tot += cur_mix * factor;
                 cur_mix *= *mix;
@@ -542,12 +654,67 @@ impl<F: Field, HS: HashSuite<F>> Hal for CpuHal<F, HS> {
     }
 }
 
+/// Evaluates the check polynomial on the CPU for any circuit which implements
+/// [PolyFp], e.g. a hand-written circuit (see [crate::adapter]).
+pub struct CpuEvalCheck<'a, C> {
+    circuit: &'a C,
+}
+
+impl<'a, C> CpuEvalCheck<'a, C> {
+    pub fn new(circuit: &'a C) -> Self {
+        Self { circuit }
+    }
+}
+
+impl<'a, H, C> EvalCheck<H> for CpuEvalCheck<'a, C>
+where
+    H: Hal<BufferElem = CpuBuffer<<H as Hal>::Elem>>,
+    C: PolyFp<H::Field> + Sync,
+{
+    #[tracing::instrument(skip_all)]
+    fn eval_check(
+        &self,
+        check: &CpuBuffer<H::Elem>,
+        groups: &[&CpuBuffer<H::Elem>],
+        globals: &[&CpuBuffer<H::Elem>],
+        poly_mix: H::ExtElem,
+        po2: usize,
+        steps: usize,
+    ) {
+        const EXP_PO2: usize = log2_ceil(INV_RATE);
+        let domain = steps * INV_RATE;
+
+        let code = groups[REGISTER_GROUP_CODE].as_slice();
+        let data = groups[REGISTER_GROUP_DATA].as_slice();
+        let accum = groups[REGISTER_GROUP_ACCUM].as_slice();
+        // The globals are given by the prover as mix, then out.
+        let mix = globals[0].as_slice();
+        let out = globals[1].as_slice();
+        let args: &[&[H::Elem]] = &[&code, &out, &data, &mix, &accum];
+        let check = check.as_slice_sync();
+
+        (0..domain).into_par_iter().for_each(|cycle| {
+            let tot = self.circuit.poly_fp(cycle, domain, &poly_mix, args);
+            let x = H::Elem::ROU_FWD[po2 + EXP_PO2].pow(cycle);
+            // Undo the zk_shift of the coefficients, i.e. divide by the
+            // vanishing polynomial of the trace domain at 3 * x.
+            let y = (H::Elem::from_u64(3) * x).pow(1 << po2);
+            let ret = tot * (y - H::Elem::ONE).inv();
+            for (i, elem) in ret.subelems().iter().enumerate() {
+                check.set(i * domain + cycle, *elem);
+            }
+        });
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use hex::FromHex;
     use rand::thread_rng;
+    use risc0_core::field::goldilocks::GoldilocksElem;
 
     use super::*;
+    use crate::hal::{dual::DualHal, testutil};
 
     #[test]
     #[should_panic]
@@ -572,6 +739,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn goldilocks_fp() {
+        let hal = GoldilocksSha256CpuHal::new();
+        const COUNT: usize = 1024;
+        test_binary(
+            &hal,
+            |o, a, b| {
+                hal.eltwise_add_elem(o, a, b);
+            },
+            |a, b| *a + *b,
+            COUNT,
+        );
+    }
+
+    #[test]
+    fn goldilocks_ntt_round_trip() {
+        let hal = GoldilocksSha256CpuHal::new();
+        let mut rng = thread_rng();
+        let count = 3;
+        let size = 1 << 10;
+        let values: Vec<_> = (0..count * size)
+            .map(|_| GoldilocksElem::random(&mut rng))
+            .collect();
+        let buf = hal.copy_from_elem("buf", &values);
+        hal.batch_interpolate_ntt(&buf, count);
+        buf.view(|coeffs| assert_ne!(coeffs, &values[..]));
+        hal.batch_evaluate_ntt(&buf, count, 0);
+        buf.view(|evals| assert_eq!(evals, &values[..]));
+    }
+
     fn test_binary<H, HF, CF>(hal: &H, hal_fn: HF, cpu_fn: CF, count: usize)
     where
         H: Hal,
@@ -614,6 +811,40 @@ mod tests {
         });
     }
 
+    fn scalar_hal() -> BabyBearSha256CpuHal {
+        CpuHal::new().with_simd(false)
+    }
+
+    #[test]
+    fn simd_batch_evaluate_ntt() {
+        let hal = BabyBearSha256CpuHal::new();
+        testutil::batch_evaluate_ntt(DualHal::new(&hal, &scalar_hal()));
+    }
+
+    #[test]
+    fn simd_eltwise_add_elem() {
+        let hal = BabyBearSha256CpuHal::new();
+        testutil::eltwise_add_elem(DualHal::new(&hal, &scalar_hal()));
+    }
+
+    #[test]
+    fn simd_eltwise_sum_extelem() {
+        let hal = BabyBearSha256CpuHal::new();
+        testutil::eltwise_sum_extelem(DualHal::new(&hal, &scalar_hal()));
+    }
+
+    #[test]
+    fn simd_fri_fold() {
+        let hal = BabyBearSha256CpuHal::new();
+        testutil::fri_fold(DualHal::new(&hal, &scalar_hal()));
+    }
+
+    #[test]
+    fn simd_mix_poly_coeffs() {
+        let hal = BabyBearSha256CpuHal::new();
+        testutil::mix_poly_coeffs(DualHal::new(&hal, &scalar_hal()));
+    }
+
     #[test]
     fn hash_rows() {
         do_hash_rows(
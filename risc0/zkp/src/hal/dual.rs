@@ -12,37 +12,217 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use core::marker::PhantomData;
+//! A [Hal] which runs every operation on two [Hal]s and compares the results.
+//!
+//! This is used to develop a new HAL backend against a reference, usually
+//! the [CpuHal](super::cpu::CpuHal). By default, the first divergence
+//! panics; see [DivergenceMode] for the alternatives.
+
+use core::{fmt, marker::PhantomData};
+use std::sync::{Arc, Mutex};
 
 use super::{Buffer, EvalCheck, Hal};
 use crate::core::sha::Digest;
 
+/// What a [DualHal] does when the two HALs produce different results.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DivergenceMode {
+    /// Panic with a description of the divergence.
+    #[default]
+    Panic,
+
+    /// Record the divergence and keep running. The second HAL's output is
+    /// then overwritten with the first HAL's, so that each later divergence
+    /// is caused by the operation it is reported for.
+    Record,
+
+    /// Record the first divergence, and from then on only run the first
+    /// HAL.
+    Stop,
+}
+
+/// The first point at which the outputs of an operation differed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// The position of the operation among all operations run by the
+    /// [DualHal], starting at zero.
+    pub op_index: usize,
+
+    /// The name of the operation, e.g. `batch_evaluate_ntt`.
+    pub op: &'static str,
+
+    /// The name the buffer was allocated with.
+    pub buffer: &'static str,
+
+    /// The index of the first element which differs.
+    pub index: usize,
+
+    /// The number of elements which differ.
+    pub count: usize,
+
+    /// The value from the first HAL, or `None` if its buffer is too short.
+    pub lhs: Option<String>,
+
+    /// The value from the second HAL, or `None` if its buffer is too short.
+    pub rhs: Option<String>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = |value: &Option<String>| value.clone().unwrap_or_else(|| "<none>".into());
+        write!(
+            f,
+            "op #{} {}: buffer '{}' differs at index {} ({} elements differ): {} != {}",
+            self.op_index,
+            self.op,
+            self.buffer,
+            self.index,
+            self.count,
+            value(&self.lhs),
+            value(&self.rhs)
+        )
+    }
+}
+
+/// The result of comparing two HALs with a [DualHal].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DivergenceReport {
+    /// The number of operations run.
+    pub ops: usize,
+
+    /// The divergences found, in the order they occurred.
+    pub divergences: Vec<Divergence>,
+
+    /// Whether comparison stopped after the first divergence.
+    pub stopped: bool,
+}
+
+impl DivergenceReport {
+    /// Returns true if no divergence was found.
+    pub fn is_ok(&self) -> bool {
+        self.divergences.is_empty()
+    }
+
+    /// The first divergence found, if any.
+    pub fn first(&self) -> Option<&Divergence> {
+        self.divergences.first()
+    }
+}
+
+impl fmt::Display for DivergenceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ops, {} divergences",
+            self.ops,
+            self.divergences.len()
+        )?;
+        if self.stopped {
+            write!(f, " (stopped)")?;
+        }
+        for divergence in self.divergences.iter() {
+            write!(f, "\n  {divergence}")?;
+        }
+        Ok(())
+    }
+}
+
+// Shared by a DualHal and all of its buffers.
+#[derive(Debug, Default)]
+struct Comparator {
+    mode: DivergenceMode,
+    report: Mutex<DivergenceReport>,
+}
+
+impl Comparator {
+    fn stopped(&self) -> bool {
+        self.report.lock().unwrap().stopped
+    }
+
+    // Counts an operation, and returns whether the second HAL should run it.
+    fn begin(&self) -> bool {
+        let mut report = self.report.lock().unwrap();
+        report.ops += 1;
+        !report.stopped
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct BufferImpl<T, U: Buffer<T>, V: Buffer<T>> {
     buf1: U,
     buf2: V,
+    name: &'static str,
+    comparator: Arc<Comparator>,
     marker: PhantomData<T>,
 }
 
 impl<T, U: Buffer<T>, V: Buffer<T>> BufferImpl<T, U, V>
 where
-    T: std::fmt::Debug + PartialEq,
+    T: Clone + fmt::Debug + PartialEq,
 {
-    pub fn new(buf1: U, buf2: V) -> Self {
+    fn new(buf1: U, buf2: V, name: &'static str, comparator: &Arc<Comparator>) -> Self {
         Self {
             buf1,
             buf2,
+            name,
+            comparator: comparator.clone(),
             marker: PhantomData,
         }
     }
 
-    fn assert_eq(&self) {
+    /// Compares the contents of both buffers after the operation `op`, and
+    /// handles any difference according to the [DivergenceMode].
+    fn compare(&self, op: &'static str) {
+        let comparator = &self.comparator;
+        if comparator.stopped() {
+            return;
+        }
+        let mut divergence = None;
         self.buf1.view(|a| {
             self.buf2.view(|b| {
-                assert_eq!(a.len(), b.len());
-                assert_eq!(a, b);
+                let count =
+                    a.iter().zip(b).filter(|(a, b)| a != b).count() + a.len().abs_diff(b.len());
+                if count == 0 {
+                    return;
+                }
+                let index = a
+                    .iter()
+                    .zip(b)
+                    .position(|(a, b)| a != b)
+                    .unwrap_or_else(|| a.len().min(b.len()));
+                divergence = Some(Divergence {
+                    op_index: 0,
+                    op,
+                    buffer: self.name,
+                    index,
+                    count,
+                    lhs: a.get(index).map(|x| format!("{x:?}")),
+                    rhs: b.get(index).map(|x| format!("{x:?}")),
+                });
             });
-        })
+        });
+        let mut divergence = match divergence {
+            Some(divergence) => divergence,
+            None => return,
+        };
+        {
+            let mut report = comparator.report.lock().unwrap();
+            divergence.op_index = report.ops - 1;
+            report.divergences.push(divergence.clone());
+            match comparator.mode {
+                DivergenceMode::Panic => {
+                    drop(report);
+                    panic!("DualHal divergence: {divergence}");
+                }
+                DivergenceMode::Record => {}
+                DivergenceMode::Stop => {
+                    report.stopped = true;
+                    return;
+                }
+            }
+        }
+        self.buf2
+            .view_mut(|dst| self.buf1.view(|src| dst.clone_from_slice(src)));
     }
 }
 
@@ -60,7 +240,7 @@ where
     fn slice(&self, offset: usize, size: usize) -> Self {
         let lhs = self.buf1.slice(offset, size);
         let rhs = self.buf2.slice(offset, size);
-        BufferImpl::new(lhs, rhs)
+        BufferImpl::new(lhs, rhs, self.name, &self.comparator)
     }
 
     fn view<F: FnOnce(&[T])>(&self, f: F) {
@@ -78,11 +258,34 @@ where
 pub struct DualHal<'a, U: Hal, V: Hal> {
     hal1: &'a U,
     hal2: &'a V,
+    comparator: Arc<Comparator>,
 }
 
 impl<'a, U: Hal, V: Hal> DualHal<'a, U, V> {
     pub fn new(hal1: &'a U, hal2: &'a V) -> Self {
-        Self { hal1, hal2 }
+        Self {
+            hal1,
+            hal2,
+            comparator: Arc::new(Comparator::default()),
+        }
+    }
+
+    /// Sets what happens when the HALs diverge. This must be called before
+    /// any buffers are allocated.
+    pub fn with_divergence_mode(self, mode: DivergenceMode) -> Self {
+        assert_eq!(Arc::strong_count(&self.comparator), 1);
+        Self {
+            comparator: Arc::new(Comparator {
+                mode,
+                ..Default::default()
+            }),
+            ..self
+        }
+    }
+
+    /// Returns the divergences found so far.
+    pub fn report(&self) -> DivergenceReport {
+        self.comparator.report.lock().unwrap().clone()
     }
 }
 
@@ -111,25 +314,25 @@ where
     fn alloc_digest(&self, name: &'static str, size: usize) -> Self::BufferDigest {
         let buf1 = self.hal1.alloc_digest(name, size);
         let buf2 = self.hal2.alloc_digest(name, size);
-        BufferImpl::new(buf1, buf2)
+        BufferImpl::new(buf1, buf2, name, &self.comparator)
     }
 
     fn alloc_elem(&self, name: &'static str, size: usize) -> Self::BufferElem {
         let buf1 = self.hal1.alloc_elem(name, size);
         let buf2 = self.hal2.alloc_elem(name, size);
-        BufferImpl::new(buf1, buf2)
+        BufferImpl::new(buf1, buf2, name, &self.comparator)
     }
 
     fn alloc_extelem(&self, name: &'static str, size: usize) -> Self::BufferExtElem {
         let buf1 = self.hal1.alloc_extelem(name, size);
         let buf2 = self.hal2.alloc_extelem(name, size);
-        BufferImpl::new(buf1, buf2)
+        BufferImpl::new(buf1, buf2, name, &self.comparator)
     }
 
     fn alloc_u32(&self, name: &'static str, size: usize) -> Self::BufferU32 {
         let buf1 = self.hal1.alloc_u32(name, size);
         let buf2 = self.hal2.alloc_u32(name, size);
-        BufferImpl::new(buf1, buf2)
+        BufferImpl::new(buf1, buf2, name, &self.comparator)
     }
 
     fn copy_from_digest(
@@ -139,13 +342,13 @@ where
     ) -> Self::BufferDigest {
         let buf1 = self.hal1.copy_from_digest(name, slice);
         let buf2 = self.hal2.copy_from_digest(name, slice);
-        BufferImpl::new(buf1, buf2)
+        BufferImpl::new(buf1, buf2, name, &self.comparator)
     }
 
     fn copy_from_elem(&self, name: &'static str, slice: &[Self::Elem]) -> Self::BufferElem {
         let buf1 = self.hal1.copy_from_elem(name, slice);
         let buf2 = self.hal2.copy_from_elem(name, bytemuck::cast_slice(slice));
-        BufferImpl::new(buf1, buf2)
+        BufferImpl::new(buf1, buf2, name, &self.comparator)
     }
 
     fn copy_from_extelem(
@@ -157,37 +360,45 @@ where
         let buf2 = self
             .hal2
             .copy_from_extelem(name, bytemuck::cast_slice(slice));
-        BufferImpl::new(buf1, buf2)
+        BufferImpl::new(buf1, buf2, name, &self.comparator)
     }
 
     fn copy_from_u32(&self, name: &'static str, slice: &[u32]) -> Self::BufferU32 {
         let buf1 = self.hal1.copy_from_u32(name, slice);
         let buf2 = self.hal2.copy_from_u32(name, slice);
-        BufferImpl::new(buf1, buf2)
+        BufferImpl::new(buf1, buf2, name, &self.comparator)
     }
 
     fn batch_expand(&self, output: &Self::BufferElem, input: &Self::BufferElem, count: usize) {
         self.hal1.batch_expand(&output.buf1, &input.buf1, count);
-        self.hal2.batch_expand(&output.buf2, &input.buf2, count);
-        output.assert_eq();
+        if self.comparator.begin() {
+            self.hal2.batch_expand(&output.buf2, &input.buf2, count);
+        }
+        output.compare("batch_expand");
     }
 
     fn batch_evaluate_ntt(&self, io: &Self::BufferElem, count: usize, expand_bits: usize) {
         self.hal1.batch_evaluate_ntt(&io.buf1, count, expand_bits);
-        self.hal2.batch_evaluate_ntt(&io.buf2, count, expand_bits);
-        io.assert_eq();
+        if self.comparator.begin() {
+            self.hal2.batch_evaluate_ntt(&io.buf2, count, expand_bits);
+        }
+        io.compare("batch_evaluate_ntt");
     }
 
     fn batch_interpolate_ntt(&self, io: &Self::BufferElem, count: usize) {
         self.hal1.batch_interpolate_ntt(&io.buf1, count);
-        self.hal2.batch_interpolate_ntt(&io.buf2, count);
-        io.assert_eq();
+        if self.comparator.begin() {
+            self.hal2.batch_interpolate_ntt(&io.buf2, count);
+        }
+        io.compare("batch_interpolate_ntt");
     }
 
     fn batch_bit_reverse(&self, io: &Self::BufferElem, count: usize) {
         self.hal1.batch_bit_reverse(&io.buf1, count);
-        self.hal2.batch_bit_reverse(&io.buf2, count);
-        io.assert_eq();
+        if self.comparator.begin() {
+            self.hal2.batch_bit_reverse(&io.buf2, count);
+        }
+        io.compare("batch_bit_reverse");
     }
 
     fn batch_evaluate_any(
@@ -209,20 +420,30 @@ where
             out.size()
         );
 
-        coeffs.assert_eq();
-        which.assert_eq();
-        xs.assert_eq();
+        self.comparator.begin();
+        coeffs.compare("batch_evaluate_any");
+        which.compare("batch_evaluate_any");
+        xs.compare("batch_evaluate_any");
         self.hal1
             .batch_evaluate_any(&coeffs.buf1, poly_count, &which.buf1, &xs.buf1, &out.buf1);
-        self.hal2
-            .batch_evaluate_any(&coeffs.buf2, poly_count, &which.buf2, &xs.buf2, &out.buf2);
-        out.assert_eq();
+        if !self.comparator.stopped() {
+            self.hal2.batch_evaluate_any(
+                &coeffs.buf2,
+                poly_count,
+                &which.buf2,
+                &xs.buf2,
+                &out.buf2,
+            );
+        }
+        out.compare("batch_evaluate_any");
     }
 
     fn zk_shift(&self, io: &Self::BufferElem, count: usize) {
         self.hal1.zk_shift(&io.buf1, count);
-        self.hal2.zk_shift(&io.buf2, count);
-        io.assert_eq();
+        if self.comparator.begin() {
+            self.hal2.zk_shift(&io.buf2, count);
+        }
+        io.compare("zk_shift");
     }
 
     fn mix_poly_coeffs(
@@ -244,16 +465,18 @@ where
             input_size,
             count,
         );
-        self.hal2.mix_poly_coeffs(
-            &out.buf2,
-            bytemuck::cast_ref(mix_start),
-            bytemuck::cast_ref(mix),
-            &input.buf2,
-            &combos.buf2,
-            input_size,
-            count,
-        );
-        out.assert_eq();
+        if self.comparator.begin() {
+            self.hal2.mix_poly_coeffs(
+                &out.buf2,
+                bytemuck::cast_ref(mix_start),
+                bytemuck::cast_ref(mix),
+                &input.buf2,
+                &combos.buf2,
+                input_size,
+                count,
+            );
+        }
+        out.compare("mix_poly_coeffs");
     }
 
     fn eltwise_add_elem(
@@ -264,43 +487,54 @@ where
     ) {
         self.hal1
             .eltwise_add_elem(&output.buf1, &input1.buf1, &input2.buf1);
-        self.hal2
-            .eltwise_add_elem(&output.buf2, &input1.buf2, &input2.buf2);
-        output.assert_eq();
+        if self.comparator.begin() {
+            self.hal2
+                .eltwise_add_elem(&output.buf2, &input1.buf2, &input2.buf2);
+        }
+        output.compare("eltwise_add_elem");
     }
 
     fn eltwise_sum_extelem(&self, output: &Self::BufferElem, input: &Self::BufferExtElem) {
         self.hal1.eltwise_sum_extelem(&output.buf1, &input.buf1);
-        self.hal2.eltwise_sum_extelem(&output.buf2, &input.buf2);
-        output.assert_eq();
+        if self.comparator.begin() {
+            self.hal2.eltwise_sum_extelem(&output.buf2, &input.buf2);
+        }
+        output.compare("eltwise_sum_extelem");
     }
 
     fn eltwise_copy_elem(&self, output: &Self::BufferElem, input: &Self::BufferElem) {
         self.hal1.eltwise_copy_elem(&output.buf1, &input.buf1);
-        self.hal2.eltwise_copy_elem(&output.buf2, &input.buf2);
-        output.assert_eq();
+        if self.comparator.begin() {
+            self.hal2.eltwise_copy_elem(&output.buf2, &input.buf2);
+        }
+        output.compare("eltwise_copy_elem");
     }
 
     fn fri_fold(&self, output: &Self::BufferElem, input: &Self::BufferElem, mix: &Self::ExtElem) {
         self.hal1.fri_fold(&output.buf1, &input.buf1, mix);
-        self.hal2
-            .fri_fold(&output.buf2, &input.buf2, bytemuck::cast_ref(mix));
-        output.assert_eq();
+        if self.comparator.begin() {
+            self.hal2
+                .fri_fold(&output.buf2, &input.buf2, bytemuck::cast_ref(mix));
+        }
+        output.compare("fri_fold");
     }
 
     fn hash_rows(&self, output: &Self::BufferDigest, matrix: &Self::BufferElem) {
         self.hal1.hash_rows(&output.buf1, &matrix.buf1);
-        self.hal2.hash_rows(&output.buf2, &matrix.buf2);
-        output.assert_eq();
+        if self.comparator.begin() {
+            self.hal2.hash_rows(&output.buf2, &matrix.buf2);
+        }
+        output.compare("hash_rows");
     }
 
     fn hash_fold(&self, io: &Self::BufferDigest, input_size: usize, output_size: usize) {
         self.hal1.hash_fold(&io.buf1, input_size, output_size);
-        self.hal2.hash_fold(&io.buf2, input_size, output_size);
-        io.assert_eq();
+        if self.comparator.begin() {
+            self.hal2.hash_fold(&io.buf2, input_size, output_size);
+        }
+        io.compare("hash_fold");
     }
 }
-
 pub struct DualEvalCheck<'a, H1: Hal, H2: Hal, E1: EvalCheck<H1>, E2: EvalCheck<H2>> {
     eval1: &'a E1,
     eval2: &'a E2,
@@ -354,14 +588,95 @@ where
             po2,
             steps,
         );
-        self.eval2.eval_check(
-            &check.buf2,
-            groups2.as_slice(),
-            globals2.as_slice(),
-            bytemuck::cast(poly_mix),
-            po2,
-            steps,
+        if check.comparator.begin() {
+            self.eval2.eval_check(
+                &check.buf2,
+                groups2.as_slice(),
+                globals2.as_slice(),
+                bytemuck::cast(poly_mix),
+                po2,
+                steps,
+            );
+        }
+        check.compare("eval_check");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::cpu::BabyBearSha256CpuHal;
+
+    type CpuDualHal<'a> = DualHal<'a, BabyBearSha256CpuHal, BabyBearSha256CpuHal>;
+
+    // Runs two operations, corrupting the second HAL's input of the first.
+    fn diverge(hal: &CpuDualHal) -> Vec<u32> {
+        let data: Vec<_> = (0..16u32).map(Into::into).collect();
+        let io = hal.copy_from_elem("io", &data);
+        io.buf2.view_mut(|io| io[5] += 1u32.into());
+        hal.batch_bit_reverse(&io, 1);
+        hal.batch_interpolate_ntt(&io, 1);
+        let mut out = Vec::new();
+        io.view(|io| out.extend(io.iter().map(|x| x.as_u32())));
+        out
+    }
+
+    fn expected() -> Divergence {
+        Divergence {
+            op_index: 0,
+            op: "batch_bit_reverse",
+            buffer: "io",
+            index: 10,
+            count: 1,
+            lhs: Some("5".into()),
+            rhs: Some("6".into()),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "op #0 batch_bit_reverse: buffer 'io' differs at index 10")]
+    fn panic_mode() {
+        let hal = BabyBearSha256CpuHal::new();
+        diverge(&DualHal::new(&hal, &hal));
+    }
+
+    #[test]
+    fn record_mode() {
+        let hal = BabyBearSha256CpuHal::new();
+        let dual = DualHal::new(&hal, &hal).with_divergence_mode(DivergenceMode::Record);
+        diverge(&dual);
+        // The second operation runs on resynchronized inputs.
+        let report = dual.report();
+        assert_eq!(report.ops, 2);
+        assert_eq!(report.divergences, vec![expected()]);
+        assert!(!report.stopped);
+        assert!(!report.is_ok());
+    }
+
+    #[test]
+    fn stop_mode() {
+        let hal = BabyBearSha256CpuHal::new();
+        let reference =
+            diverge(&DualHal::new(&hal, &hal).with_divergence_mode(DivergenceMode::Record));
+        let dual = DualHal::new(&hal, &hal).with_divergence_mode(DivergenceMode::Stop);
+        assert_eq!(diverge(&dual), reference);
+        let report = dual.report();
+        assert_eq!(report.ops, 2);
+        assert_eq!(report.first(), Some(&expected()));
+        assert_eq!(report.divergences.len(), 1);
+        assert!(report.stopped);
+        assert_eq!(
+            report.to_string(),
+            "2 ops, 1 divergences (stopped)\n  op #0 batch_bit_reverse: buffer 'io' differs at index 10 (1 elements differ): 5 != 6"
         );
-        check.assert_eq();
+    }
+
+    #[test]
+    fn no_divergence() {
+        let hal = BabyBearSha256CpuHal::new();
+        let dual = DualHal::new(&hal, &hal).with_divergence_mode(DivergenceMode::Record);
+        let io = dual.alloc_elem("io", 16);
+        dual.batch_bit_reverse(&io, 1);
+        assert!(dual.report().is_ok());
     }
 }
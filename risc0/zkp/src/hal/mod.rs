@@ -20,6 +20,8 @@ pub mod cuda;
 pub mod dual;
 #[cfg(feature = "metal")]
 pub mod metal;
+pub mod profiling;
+mod simd;
 
 use risc0_core::field::{Elem, ExtElem, Field, RootsOfUnity};
 
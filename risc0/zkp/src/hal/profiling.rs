@@ -0,0 +1,564 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A [Hal] which wraps another [Hal] and records how long each operation
+//! takes, how much data it touches, and how much memory is held in buffers.
+//!
+//! Timings are measured on the host around each call. Backends which queue
+//! work asynchronously may attribute some time to a later operation.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    marker::PhantomData,
+    mem::size_of,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "profile_json")]
+use serde::Serialize;
+
+use super::{Buffer, EvalCheck, Hal};
+use crate::core::digest::Digest;
+
+#[derive(Default)]
+struct State {
+    ops: BTreeMap<&'static str, OpStats>,
+    buffers: BTreeMap<&'static str, BufferStats>,
+    live_bytes: usize,
+    peak_live_bytes: usize,
+}
+
+impl State {
+    fn alloc(&mut self, name: &'static str, bytes: usize) {
+        let stats = self.buffers.entry(name).or_default();
+        stats.count += 1;
+        stats.live_bytes += bytes;
+        stats.peak_live_bytes = stats.peak_live_bytes.max(stats.live_bytes);
+        self.live_bytes += bytes;
+        self.peak_live_bytes = self.peak_live_bytes.max(self.live_bytes);
+    }
+
+    fn free(&mut self, name: &'static str, bytes: usize) {
+        self.buffers.get_mut(name).unwrap().live_bytes -= bytes;
+        self.live_bytes -= bytes;
+    }
+}
+
+/// Statistics for a single [Hal] operation.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "profile_json", derive(Serialize))]
+pub struct OpStats {
+    /// The number of calls.
+    pub calls: u64,
+
+    /// The total wall time spent in calls, in nanoseconds.
+    pub nanos: u64,
+
+    /// The total number of elements in the buffers passed to the calls.
+    pub elements: u64,
+
+    /// The total number of bytes allocated by the calls.
+    pub bytes_allocated: u64,
+}
+
+impl OpStats {
+    /// The total wall time spent in calls.
+    pub fn time(&self) -> Duration {
+        Duration::from_nanos(self.nanos)
+    }
+}
+
+/// Statistics for the buffers allocated with a given name.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "profile_json", derive(Serialize))]
+pub struct BufferStats {
+    /// The number of buffers allocated.
+    pub count: u64,
+
+    /// The number of bytes currently held by live buffers.
+    pub live_bytes: usize,
+
+    /// The largest number of bytes held by live buffers at once.
+    pub peak_live_bytes: usize,
+}
+
+/// A snapshot of the statistics recorded by a [ProfilingHal].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "profile_json", derive(Serialize))]
+pub struct ProfileReport {
+    /// Statistics for each operation, by name.
+    pub ops: BTreeMap<&'static str, OpStats>,
+
+    /// Statistics for each buffer name.
+    pub buffers: BTreeMap<&'static str, BufferStats>,
+
+    /// The number of bytes currently held by live buffers.
+    pub live_bytes: usize,
+
+    /// The largest number of bytes held by live buffers at once.
+    pub peak_live_bytes: usize,
+}
+
+#[cfg(feature = "profile_json")]
+impl ProfileReport {
+    /// Serializes the report as JSON. Requires the `profile_json` feature.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+/// Formats the report as text tables, with the operations sorted by time.
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total: u64 = self.ops.values().map(|op| op.nanos).sum();
+        let mut ops: Vec<_> = self.ops.iter().collect();
+        ops.sort_by(|a, b| b.1.nanos.cmp(&a.1.nanos).then(a.0.cmp(b.0)));
+        writeln!(
+            f,
+            "{:<24} {:>8} {:>12} {:>7} {:>14} {:>14}",
+            "operation", "calls", "time (ms)", "time %", "elements", "allocated"
+        )?;
+        for (name, op) in ops {
+            let percent = if total == 0 {
+                0.0
+            } else {
+                op.nanos as f64 * 100.0 / total as f64
+            };
+            writeln!(
+                f,
+                "{:<24} {:>8} {:>12.3} {:>7.1} {:>14} {:>14}",
+                name,
+                op.calls,
+                op.nanos as f64 / 1e6,
+                percent,
+                op.elements,
+                op.bytes_allocated
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<24} {:>8} {:>14} {:>14}",
+            "buffer", "count", "live", "peak"
+        )?;
+        for (name, buf) in self.buffers.iter() {
+            writeln!(
+                f,
+                "{:<24} {:>8} {:>14} {:>14}",
+                name, buf.count, buf.live_bytes, buf.peak_live_bytes
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "live bytes: {}", self.live_bytes)?;
+        write!(f, "peak live bytes: {}", self.peak_live_bytes)
+    }
+}
+
+// Shared by a buffer and all of its clones and slices, so the allocation is
+// released when the last of them is dropped.
+struct Allocation {
+    name: &'static str,
+    bytes: usize,
+    state: Arc<Mutex<State>>,
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        self.state.lock().unwrap().free(self.name, self.bytes);
+    }
+}
+
+#[derive(Clone)]
+pub struct ProfilingBuffer<T, B: Buffer<T>> {
+    buf: B,
+    alloc: Arc<Allocation>,
+    marker: PhantomData<T>,
+}
+
+impl<T, B: Buffer<T>> ProfilingBuffer<T, B> {
+    fn new(state: &Arc<Mutex<State>>, name: &'static str, buf: B) -> Self {
+        let bytes = buf.size() * size_of::<T>();
+        state.lock().unwrap().alloc(name, bytes);
+        Self {
+            buf,
+            alloc: Arc::new(Allocation {
+                name,
+                bytes,
+                state: state.clone(),
+            }),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Clone, B: Buffer<T>> Buffer<T> for ProfilingBuffer<T, B> {
+    fn size(&self) -> usize {
+        self.buf.size()
+    }
+
+    fn slice(&self, offset: usize, size: usize) -> Self {
+        Self {
+            buf: self.buf.slice(offset, size),
+            alloc: self.alloc.clone(),
+            marker: PhantomData,
+        }
+    }
+
+    fn view<F: FnOnce(&[T])>(&self, f: F) {
+        self.buf.view(f)
+    }
+
+    fn view_mut<F: FnOnce(&mut [T])>(&self, f: F) {
+        self.buf.view_mut(f)
+    }
+}
+
+/// A [Hal] which records call counts, wall time, input sizes and allocations
+/// for every operation of the wrapped [Hal], as well as the memory held by
+/// live buffers. See [ProfilingHal::report].
+pub struct ProfilingHal<H: Hal> {
+    hal: H,
+    state: Arc<Mutex<State>>,
+}
+
+impl<H: Hal> ProfilingHal<H> {
+    pub fn new(hal: H) -> Self {
+        Self {
+            hal,
+            state: Arc::new(Mutex::new(State::default())),
+        }
+    }
+
+    /// The wrapped [Hal].
+    pub fn inner(&self) -> &H {
+        &self.hal
+    }
+
+    /// Returns the statistics recorded so far.
+    pub fn report(&self) -> ProfileReport {
+        let state = self.state.lock().unwrap();
+        ProfileReport {
+            ops: state.ops.clone(),
+            buffers: state.buffers.clone(),
+            live_bytes: state.live_bytes,
+            peak_live_bytes: state.peak_live_bytes,
+        }
+    }
+
+    /// Clears the recorded operation statistics and buffer peaks. Buffers
+    /// which are still live remain accounted for.
+    pub fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.ops.clear();
+        for buf in state.buffers.values_mut() {
+            buf.count = 0;
+            buf.peak_live_bytes = buf.live_bytes;
+        }
+        state.buffers.retain(|_, buf| buf.live_bytes > 0);
+        state.peak_live_bytes = state.live_bytes;
+    }
+
+    fn record<R>(&self, op: &'static str, elements: usize, f: impl FnOnce() -> R) -> R {
+        let start = Instant::now();
+        let result = f();
+        let nanos = start.elapsed().as_nanos() as u64;
+        let mut state = self.state.lock().unwrap();
+        let stats = state.ops.entry(op).or_default();
+        stats.calls += 1;
+        stats.nanos += nanos;
+        stats.elements += elements as u64;
+        result
+    }
+
+    fn record_alloc<T, B: Buffer<T>>(
+        &self,
+        op: &'static str,
+        name: &'static str,
+        f: impl FnOnce() -> B,
+    ) -> ProfilingBuffer<T, B> {
+        let buf = self.record(op, 0, f);
+        let size = buf.size();
+        {
+            let mut state = self.state.lock().unwrap();
+            let stats = state.ops.get_mut(op).unwrap();
+            stats.elements += size as u64;
+            stats.bytes_allocated += (size * size_of::<T>()) as u64;
+        }
+        ProfilingBuffer::new(&self.state, name, buf)
+    }
+}
+
+impl<H: Hal> Hal for ProfilingHal<H> {
+    type Elem = H::Elem;
+    type ExtElem = H::ExtElem;
+    type Field = H::Field;
+    type BufferDigest = ProfilingBuffer<Digest, H::BufferDigest>;
+    type BufferElem = ProfilingBuffer<Self::Elem, H::BufferElem>;
+    type BufferExtElem = ProfilingBuffer<Self::ExtElem, H::BufferExtElem>;
+    type BufferU32 = ProfilingBuffer<u32, H::BufferU32>;
+    type HashSuite = H::HashSuite;
+    type Hash = H::Hash;
+    type Rng = H::Rng;
+
+    fn alloc_digest(&self, name: &'static str, size: usize) -> Self::BufferDigest {
+        self.record_alloc("alloc_digest", name, || self.hal.alloc_digest(name, size))
+    }
+
+    fn alloc_elem(&self, name: &'static str, size: usize) -> Self::BufferElem {
+        self.record_alloc("alloc_elem", name, || self.hal.alloc_elem(name, size))
+    }
+
+    fn alloc_extelem(&self, name: &'static str, size: usize) -> Self::BufferExtElem {
+        self.record_alloc("alloc_extelem", name, || self.hal.alloc_extelem(name, size))
+    }
+
+    fn alloc_u32(&self, name: &'static str, size: usize) -> Self::BufferU32 {
+        self.record_alloc("alloc_u32", name, || self.hal.alloc_u32(name, size))
+    }
+
+    fn copy_from_digest(&self, name: &'static str, slice: &[Digest]) -> Self::BufferDigest {
+        self.record_alloc("copy_from_digest", name, || {
+            self.hal.copy_from_digest(name, slice)
+        })
+    }
+
+    fn copy_from_elem(&self, name: &'static str, slice: &[Self::Elem]) -> Self::BufferElem {
+        self.record_alloc("copy_from_elem", name, || {
+            self.hal.copy_from_elem(name, slice)
+        })
+    }
+
+    fn copy_from_extelem(
+        &self,
+        name: &'static str,
+        slice: &[Self::ExtElem],
+    ) -> Self::BufferExtElem {
+        self.record_alloc("copy_from_extelem", name, || {
+            self.hal.copy_from_extelem(name, slice)
+        })
+    }
+
+    fn copy_from_u32(&self, name: &'static str, slice: &[u32]) -> Self::BufferU32 {
+        self.record_alloc("copy_from_u32", name, || {
+            self.hal.copy_from_u32(name, slice)
+        })
+    }
+
+    fn batch_expand(&self, output: &Self::BufferElem, input: &Self::BufferElem, count: usize) {
+        self.record("batch_expand", output.size() + input.size(), || {
+            self.hal.batch_expand(&output.buf, &input.buf, count)
+        })
+    }
+
+    fn batch_evaluate_ntt(&self, io: &Self::BufferElem, count: usize, expand_bits: usize) {
+        self.record("batch_evaluate_ntt", io.size(), || {
+            self.hal.batch_evaluate_ntt(&io.buf, count, expand_bits)
+        })
+    }
+
+    fn batch_interpolate_ntt(&self, io: &Self::BufferElem, count: usize) {
+        self.record("batch_interpolate_ntt", io.size(), || {
+            self.hal.batch_interpolate_ntt(&io.buf, count)
+        })
+    }
+
+    fn batch_bit_reverse(&self, io: &Self::BufferElem, count: usize) {
+        self.record("batch_bit_reverse", io.size(), || {
+            self.hal.batch_bit_reverse(&io.buf, count)
+        })
+    }
+
+    fn batch_evaluate_any(
+        &self,
+        coeffs: &Self::BufferElem,
+        poly_count: usize,
+        which: &Self::BufferU32,
+        xs: &Self::BufferExtElem,
+        out: &Self::BufferExtElem,
+    ) {
+        let elements = coeffs.size() + which.size() + xs.size() + out.size();
+        self.record("batch_evaluate_any", elements, || {
+            self.hal
+                .batch_evaluate_any(&coeffs.buf, poly_count, &which.buf, &xs.buf, &out.buf)
+        })
+    }
+
+    fn zk_shift(&self, io: &Self::BufferElem, count: usize) {
+        self.record("zk_shift", io.size(), || self.hal.zk_shift(&io.buf, count))
+    }
+
+    fn mix_poly_coeffs(
+        &self,
+        out: &Self::BufferExtElem,
+        mix_start: &Self::ExtElem,
+        mix: &Self::ExtElem,
+        input: &Self::BufferElem,
+        combos: &Self::BufferU32,
+        input_size: usize,
+        count: usize,
+    ) {
+        let elements = out.size() + input.size() + combos.size();
+        self.record("mix_poly_coeffs", elements, || {
+            self.hal.mix_poly_coeffs(
+                &out.buf,
+                mix_start,
+                mix,
+                &input.buf,
+                &combos.buf,
+                input_size,
+                count,
+            )
+        })
+    }
+
+    fn eltwise_add_elem(
+        &self,
+        output: &Self::BufferElem,
+        input1: &Self::BufferElem,
+        input2: &Self::BufferElem,
+    ) {
+        let elements = output.size() + input1.size() + input2.size();
+        self.record("eltwise_add_elem", elements, || {
+            self.hal
+                .eltwise_add_elem(&output.buf, &input1.buf, &input2.buf)
+        })
+    }
+
+    fn eltwise_sum_extelem(&self, output: &Self::BufferElem, input: &Self::BufferExtElem) {
+        self.record("eltwise_sum_extelem", output.size() + input.size(), || {
+            self.hal.eltwise_sum_extelem(&output.buf, &input.buf)
+        })
+    }
+
+    fn eltwise_copy_elem(&self, output: &Self::BufferElem, input: &Self::BufferElem) {
+        self.record("eltwise_copy_elem", output.size() + input.size(), || {
+            self.hal.eltwise_copy_elem(&output.buf, &input.buf)
+        })
+    }
+
+    fn fri_fold(&self, output: &Self::BufferElem, input: &Self::BufferElem, mix: &Self::ExtElem) {
+        self.record("fri_fold", output.size() + input.size(), || {
+            self.hal.fri_fold(&output.buf, &input.buf, mix)
+        })
+    }
+
+    fn hash_rows(&self, output: &Self::BufferDigest, matrix: &Self::BufferElem) {
+        self.record("hash_rows", output.size() + matrix.size(), || {
+            self.hal.hash_rows(&output.buf, &matrix.buf)
+        })
+    }
+
+    fn hash_fold(&self, io: &Self::BufferDigest, input_size: usize, output_size: usize) {
+        self.record("hash_fold", input_size + output_size, || {
+            self.hal.hash_fold(&io.buf, input_size, output_size)
+        })
+    }
+}
+
+/// Adapts an [EvalCheck] for the wrapped [Hal] to a [ProfilingHal], recording
+/// its calls as the `eval_check` operation.
+pub struct ProfilingEvalCheck<'a, H: Hal, E: EvalCheck<H>> {
+    hal: &'a ProfilingHal<H>,
+    eval: &'a E,
+}
+
+impl<'a, H: Hal, E: EvalCheck<H>> ProfilingEvalCheck<'a, H, E> {
+    pub fn new(hal: &'a ProfilingHal<H>, eval: &'a E) -> Self {
+        Self { hal, eval }
+    }
+}
+
+impl<'a, H: Hal, E: EvalCheck<H>> EvalCheck<ProfilingHal<H>> for ProfilingEvalCheck<'a, H, E> {
+    fn eval_check(
+        &self,
+        check: &<ProfilingHal<H> as Hal>::BufferElem,
+        groups: &[&<ProfilingHal<H> as Hal>::BufferElem],
+        globals: &[&<ProfilingHal<H> as Hal>::BufferElem],
+        poly_mix: H::ExtElem,
+        po2: usize,
+        steps: usize,
+    ) {
+        let groups: Vec<&_> = groups.iter().map(|g| &g.buf).collect();
+        let globals: Vec<&_> = globals.iter().map(|g| &g.buf).collect();
+        let elements = check.size()
+            + groups.iter().map(|g| g.size()).sum::<usize>()
+            + globals.iter().map(|g| g.size()).sum::<usize>();
+        self.hal.record("eval_check", elements, || {
+            self.eval.eval_check(
+                &check.buf,
+                groups.as_slice(),
+                globals.as_slice(),
+                poly_mix,
+                po2,
+                steps,
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::{cpu::BabyBearSha256CpuHal, testutil};
+
+    #[test]
+    fn report() {
+        let hal = ProfilingHal::new(BabyBearSha256CpuHal::new());
+        let io = hal.alloc_elem("io", 1 << 10);
+        let other = hal.alloc_elem("other", 1 << 10);
+        let slice = other.slice(0, 16);
+        drop(other);
+        hal.batch_evaluate_ntt(&io, 1, 0);
+        hal.batch_evaluate_ntt(&io, 1, 0);
+        hal.eltwise_copy_elem(&slice, &io.slice(0, 16));
+
+        let report = hal.report();
+        let ntt = &report.ops["batch_evaluate_ntt"];
+        assert_eq!(ntt.calls, 2);
+        assert_eq!(ntt.elements, 2 << 10);
+        assert_eq!(ntt.bytes_allocated, 0);
+        let alloc = &report.ops["alloc_elem"];
+        assert_eq!(alloc.calls, 2);
+        assert_eq!(alloc.bytes_allocated, 8 << 10);
+        assert_eq!(report.ops["eltwise_copy_elem"].elements, 32);
+        // The slice keeps the whole buffer alive.
+        assert_eq!(report.live_bytes, 8 << 10);
+        assert_eq!(report.buffers["other"].live_bytes, 4 << 10);
+
+        drop(slice);
+        drop(io);
+        let report = hal.report();
+        assert_eq!(report.live_bytes, 0);
+        assert_eq!(report.peak_live_bytes, 8 << 10);
+        assert_eq!(report.buffers["io"].peak_live_bytes, 4 << 10);
+        #[cfg(feature = "profile_json")]
+        assert!(report.to_json().contains("\"batch_evaluate_ntt\""));
+        assert!(report.to_string().contains("batch_evaluate_ntt"));
+
+        hal.reset();
+        assert_eq!(hal.report(), ProfileReport::default());
+    }
+
+    #[test]
+    fn fri_fold() {
+        testutil::fri_fold(ProfilingHal::new(BabyBearSha256CpuHal::new()));
+    }
+
+    #[test]
+    fn mix_poly_coeffs() {
+        testutil::mix_poly_coeffs(ProfilingHal::new(BabyBearSha256CpuHal::new()));
+    }
+}
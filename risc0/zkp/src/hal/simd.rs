@@ -0,0 +1,537 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Vectorized BabyBear arithmetic for the CPU HAL.
+//!
+//! These kernels operate directly on the Montgomery representation of
+//! [BabyBearElem]s, eight lanes at a time using AVX2 when the CPU supports it
+//! (detected at runtime), and fall back to scalar arithmetic otherwise. Since
+//! every input is a canonical field element and every operation reduces back to
+//! canonical form, the results are bit-for-bit identical to the scalar code.
+
+use core::any::TypeId;
+
+use bytemuck::Pod;
+use risc0_core::field::{
+    baby_bear::{BabyBearElem, BabyBearExtElem},
+    Elem, ExtElem, RootsOfUnity,
+};
+
+use crate::core::{log2_ceil, ntt::bit_rev_32};
+
+/// The number of field elements in a vector.
+const LANES: usize = 8;
+
+/// The modulus of the BabyBear field.
+const P: u32 = 15 * (1 << 27) + 1;
+
+/// `P^-1 mod 2^32`, used for Montgomery reduction.
+const M: u32 = 0x88000001;
+
+/// Returns true if vectorized kernels are supported on this CPU.
+pub(crate) fn available() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        std::is_x86_feature_detected!("avx2")
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
+}
+
+/// Reinterprets a slice of `T` as a slice of `U` if they are the same type.
+///
+/// This lets the generic [CpuHal](super::cpu::CpuHal) pick the BabyBear kernels
+/// without specialization.
+pub(crate) fn cast_if<T: Pod, U: Pod>(slice: &[T]) -> Option<&[U]> {
+    (TypeId::of::<T>() == TypeId::of::<U>()).then(|| bytemuck::cast_slice(slice))
+}
+
+/// Mutable version of [cast_if].
+pub(crate) fn cast_if_mut<T: Pod, U: Pod>(slice: &mut [T]) -> Option<&mut [U]> {
+    (TypeId::of::<T>() == TypeId::of::<U>()).then(|| bytemuck::cast_slice_mut(slice))
+}
+
+/// Reinterprets a value of `T` as a `U` if they are the same type.
+pub(crate) fn cast_ref_if<T: Pod, U: Pod>(value: &T) -> Option<&U> {
+    (TypeId::of::<T>() == TypeId::of::<U>()).then(|| bytemuck::cast_ref(value))
+}
+
+fn words(slice: &[BabyBearElem]) -> &[u32] {
+    bytemuck::cast_slice(slice)
+}
+
+fn words_mut(slice: &mut [BabyBearElem]) -> &mut [u32] {
+    bytemuck::cast_slice_mut(slice)
+}
+
+fn ext_words(slice: &[BabyBearExtElem]) -> &[u32] {
+    bytemuck::cast_slice(slice)
+}
+
+fn ext_words_mut(slice: &mut [BabyBearExtElem]) -> &mut [u32] {
+    bytemuck::cast_slice_mut(slice)
+}
+
+/// `output[i] = lhs[i] + rhs[i]`
+pub(crate) fn add(output: &mut [BabyBearElem], lhs: &[BabyBearElem], rhs: &[BabyBearElem]) {
+    assert_eq!(output.len(), lhs.len());
+    assert_eq!(output.len(), rhs.len());
+    let mut done = 0;
+    #[cfg(target_arch = "x86_64")]
+    if available() {
+        // SAFETY: AVX2 support was checked above.
+        done = unsafe { avx2::add(words_mut(output), words(lhs), words(rhs)) };
+    }
+    for i in done..output.len() {
+        output[i] = lhs[i] + rhs[i];
+    }
+}
+
+/// `io[i] += rhs[i]`
+pub(crate) fn add_assign(io: &mut [BabyBearElem], rhs: &[BabyBearElem]) {
+    assert_eq!(io.len(), rhs.len());
+    let mut done = 0;
+    #[cfg(target_arch = "x86_64")]
+    if available() {
+        // SAFETY: AVX2 support was checked above.
+        done = unsafe { avx2::add_assign(words_mut(io), words(rhs)) };
+    }
+    for i in done..io.len() {
+        io[i] += rhs[i];
+    }
+}
+
+/// `io[i] += mix * input[i]`
+pub(crate) fn mul_add_ext(
+    io: &mut [BabyBearExtElem],
+    mix: &BabyBearExtElem,
+    input: &[BabyBearElem],
+) {
+    assert_eq!(io.len(), input.len());
+    let mut done = 0;
+    #[cfg(target_arch = "x86_64")]
+    if available() {
+        // SAFETY: AVX2 support was checked above.
+        done = unsafe {
+            avx2::mul_add_ext(
+                ext_words_mut(io),
+                ext_words(core::slice::from_ref(mix)),
+                words(input),
+            )
+        };
+    }
+    for i in done..io.len() {
+        io[i] += *mix * input[i];
+    }
+}
+
+/// Precomputed powers of the roots of unity used by [evaluate_ntt].
+pub(crate) struct NttTwiddles {
+    // levels[n][i] = ROU_FWD[n]^i, for i < 2^(n - 1)
+    levels: Vec<Vec<BabyBearElem>>,
+}
+
+impl NttTwiddles {
+    /// Computes the twiddles needed for NTTs of size up to `2^max_po2`.
+    pub(crate) fn new(max_po2: usize) -> Self {
+        let levels = (0..=max_po2)
+            .map(|n| {
+                if n == 0 {
+                    return Vec::new();
+                }
+                let step = BabyBearElem::ROU_FWD[n];
+                let mut cur = BabyBearElem::ONE;
+                (0..1 << (n - 1))
+                    .map(|_| {
+                        let val = cur;
+                        cur *= step;
+                        val
+                    })
+                    .collect()
+            })
+            .collect();
+        Self { levels }
+    }
+}
+
+/// Performs a forward NTT, exactly like [crate::core::ntt::evaluate_ntt], with
+/// vectorized butterflies.
+pub(crate) fn evaluate_ntt(io: &mut [BabyBearElem], expand_bits: usize, twiddles: &NttTwiddles) {
+    let n = log2_ceil(io.len());
+    assert_eq!(1 << n, io.len());
+    fwd_butterfly(io, n, expand_bits, twiddles);
+}
+
+fn fwd_butterfly(io: &mut [BabyBearElem], n: usize, expand_bits: usize, twiddles: &NttTwiddles) {
+    if n == 0 || n == expand_bits {
+        return;
+    }
+    let half = 1 << (n - 1);
+    let (lo, hi) = io.split_at_mut(half);
+    fwd_butterfly(lo, n - 1, expand_bits, twiddles);
+    fwd_butterfly(hi, n - 1, expand_bits, twiddles);
+    let twiddles = &twiddles.levels[n];
+    let mut done = 0;
+    #[cfg(target_arch = "x86_64")]
+    if half >= LANES && available() {
+        // SAFETY: AVX2 support was checked above.
+        done = unsafe { avx2::butterfly(words_mut(lo), words_mut(hi), words(twiddles)) };
+    }
+    for i in done..half {
+        let a = lo[i];
+        let b = hi[i] * twiddles[i];
+        lo[i] = a + b;
+        hi[i] = a - b;
+    }
+}
+
+/// Performs a FRI fold exactly like the scalar `CpuHal::fri_fold`.
+///
+/// `input` and `output` are laid out as `EXT_SIZE` consecutive planes, so the
+/// fold is vectorized across `count` adjacent indexes.
+pub(crate) fn fri_fold(output: &mut [BabyBearElem], input: &[BabyBearElem], mix: &BabyBearExtElem) {
+    let ext_size = BabyBearExtElem::EXT_SIZE;
+    let count = output.len() / ext_size;
+    let fold = input.len() / output.len();
+    let fold_po2 = log2_ceil(fold);
+    let mut mix_pows = Vec::with_capacity(fold);
+    let mut cur_mix = BabyBearExtElem::ONE;
+    for _ in 0..fold {
+        mix_pows.push(cur_mix);
+        cur_mix *= *mix;
+    }
+    let rev_offsets: Vec<usize> = (0..fold)
+        .map(|i| (bit_rev_32(i as u32) >> (32 - fold_po2)) as usize * count)
+        .collect();
+
+    let mut done = 0;
+    #[cfg(target_arch = "x86_64")]
+    if available() {
+        // SAFETY: AVX2 support was checked above.
+        done = unsafe {
+            avx2::fri_fold(
+                words_mut(output),
+                words(input),
+                ext_words(&mix_pows),
+                &rev_offsets,
+                count,
+            )
+        };
+    }
+    for idx in done..count {
+        let mut tot = BabyBearExtElem::ZERO;
+        for (mix_pow, rev_offset) in mix_pows.iter().zip(rev_offsets.iter()) {
+            let factor = BabyBearExtElem::from_subelems(
+                (0..ext_size).map(|i| input[i * count * fold + rev_offset + idx]),
+            );
+            tot += *mix_pow * factor;
+        }
+        for i in 0..ext_size {
+            output[count * i + idx] = tot.subelems()[i];
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    //! AVX2 kernels. Each returns the number of leading elements it handled;
+    //! the caller is responsible for the remainder.
+
+    use core::arch::x86_64::*;
+
+    use risc0_core::field::baby_bear::BabyBearElem;
+
+    use super::{LANES, M, P};
+
+    /// `-11` in Montgomery form, where `X^4 + 11` is the irreducible polynomial
+    /// of the extension field.
+    const NBETA: u32 = BabyBearElem::new(P - 11).as_u32_montgomery();
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn load(src: &[u32]) -> __m256i {
+        debug_assert!(src.len() >= LANES);
+        _mm256_loadu_si256(src.as_ptr() as *const __m256i)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn store(dst: &mut [u32], val: __m256i) {
+        debug_assert!(dst.len() >= LANES);
+        _mm256_storeu_si256(dst.as_mut_ptr() as *mut __m256i, val)
+    }
+
+    /// Reduces `x` in `[0, 2P)` to `[0, P)`.
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn reduce_once(x: __m256i) -> __m256i {
+        // If x < P then x - P wraps around to something larger than x.
+        _mm256_min_epu32(x, _mm256_sub_epi32(x, _mm256_set1_epi32(P as i32)))
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn add_vec(lhs: __m256i, rhs: __m256i) -> __m256i {
+        reduce_once(_mm256_add_epi32(lhs, rhs))
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn sub_vec(lhs: __m256i, rhs: __m256i) -> __m256i {
+        // If lhs < rhs then x wraps around to something larger than x + P.
+        let x = _mm256_sub_epi32(lhs, rhs);
+        _mm256_min_epu32(x, _mm256_add_epi32(x, _mm256_set1_epi32(P as i32)))
+    }
+
+    /// Montgomery reduction of the 64-bit products in each 64-bit lane,
+    /// leaving the (unreduced) result in the high 32 bits of each lane.
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn mont_reduce(prod: __m256i) -> __m256i {
+        let low = _mm256_sub_epi32(_mm256_setzero_si256(), prod);
+        let red = _mm256_mul_epu32(low, _mm256_set1_epi32(M as i32));
+        _mm256_add_epi64(prod, _mm256_mul_epu32(red, _mm256_set1_epi32(P as i32)))
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn mul_vec(lhs: __m256i, rhs: __m256i) -> __m256i {
+        let evn = mont_reduce(_mm256_mul_epu32(lhs, rhs));
+        let odd = mont_reduce(_mm256_mul_epu32(
+            _mm256_srli_epi64(lhs, 32),
+            _mm256_srli_epi64(rhs, 32),
+        ));
+        reduce_once(_mm256_blend_epi32(
+            _mm256_srli_epi64(evn, 32),
+            odd,
+            0b10101010,
+        ))
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn add(output: &mut [u32], lhs: &[u32], rhs: &[u32]) -> usize {
+        let done = output.len() / LANES * LANES;
+        for i in (0..done).step_by(LANES) {
+            store(&mut output[i..], add_vec(load(&lhs[i..]), load(&rhs[i..])));
+        }
+        done
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn add_assign(io: &mut [u32], rhs: &[u32]) -> usize {
+        let done = io.len() / LANES * LANES;
+        for i in (0..done).step_by(LANES) {
+            let sum = add_vec(load(&io[i..]), load(&rhs[i..]));
+            store(&mut io[i..], sum);
+        }
+        done
+    }
+
+    /// `io` holds extension elements and `mix` a single extension element.
+    /// Returns the number of extension elements handled.
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn mul_add_ext(io: &mut [u32], mix: &[u32], input: &[u32]) -> usize {
+        // Two extension elements per vector: [m0, m1, m2, m3, m0, m1, m2, m3]
+        let mix = _mm256_setr_epi32(
+            mix[0] as i32,
+            mix[1] as i32,
+            mix[2] as i32,
+            mix[3] as i32,
+            mix[0] as i32,
+            mix[1] as i32,
+            mix[2] as i32,
+            mix[3] as i32,
+        );
+        let done = input.len() / LANES * LANES;
+        for i in (0..done).step_by(LANES) {
+            let vals = load(&input[i..]);
+            for j in 0..4 {
+                // Broadcast input elements 2j and 2j + 1 to match the layout of mix.
+                let lo = 2 * j as i32;
+                let hi = lo + 1;
+                let idx = _mm256_setr_epi32(lo, lo, lo, lo, hi, hi, hi, hi);
+                let vals = _mm256_permutevar8x32_epi32(vals, idx);
+                let out = &mut io[4 * i + LANES * j..];
+                let sum = add_vec(load(out), mul_vec(mix, vals));
+                store(out, sum);
+            }
+        }
+        done
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn butterfly(lo: &mut [u32], hi: &mut [u32], twiddles: &[u32]) -> usize {
+        let done = lo.len() / LANES * LANES;
+        for i in (0..done).step_by(LANES) {
+            let a = load(&lo[i..]);
+            let b = mul_vec(load(&hi[i..]), load(&twiddles[i..]));
+            store(&mut lo[i..], add_vec(a, b));
+            store(&mut hi[i..], sub_vec(a, b));
+        }
+        done
+    }
+
+    /// Multiplies the broadcast extension element `a` by the extension elements
+    /// with planes `b`.
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn ext_mul(a: &[__m256i; 4], na: &[__m256i; 4], b: &[__m256i; 4]) -> [__m256i; 4] {
+        [
+            dot4(a[0], b[0], na[1], b[3], na[2], b[2], na[3], b[1]),
+            dot4(a[0], b[1], a[1], b[0], na[2], b[3], na[3], b[2]),
+            dot4(a[0], b[2], a[1], b[1], a[2], b[0], na[3], b[3]),
+            dot4(a[0], b[3], a[1], b[2], a[2], b[1], a[3], b[0]),
+        ]
+    }
+
+    /// `a0 * b0 + a1 * b1 + a2 * b2 + a3 * b3`
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn dot4(
+        a0: __m256i,
+        b0: __m256i,
+        a1: __m256i,
+        b1: __m256i,
+        a2: __m256i,
+        b2: __m256i,
+        a3: __m256i,
+        b3: __m256i,
+    ) -> __m256i {
+        add_vec(
+            add_vec(mul_vec(a0, b0), mul_vec(a1, b1)),
+            add_vec(mul_vec(a2, b2), mul_vec(a3, b3)),
+        )
+    }
+
+    /// Returns the number of indexes handled.
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn fri_fold(
+        output: &mut [u32],
+        input: &[u32],
+        mix_pows: &[u32],
+        rev_offsets: &[usize],
+        count: usize,
+    ) -> usize {
+        let fold = rev_offsets.len();
+        let nbeta = _mm256_set1_epi32(NBETA as i32);
+        // Broadcast each power of mix, along with its product with NBETA.
+        let mut mix_vecs = Vec::with_capacity(fold);
+        for mix_pow in mix_pows.chunks_exact(4) {
+            let mut a = [_mm256_setzero_si256(); 4];
+            let mut na = [_mm256_setzero_si256(); 4];
+            for i in 0..4 {
+                a[i] = _mm256_set1_epi32(mix_pow[i] as i32);
+                na[i] = mul_vec(nbeta, a[i]);
+            }
+            mix_vecs.push((a, na));
+        }
+        let done = count / LANES * LANES;
+        for idx in (0..done).step_by(LANES) {
+            let mut tot = [_mm256_setzero_si256(); 4];
+            for ((a, na), rev_offset) in mix_vecs.iter().zip(rev_offsets.iter()) {
+                let mut factor = [_mm256_setzero_si256(); 4];
+                for i in 0..4 {
+                    factor[i] = load(&input[i * count * fold + rev_offset + idx..]);
+                }
+                let prod = ext_mul(a, na, &factor);
+                for i in 0..4 {
+                    tot[i] = add_vec(tot[i], prod[i]);
+                }
+            }
+            for i in 0..4 {
+                store(&mut output[count * i + idx..], tot[i]);
+            }
+        }
+        done
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+    use risc0_core::field::Elem;
+
+    use super::*;
+    use crate::core::ntt;
+
+    fn random_elems(size: usize) -> Vec<BabyBearElem> {
+        let mut rng = thread_rng();
+        (0..size).map(|_| BabyBearElem::random(&mut rng)).collect()
+    }
+
+    #[test]
+    fn arithmetic() {
+        // Include the edge cases of the field alongside random elements.
+        let mut lhs = random_elems(1021);
+        let mut rhs = random_elems(1021);
+        let edges = [0, 1, 2, P - 2, P - 1].map(BabyBearElem::new);
+        for (i, a) in edges.iter().enumerate() {
+            for (j, b) in edges.iter().enumerate() {
+                lhs[i * edges.len() + j] = *a;
+                rhs[i * edges.len() + j] = *b;
+            }
+        }
+
+        let mut sum = vec![BabyBearElem::ZERO; lhs.len()];
+        add(&mut sum, &lhs, &rhs);
+        let mut acc = lhs.clone();
+        add_assign(&mut acc, &rhs);
+        for i in 0..lhs.len() {
+            assert_eq!(sum[i], lhs[i] + rhs[i]);
+            assert_eq!(acc[i], lhs[i] + rhs[i]);
+        }
+    }
+
+    #[test]
+    fn mul_add_ext_matches_scalar() {
+        let mut rng = thread_rng();
+        let mix = BabyBearExtElem::random(&mut rng);
+        let input = random_elems(1027);
+        let init: Vec<BabyBearExtElem> = (0..input.len())
+            .map(|_| BabyBearExtElem::random(&mut rng))
+            .collect();
+        let mut io = init.clone();
+        mul_add_ext(&mut io, &mix, &input);
+        for i in 0..input.len() {
+            assert_eq!(io[i], init[i] + mix * input[i]);
+        }
+    }
+
+    #[test]
+    fn evaluate_ntt_matches_scalar() {
+        let twiddles = NttTwiddles::new(12);
+        for n in 0..=12 {
+            for expand_bits in [0, 1, 2] {
+                if expand_bits > n {
+                    continue;
+                }
+                let mut expected = random_elems(1 << n);
+                let mut actual = expected.clone();
+                ntt::evaluate_ntt::<BabyBearElem, BabyBearElem>(&mut expected, expand_bits);
+                evaluate_ntt(&mut actual, expand_bits, &twiddles);
+                assert_eq!(actual, expected, "n = {n}, expand_bits = {expand_bits}");
+            }
+        }
+    }
+
+    #[test]
+    fn cast() {
+        let elems = random_elems(4);
+        assert!(cast_if::<BabyBearElem, BabyBearElem>(&elems).is_some());
+        assert!(cast_if::<BabyBearElem, u32>(&elems).is_none());
+    }
+}
@@ -23,9 +23,12 @@ pub mod core;
 #[cfg(feature = "prove")]
 pub mod hal;
 mod merkle;
+pub mod params;
+mod pow;
 #[cfg(feature = "prove")]
 pub mod prove;
 pub mod taps;
+pub mod transcript;
 pub mod verify;
 
 #[cfg(not(feature = "prove"))]
@@ -44,14 +47,16 @@ pub const MIN_CYCLES: usize = 1 << MIN_CYCLES_PO2; // 1K
 pub const MAX_CYCLES_PO2: usize = 24;
 pub const MAX_CYCLES: usize = 1 << MAX_CYCLES_PO2; // 16M
 
-/// 50 FRI queries gives ~100 bits of conjectured security
+/// Default number of FRI queries; 50 FRI queries gives ~100 bits of
+/// conjectured security. See [params::ProofParams] to change this at runtime.
 pub const QUERIES: usize = 50;
 pub const ZK_CYCLES: usize = QUERIES;
 pub const MIN_PO2: usize = core::log2_ceil(1 + ZK_CYCLES);
 
-/// Inverse of Reed-Solomon Expansion Rate
+/// Default inverse of Reed-Solomon Expansion Rate. This is also the degree
+/// bound of the constraints, and so sets the size of the check polynomial.
 pub const INV_RATE: usize = 4;
-/// FRI folding factor is 2 ^ FRI_FOLD_PO2
+/// Default FRI folding factor is 2 ^ FRI_FOLD_PO2
 const FRI_FOLD_PO2: usize = 4;
 pub const FRI_FOLD: usize = 1 << FRI_FOLD_PO2;
 /// FRI continues until the degree of the FRI polynomial reaches FRI_MIN_DEGREE
@@ -17,23 +17,36 @@ use crate::core::to_po2;
 /// The parameters of a merkle tree of prime field elements, including:
 /// row_size - the number of leaves in the tree
 /// col_size - the number of field elements associated with each leaf
-/// queries - the number of queries that will be made to this merkle tree
 /// layers - the number of levels on the merkle tree
-/// top_layer - the index of the layer above which we check hashes only once
-/// top_size - the number of hashes in the top layer
+/// top_size - the number of hashes in the top layer, above which we check
+/// hashes only once
 pub struct MerkleTreeParams {
     pub row_size: usize,
     pub col_size: usize,
-    pub queries: usize,
     pub layers: usize,
-    pub top_layer: usize,
     pub top_size: usize,
 }
 
 impl MerkleTreeParams {
     /// Returns the parameters of the Merkle tree, given the row and column size
     /// and the number of queries to be made.
+    #[cfg(test)]
     pub fn new(row_size: usize, col_size: usize, queries: usize) -> Self {
+        Self::new_with_cap(row_size, col_size, queries, None)
+    }
+
+    /// Returns the parameters of the Merkle tree with an explicit cap height.
+    ///
+    /// A cap of height `k` makes layer `k` (of size `2^k`) the top layer,
+    /// limited to the layer just above the leaves. If `cap` is `None`, the top
+    /// layer is chosen from the number of queries as in
+    /// [MerkleTreeParams::new].
+    pub fn new_with_cap(
+        row_size: usize,
+        col_size: usize,
+        queries: usize,
+        cap: Option<usize>,
+    ) -> Self {
         // The number of layers is the logarithm base 2 of the row_size.
         let layers: usize = to_po2(row_size);
         assert!(1 << layers == row_size);
@@ -42,20 +55,24 @@ impl MerkleTreeParams {
         // to this top layer. This allows us to avoid checking hashes in this
         // part of the tree multiple times. We choose the top layer to be the
         // one with size at most equal to queries.
-        let mut top_layer = 0;
-        for i in 1..layers {
-            if (1 << i) > queries {
-                break;
+        let top_layer = match cap {
+            Some(cap) => cap.min(layers.saturating_sub(1)),
+            None => {
+                let mut top_layer = 0;
+                for i in 1..layers {
+                    if (1 << i) > queries {
+                        break;
+                    }
+                    top_layer = i;
+                }
+                top_layer
             }
-            top_layer = i;
-        }
+        };
         let top_size = 1 << top_layer;
         MerkleTreeParams {
             row_size,
             col_size,
-            queries,
             layers,
-            top_layer,
             top_size,
         }
     }
@@ -74,9 +91,7 @@ mod tests {
 
         assert_eq!(params.row_size, row_size);
         assert_eq!(params.col_size, col_size);
-        assert_eq!(params.queries, queries);
         assert_eq!(params.layers, 10);
-        assert_eq!(params.top_layer, 5);
         assert_eq!(params.top_size, 32);
     }
 
@@ -89,9 +104,20 @@ mod tests {
 
         assert_eq!(params.row_size, row_size);
         assert_eq!(params.col_size, col_size);
-        assert_eq!(params.queries, queries);
         assert_eq!(params.layers, 11);
-        assert_eq!(params.top_layer, 7);
         assert_eq!(params.top_size, 128);
     }
+
+    #[test]
+    fn new_merkle_tree_params_cap() {
+        let params = MerkleTreeParams::new_with_cap(1024, 16, 50, Some(3));
+        assert_eq!(params.layers, 10);
+        assert_eq!(params.top_size, 8);
+
+        let params = MerkleTreeParams::new_with_cap(8, 16, 50, Some(8));
+        assert_eq!(params.top_size, 4);
+
+        let params = MerkleTreeParams::new_with_cap(1024, 16, 50, Some(0));
+        assert_eq!(params.top_size, 1);
+    }
 }
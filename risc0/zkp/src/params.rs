@@ -0,0 +1,384 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Runtime-configurable parameters of the FRI protocol.
+//!
+//! The [ProofParams] used to generate a seal are written at the start of the
+//! seal and committed to the Fiat-Shamir transcript, so a verifier always
+//! knows (and can place requirements on) the parameters a proof was generated
+//! with.
+
+use core::fmt;
+
+use crate::{
+    core::{digest::Digest, log2_ceil},
+    verify::VerificationError,
+    FRI_FOLD, INV_RATE, QUERIES, ZK_CYCLES,
+};
+
+/// Smallest supported inverse rate (blowup factor) of the Reed-Solomon code.
+pub const MIN_INV_RATE: usize = 2;
+/// Largest supported inverse rate (blowup factor) of the Reed-Solomon code.
+pub const MAX_INV_RATE: usize = 16;
+/// Smallest supported FRI folding factor.
+pub const MIN_FRI_FOLD: usize = 2;
+/// Largest supported FRI folding factor.
+pub const MAX_FRI_FOLD: usize = 64;
+/// Largest supported number of proof-of-work grinding bits.
+pub const MAX_POW_BITS: usize = 24;
+/// Largest supported Merkle cap height, i.e. a cap of at most
+/// `2^MAX_MERKLE_CAP` digests.
+pub const MAX_MERKLE_CAP: usize = 10;
+/// Largest supported number of FRI queries.
+///
+/// Each query reveals one row of the execution trace, so the number of queries
+/// may not exceed the number of randomized padding cycles without giving up
+/// zero-knowledge.
+pub const MAX_QUERIES: usize = ZK_CYCLES;
+
+/// Parameters that trade seal size against proving time and security.
+///
+/// * `queries`: the number of FRI queries. Each query adds Merkle branches to
+///   the seal, and contributes `log2(inv_rate)` bits of conjectured security.
+/// * `inv_rate`: the inverse rate of the Reed-Solomon code. A larger rate
+///   increases proving time and memory but gives more security per query.
+/// * `fri_fold`: the FRI folding factor. A larger factor means fewer FRI rounds
+///   (and so fewer Merkle branches per query) but wider rows per branch.
+/// * `pow_bits`: the number of proof-of-work bits the prover must grind before
+///   the FRI queries are sampled. Each bit adds one bit of security at the cost
+///   of doubling the (expected) grinding work, so fewer queries are needed.
+/// * `merkle_batch`: whether the Merkle openings for all queries are written as
+///   a single deduplicated multi-proof per tree, rather than one authentication
+///   path per query. Sibling hashes shared between queries (which is most of
+///   the upper part of each tree) are then only written once.
+/// * `merkle_cap`: the height `k` of the Merkle cap. The `2^k` digests of layer
+///   `k` of each tree are written to the seal, and authentication paths stop
+///   there. If unset, the cap is the largest layer with at most `queries`
+///   digests.
+///
+/// `inv_rate` and `fri_fold` must be powers of 2.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ProofParams {
+    queries: usize,
+    inv_rate: usize,
+    fri_fold: usize,
+    pow_bits: usize,
+    merkle_batch: bool,
+    merkle_cap: Option<usize>,
+}
+
+impl ProofParams {
+    /// The number of words used to encode [ProofParams] in a seal.
+    pub const WORDS: usize = 6;
+
+    /// Returns the default parameters, which gives ~100 bits of conjectured
+    /// security.
+    pub const fn new() -> Self {
+        Self {
+            queries: QUERIES,
+            inv_rate: INV_RATE,
+            fri_fold: FRI_FOLD,
+            pow_bits: 0,
+            merkle_batch: false,
+            merkle_cap: None,
+        }
+    }
+
+    /// Sets the number of FRI queries.
+    pub fn with_queries(self, queries: usize) -> Self {
+        Self { queries, ..self }
+    }
+
+    /// Sets the inverse rate of the Reed-Solomon code.
+    pub fn with_inv_rate(self, inv_rate: usize) -> Self {
+        Self { inv_rate, ..self }
+    }
+
+    /// Sets the FRI folding factor.
+    ///
+    /// Note that the GPU HALs currently only support the default [FRI_FOLD].
+    pub fn with_fri_fold(self, fri_fold: usize) -> Self {
+        Self { fri_fold, ..self }
+    }
+
+    /// Sets the number of proof-of-work grinding bits.
+    pub fn with_pow_bits(self, pow_bits: usize) -> Self {
+        Self { pow_bits, ..self }
+    }
+
+    /// Sets whether Merkle openings are batched into multi-proofs.
+    ///
+    /// This removes the authentication path nodes which the verifier can
+    /// compute from other queries. The opened rows themselves, which make up
+    /// most of the seal, are only shared when queries collide.
+    ///
+    /// Proving a minimal guest with the rv32im circuit and otherwise default
+    /// parameters, batching reduced the seal from 194 KiB to 180 KiB at po2 15
+    /// and from 240 KiB to 221 KiB at po2 18; see
+    /// [ProofParams::with_merkle_cap] for the effect of the cap height.
+    pub fn with_merkle_batch(self, merkle_batch: bool) -> Self {
+        Self {
+            merkle_batch,
+            ..self
+        }
+    }
+
+    /// Sets the height of the Merkle cap, or `None` to size the cap from the
+    /// number of queries.
+    ///
+    /// A higher cap writes more digests to the seal but shortens every
+    /// authentication path. Measured seal sizes in KiB, proving a minimal guest
+    /// with the rv32im circuit and otherwise default parameters (`None` is a
+    /// cap height of 5 with the default 50 queries):
+    ///
+    /// | `merkle_cap` | po2 15 | po2 15, batched | po2 18 | po2 18, batched |
+    /// |--------------|--------|-----------------|--------|-----------------|
+    /// | `None`       | 194    | 180             | 240    | 221             |
+    /// | `Some(0)`    | 235    | 175             | 288    | 216             |
+    /// | `Some(2)`    | 217    | 175             | 266    | 217             |
+    /// | `Some(4)`    | 201    | 175             | 247    | 218             |
+    /// | `Some(6)`    | 191    | 183             | 236    | 223             |
+    /// | `Some(8)`    | 208    | 204             | 253    | 248             |
+    /// | `Some(10)`   | 313    | 311             | 379    | 377             |
+    pub fn with_merkle_cap(self, merkle_cap: Option<usize>) -> Self {
+        Self { merkle_cap, ..self }
+    }
+
+    /// The number of FRI queries.
+    pub fn queries(&self) -> usize {
+        self.queries
+    }
+
+    /// The inverse rate of the Reed-Solomon code.
+    pub fn inv_rate(&self) -> usize {
+        self.inv_rate
+    }
+
+    /// The FRI folding factor.
+    pub fn fri_fold(&self) -> usize {
+        self.fri_fold
+    }
+
+    /// The number of proof-of-work grinding bits.
+    pub fn pow_bits(&self) -> usize {
+        self.pow_bits
+    }
+
+    /// Whether Merkle openings are batched into multi-proofs.
+    pub fn merkle_batch(&self) -> bool {
+        self.merkle_batch
+    }
+
+    /// The height of the Merkle cap, if set explicitly.
+    pub fn merkle_cap(&self) -> Option<usize> {
+        self.merkle_cap
+    }
+
+    /// `log2(fri_fold)`
+    pub fn fri_fold_po2(&self) -> usize {
+        log2_ceil(self.fri_fold)
+    }
+
+    /// Returns the conjectured security level of these parameters, in bits.
+    ///
+    /// Under the standard FRI soundness conjecture each query contributes
+    /// `log2(inv_rate)` bits of security, and grinding adds `pow_bits` on top.
+    pub fn security_bits(&self) -> usize {
+        self.queries * log2_ceil(self.inv_rate) + self.pow_bits
+    }
+
+    /// Checks that these parameters are supported by the prover and verifier.
+    pub fn validate(&self) -> Result<(), ProofParamsError> {
+        if self.queries == 0 || self.queries > MAX_QUERIES {
+            return Err(ProofParamsError::Queries(self.queries));
+        }
+        if !self.inv_rate.is_power_of_two()
+            || self.inv_rate < MIN_INV_RATE
+            || self.inv_rate > MAX_INV_RATE
+        {
+            return Err(ProofParamsError::InvRate(self.inv_rate));
+        }
+        if !self.fri_fold.is_power_of_two()
+            || self.fri_fold < MIN_FRI_FOLD
+            || self.fri_fold > MAX_FRI_FOLD
+        {
+            return Err(ProofParamsError::FriFold(self.fri_fold));
+        }
+        if self.pow_bits > MAX_POW_BITS {
+            return Err(ProofParamsError::PowBits(self.pow_bits));
+        }
+        if let Some(merkle_cap) = self.merkle_cap {
+            if merkle_cap > MAX_MERKLE_CAP {
+                return Err(ProofParamsError::MerkleCap(merkle_cap));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that these parameters provide at least `min_bits` of conjectured
+    /// security.
+    pub fn check_security(&self, min_bits: usize) -> Result<(), VerificationError> {
+        let bits = self.security_bits();
+        if bits < min_bits {
+            return Err(VerificationError::InsufficientSecurity {
+                bits,
+                required: min_bits,
+            });
+        }
+        Ok(())
+    }
+
+    /// Encodes these parameters as they are written to the seal.
+    ///
+    /// The Merkle cap is encoded as `k + 1`, with zero meaning unset.
+    pub fn encode(&self) -> [u32; Self::WORDS] {
+        [
+            self.queries as u32,
+            self.inv_rate as u32,
+            self.fri_fold as u32,
+            self.pow_bits as u32,
+            self.merkle_batch as u32,
+            self.merkle_cap.map_or(0, |k| k as u32 + 1),
+        ]
+    }
+
+    /// Decodes and validates parameters read from a seal.
+    pub fn decode(words: &[u32]) -> Result<Self, VerificationError> {
+        let params = match words {
+            &[queries, inv_rate, fri_fold, pow_bits, merkle_batch, merkle_cap] => Self {
+                queries: queries as usize,
+                inv_rate: inv_rate as usize,
+                fri_fold: fri_fold as usize,
+                pow_bits: pow_bits as usize,
+                merkle_batch: match merkle_batch {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(VerificationError::InvalidProofParams),
+                },
+                merkle_cap: merkle_cap.checked_sub(1).map(|k| k as usize),
+            },
+            _ => return Err(VerificationError::ReceiptFormatError),
+        };
+        params
+            .validate()
+            .map_err(|_| VerificationError::InvalidProofParams)?;
+        Ok(params)
+    }
+
+    /// The digest that commits these parameters to the Fiat-Shamir transcript.
+    pub fn digest(&self) -> Digest {
+        let mut words = [0u32; 8];
+        words[..Self::WORDS].copy_from_slice(&self.encode());
+        Digest::from(words)
+    }
+}
+
+impl Default for ProofParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reasons a set of [ProofParams] may be rejected.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ProofParamsError {
+    /// The number of queries is zero or exceeds [MAX_QUERIES].
+    Queries(usize),
+    /// The inverse rate is not a supported power of 2.
+    InvRate(usize),
+    /// The FRI folding factor is not a supported power of 2.
+    FriFold(usize),
+    /// The number of grinding bits exceeds [MAX_POW_BITS].
+    PowBits(usize),
+    /// The Merkle cap height exceeds [MAX_MERKLE_CAP].
+    MerkleCap(usize),
+}
+
+impl fmt::Display for ProofParamsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProofParamsError::Queries(queries) => {
+                write!(f, "queries must be in 1..={MAX_QUERIES}, got {queries}")
+            }
+            ProofParamsError::InvRate(inv_rate) => write!(
+                f,
+                "inv_rate must be a power of 2 in {MIN_INV_RATE}..={MAX_INV_RATE}, got {inv_rate}"
+            ),
+            ProofParamsError::FriFold(fri_fold) => write!(
+                f,
+                "fri_fold must be a power of 2 in {MIN_FRI_FOLD}..={MAX_FRI_FOLD}, got {fri_fold}"
+            ),
+            ProofParamsError::PowBits(pow_bits) => {
+                write!(f, "pow_bits must be at most {MAX_POW_BITS}, got {pow_bits}")
+            }
+            ProofParamsError::MerkleCap(merkle_cap) => write!(
+                f,
+                "merkle_cap must be at most {MAX_MERKLE_CAP}, got {merkle_cap}"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_security() {
+        let params = ProofParams::default();
+        assert_eq!(params.security_bits(), 100);
+        params.validate().unwrap();
+        params.check_security(100).unwrap();
+        assert!(params.check_security(101).is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let params = ProofParams::default()
+            .with_queries(30)
+            .with_inv_rate(8)
+            .with_fri_fold(4)
+            .with_pow_bits(16);
+        assert_eq!(params.security_bits(), 106);
+        assert_eq!(ProofParams::decode(&params.encode()).unwrap(), params);
+        for merkle_cap in [None, Some(0), Some(MAX_MERKLE_CAP)] {
+            let params = params.with_merkle_batch(true).with_merkle_cap(merkle_cap);
+            assert_eq!(ProofParams::decode(&params.encode()).unwrap(), params);
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        let bad = [
+            ProofParams::default().with_queries(0),
+            ProofParams::default().with_queries(MAX_QUERIES + 1),
+            ProofParams::default().with_inv_rate(1),
+            ProofParams::default().with_inv_rate(6),
+            ProofParams::default().with_inv_rate(MAX_INV_RATE * 2),
+            ProofParams::default().with_fri_fold(1),
+            ProofParams::default().with_fri_fold(12),
+            ProofParams::default().with_fri_fold(MAX_FRI_FOLD * 2),
+            ProofParams::default().with_pow_bits(MAX_POW_BITS + 1),
+            ProofParams::default().with_merkle_cap(Some(MAX_MERKLE_CAP + 1)),
+        ];
+        for params in bad {
+            assert!(params.validate().is_err(), "{params:?}");
+            assert!(ProofParams::decode(&params.encode()).is_err(), "{params:?}");
+        }
+        assert!(ProofParams::decode(&[QUERIES as u32]).is_err());
+        let mut words = ProofParams::default().encode();
+        words[4] = 2;
+        assert!(ProofParams::decode(&words).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Proof-of-work grinding shared by the FRI prover and verifier.
+//!
+//! Before the FRI queries are sampled, the prover draws a challenge from the
+//! IOP and searches for a nonce such that `hash_pair(challenge, nonce)` has
+//! `pow_bits` low zero bits. The nonce is written to the seal and the
+//! resulting digest is committed, so every attempt to re-roll the queries
+//! costs the prover `2^pow_bits` hashes.
+
+use risc0_core::field::Field;
+
+use crate::core::{config::ConfigHash, digest::Digest};
+
+/// The number of field elements drawn from the IOP to form the challenge.
+pub const CHALLENGE_ELEMS: usize = 8;
+
+/// Nonces are kept below this bound so that they are valid field elements for
+/// all hash suites.
+pub const MAX_NONCE: u32 = 1 << 30;
+
+/// Compute the digest of a grinding attempt with the given nonce.
+pub fn pow_digest<F: Field, H: ConfigHash<F>>(challenge: &Digest, nonce: u32) -> H::DigestPtr {
+    H::hash_pair(challenge, &Digest::from([nonce, 0, 0, 0, 0, 0, 0, 0]))
+}
+
+/// Check whether a grinding digest has the required number of low zero bits.
+pub fn pow_check(digest: &Digest, pow_bits: usize) -> bool {
+    digest.as_words()[0] & ((1 << pow_bits) - 1) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use risc0_core::field::baby_bear::BabyBear;
+
+    use super::*;
+    use crate::core::{
+        config::{ConfigHashPoseidon, ConfigHashSha256},
+        sha_cpu,
+    };
+
+    fn grind<H: ConfigHash<BabyBear>>(challenge: &Digest, pow_bits: usize) -> u32 {
+        (0..MAX_NONCE)
+            .find(|nonce| pow_check(&pow_digest::<BabyBear, H>(challenge, *nonce), pow_bits))
+            .unwrap()
+    }
+
+    fn check_grind<H: ConfigHash<BabyBear>>() {
+        let challenge = Digest::from([1, 2, 3, 4, 5, 6, 7, 8]);
+        let nonce = grind::<H>(&challenge, 8);
+        let digest = pow_digest::<BabyBear, H>(&challenge, nonce);
+        assert!(pow_check(&digest, 8));
+        assert_eq!(digest.as_words()[0] & 0xff, 0);
+        // Grinding for fewer bits can never need a later nonce.
+        assert!(grind::<H>(&challenge, 4) <= nonce);
+        // A different challenge invalidates the nonce with high probability.
+        let other = Digest::from([8, 7, 6, 5, 4, 3, 2, 1]);
+        assert_ne!(grind::<H>(&other, 8), nonce);
+    }
+
+    #[test]
+    fn grind_sha() {
+        check_grind::<ConfigHashSha256<sha_cpu::Impl>>();
+    }
+
+    #[test]
+    fn grind_poseidon() {
+        check_grind::<ConfigHashPoseidon>();
+    }
+}
@@ -66,6 +66,7 @@ where
     /// Perform initial 'execution' setting code + data.
     /// Additionally, write any 'results' as needed.
     pub fn execute<R: ConfigRng<F>>(&mut self, iop: &mut WriteIOP<F, R>) {
+        iop.label("execute");
         iop.write_field_elem_slice(&*self.exec.io.as_slice());
         iop.write_u32_slice(&[self.exec.po2 as u32]);
     }
@@ -122,6 +123,7 @@ where
     /// Perform 'accumulate' stage, using the iop for any RNG state.
     #[tracing::instrument(skip_all)]
     pub fn accumulate<R: ConfigRng<F>>(&mut self, iop: &mut WriteIOP<F, R>) {
+        iop.label("accumulate");
         // Make the mixing values
         self.mix = CpuBuffer::from_fn(C::MIX_SIZE, |_| iop.random_elem());
         // Make and compute accum data
@@ -15,17 +15,21 @@
 use alloc::vec::Vec;
 
 use log::debug;
+use rayon::prelude::*;
 use risc0_core::field::ExtElem;
 
 use crate::{
     core::{config::ConfigHash, log2_ceil},
     hal::{Buffer, Hal},
+    params::ProofParams,
+    pow::{pow_check, pow_digest, CHALLENGE_ELEMS, MAX_NONCE},
     prove::{merkle::MerkleTreeProver, write_iop::WriteIOP},
-    FRI_FOLD, FRI_MIN_DEGREE, INV_RATE, QUERIES,
+    FRI_MIN_DEGREE,
 };
 
 struct ProveRoundInfo<H: Hal> {
     domain: usize,
+    fold: usize,
     coeffs: H::BufferElem,
     merkle: MerkleTreeProver<H>,
 }
@@ -36,13 +40,20 @@ impl<H: Hal> ProveRoundInfo<H> {
     /// produce the evaluations of the polynomial, the merkle tree
     /// committing to the evaluation, and the coefficients of the folded
     /// polynomial.
-    pub fn new(hal: &H, iop: &mut WriteIOP<H::Field, H::Rng>, coeffs: &H::BufferElem) -> Self {
+    pub fn new(
+        hal: &H,
+        iop: &mut WriteIOP<H::Field, H::Rng>,
+        params: &ProofParams,
+        coeffs: &H::BufferElem,
+    ) -> Self {
         debug!("Doing FRI folding");
         let ext_size = H::ExtElem::EXT_SIZE;
+        let inv_rate = params.inv_rate();
+        let fold = params.fri_fold();
         // Get the number of coefficients of the polynomial over the extension field.
         let size = coeffs.size() / ext_size;
         // Get a larger domain to interpolate over.
-        let domain = size * INV_RATE;
+        let domain = size * inv_rate;
         // Allocate space in which to put the interpolated values.
         let evaluated = hal.alloc_elem("evaluated", domain * ext_size);
         // Put in the coefficients, padding out with zeros so that we are left with the
@@ -50,55 +61,79 @@ impl<H: Hal> ProveRoundInfo<H> {
         hal.batch_expand(&evaluated, coeffs, ext_size);
         // Evaluate the NTT in-place, filling the buffer with the evaluations of the
         // polynomial.
-        hal.batch_evaluate_ntt(&evaluated, ext_size, log2_ceil(INV_RATE));
+        hal.batch_evaluate_ntt(&evaluated, ext_size, log2_ceil(inv_rate));
         // Compute a Merkle tree committing to the polynomial evaluations.
-        let merkle = MerkleTreeProver::new(
+        let merkle = MerkleTreeProver::new_with_cap(
             hal,
             &evaluated,
-            domain / FRI_FOLD,
-            FRI_FOLD * ext_size,
-            QUERIES,
+            domain / fold,
+            fold * ext_size,
+            params.queries(),
+            params.merkle_cap(),
         );
         // Send the merkle tree (as a commitment) to the virtual IOP verifier
         merkle.commit(iop);
         // Retrieve from the IOP verifier a random value to mix the polynomial slices.
         let fold_mix = iop.random_ext_elem();
         // Create a buffer to hold the mixture of slices.
-        let out_coeffs = hal.alloc_elem("out_coeffs", size / FRI_FOLD * ext_size);
+        let out_coeffs = hal.alloc_elem("out_coeffs", size / fold * ext_size);
         // Compute the folded polynomial
         hal.fri_fold(&out_coeffs, coeffs, &fold_mix);
         ProveRoundInfo {
             domain,
+            fold,
             coeffs: out_coeffs,
             merkle,
         }
     }
 
-    pub fn prove_query(&mut self, iop: &mut WriteIOP<H::Field, H::Rng>, pos: &mut usize) {
-        // Compute which group we are in
-        let group = *pos % (self.domain / FRI_FOLD);
+    pub fn prove_queries(&mut self, iop: &mut WriteIOP<H::Field, H::Rng>, pos: &mut [usize]) {
+        // Compute which group each query is in
+        for pos in pos.iter_mut() {
+            *pos %= self.domain / self.fold;
+        }
         // Generate the proof
-        self.merkle.prove(iop, group);
-        // Update pos
-        *pos = group;
+        self.merkle.prove_batch(iop, pos);
     }
 }
 
+/// Grind for a nonce which satisfies the proof-of-work, then write the nonce
+/// and commit to the resulting digest.
+#[tracing::instrument(skip_all)]
+fn grind<H: Hal>(iop: &mut WriteIOP<H::Field, H::Rng>, pow_bits: usize) {
+    let seed: Vec<H::Elem> = (0..CHALLENGE_ELEMS).map(|_| iop.random_elem()).collect();
+    let challenge = *H::Hash::hash_elem_slice(&seed);
+    let nonce = (0..MAX_NONCE)
+        .into_par_iter()
+        .find_first(|nonce| {
+            pow_check(
+                &pow_digest::<H::Field, H::Hash>(&challenge, *nonce),
+                pow_bits,
+            )
+        })
+        .expect("No proof-of-work nonce found");
+    debug!("Found proof-of-work nonce {nonce} for {pow_bits} bits");
+    iop.write_u32_slice(&[nonce]);
+    iop.commit(&pow_digest::<H::Field, H::Hash>(&challenge, nonce));
+}
+
 #[tracing::instrument(skip_all)]
 pub fn fri_prove<H: Hal, F>(
     hal: &H,
     iop: &mut WriteIOP<H::Field, H::Rng>,
+    params: &ProofParams,
     coeffs: &H::BufferElem,
     mut f: F,
 ) where
-    F: FnMut(&mut WriteIOP<H::Field, H::Rng>, usize),
+    F: FnMut(&mut WriteIOP<H::Field, H::Rng>, &[usize]),
 {
+    iop.label("fri");
     let ext_size = H::ExtElem::EXT_SIZE;
-    let orig_domain = coeffs.size() / ext_size * INV_RATE;
+    let orig_domain = coeffs.size() / ext_size * params.inv_rate();
     let mut rounds = Vec::new();
     let mut coeffs = coeffs.clone();
     while coeffs.size() / ext_size > FRI_MIN_DEGREE {
-        let round = ProveRoundInfo::new(hal, iop, &coeffs);
+        let round = ProveRoundInfo::new(hal, iop, params, &coeffs);
         coeffs = round.coeffs.clone();
         rounds.push(round);
     }
@@ -112,16 +147,203 @@ pub fn fri_prove<H: Hal, F>(
         let digest = H::Hash::hash_elem_slice(view);
         iop.commit(&digest);
     });
-    // Do queries
+    // Grind before sampling the queries
+    if params.pow_bits() > 0 {
+        iop.label("fri.pow");
+        grind::<H>(iop, params.pow_bits());
+    }
+    // Do queries, either one at a time or all together as a single batch
     debug!("Doing Queries");
-    for _ in 0..QUERIES {
-        // Get a 'random' index.
-        let mut pos = iop.random_bits(log2_ceil(orig_domain)) as usize;
-        // Do the 'inner' proof for this index
-        f(iop, pos);
+    iop.label("fri.query");
+    let batch = if params.merkle_batch() {
+        params.queries()
+    } else {
+        1
+    };
+    for _ in 0..params.queries() / batch {
+        // Get 'random' indexes.
+        let mut pos: Vec<usize> = (0..batch)
+            .map(|_| iop.random_bits(log2_ceil(orig_domain)) as usize)
+            .collect();
+        // Do the 'inner' proof for these indexes
+        f(iop, &pos);
         // Write the per-round proofs
         for round in rounds.iter_mut() {
-            round.prove_query(iop, &mut pos);
+            round.prove_queries(iop, &mut pos);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+    use risc0_core::field::{
+        baby_bear::{BabyBear, BabyBearElem, BabyBearExtElem},
+        Elem, RootsOfUnity,
+    };
+
+    use super::*;
+    use crate::{
+        adapter::{MixState, PolyExt},
+        core::{
+            config::{HashSuite, HashSuiteSha256},
+            ntt::bit_reverse,
+            sha_cpu,
+        },
+        hal::cpu::CpuHal,
+        params::{MAX_FRI_FOLD, MIN_FRI_FOLD},
+        transcript::Event,
+        verify::{fri::fri_verify, read_iop::ReadIOP, CpuVerifyHal, VerificationError, VerifyHal},
+    };
+
+    struct MockCircuit {}
+
+    impl PolyExt<BabyBear> for MockCircuit {
+        fn poly_ext(
+            &self,
+            _mix: &BabyBearExtElem,
+            _u: &[BabyBearExtElem],
+            _args: &[&[BabyBearElem]],
+        ) -> MixState<BabyBearExtElem> {
+            unimplemented!()
+        }
+    }
+
+    type HS = HashSuiteSha256<BabyBear, sha_cpu::Impl>;
+    type VerifierHal<'a> = CpuVerifyHal<'a, BabyBear, HS, MockCircuit>;
+    type Rng = <HS as HashSuite<BabyBear>>::Rng;
+
+    const DEGREE: usize = 4096;
+
+    fn random_poly() -> Vec<BabyBearExtElem> {
+        let mut rng = thread_rng();
+        (0..DEGREE)
+            .map(|_| BabyBearExtElem::random(&mut rng))
+            .collect()
+    }
+
+    // Runs FRI on `poly`, whose coefficients are in natural order.
+    fn prove(params: &ProofParams, poly: &[BabyBearExtElem]) -> WriteIOP<BabyBear, Rng> {
+        let hal = CpuHal::<BabyBear, HS>::new();
+        // The prover expects the coefficients bit reversed, one subelement per
+        // row.
+        let mut rev = poly.to_vec();
+        bit_reverse(&mut rev);
+        let mut coeffs = vec![BabyBearElem::ZERO; DEGREE * BabyBearExtElem::EXT_SIZE];
+        for (i, coeff) in rev.iter().enumerate() {
+            for (j, elem) in coeff.subelems().iter().enumerate() {
+                coeffs[j * DEGREE + i] = *elem;
+            }
+        }
+        let coeffs = hal.copy_from_elem("coeffs", &coeffs);
+        let mut iop = WriteIOP::with_transcript();
+        fri_prove(&hal, &mut iop, params, &coeffs, |_, _| {});
+        iop
+    }
+
+    // Checks the FRI proof in `proof`, taking the goals directly from `poly`.
+    fn verify(
+        params: &ProofParams,
+        poly: &[BabyBearExtElem],
+        proof: &[u32],
+    ) -> Result<(), VerificationError> {
+        let circuit = MockCircuit {};
+        let hal = VerifierHal::new(&circuit);
+        let mut iop = ReadIOP::new(proof);
+        let domain = DEGREE * params.inv_rate();
+        let gen = BabyBearElem::ROU_FWD[log2_ceil(domain)];
+        fri_verify(&hal, &mut iop, params, DEGREE, |_, idxs| {
+            Ok(idxs
+                .iter()
+                .map(|idx| hal.poly_eval(poly, BabyBearExtElem::from_subfield(&gen.pow(*idx))))
+                .collect())
+        })?;
+        iop.verify_complete()
+    }
+
+    #[test]
+    fn round_trip_folds() {
+        let poly = random_poly();
+        let mut fold = MIN_FRI_FOLD;
+        while fold <= MAX_FRI_FOLD {
+            let params = ProofParams::default().with_fri_fold(fold);
+            let iop = prove(&params, &poly);
+            verify(&params, &poly, &iop.proof).unwrap();
+            fold *= 2;
+        }
+    }
+
+    // Finds the offset of the proof-of-work nonce from the prover's transcript.
+    fn nonce_offset(iop: &WriteIOP<BabyBear, Rng>) -> usize {
+        let mut offset = 0;
+        for entry in iop.transcript().unwrap().entries() {
+            if let Event::Data { words, .. } = entry.event {
+                if entry.label == "fri.pow" {
+                    assert_eq!(words, 1);
+                    return offset;
+                }
+                offset += words;
+            }
         }
+        panic!("No proof-of-work nonce in the transcript");
+    }
+
+    #[test]
+    fn pow_round_trip() {
+        let poly = random_poly();
+        let params = ProofParams::default().with_pow_bits(12);
+        let iop = prove(&params, &poly);
+        verify(&params, &poly, &iop.proof).unwrap();
+        // Without the proof-of-work, the queries differ.
+        assert!(verify(&ProofParams::default(), &poly, &iop.proof).is_err());
+    }
+
+    #[test]
+    fn pow_bad_nonce() {
+        let params = ProofParams::default().with_pow_bits(12);
+        // The prover picks the smallest valid nonce, so any smaller one fails.
+        let (poly, iop, offset) = loop {
+            let poly = random_poly();
+            let iop = prove(&params, &poly);
+            let offset = nonce_offset(&iop);
+            if iop.proof[offset] > 0 {
+                break (poly, iop, offset);
+            }
+        };
+        for nonce in [iop.proof[offset] - 1, MAX_NONCE, u32::MAX] {
+            let mut proof = iop.proof.clone();
+            proof[offset] = nonce;
+            assert!(matches!(
+                verify(&params, &poly, &proof),
+                Err(VerificationError::InvalidProof)
+            ));
+        }
+    }
+
+    #[test]
+    fn bad_length() {
+        let poly = random_poly();
+        let params = ProofParams::default();
+        let mut proof = prove(&params, &poly).proof;
+        proof.push(0);
+        assert!(matches!(
+            verify(&params, &poly, &proof),
+            Err(VerificationError::ReceiptFormatError)
+        ));
+        proof.truncate(proof.len() / 2);
+        assert!(matches!(
+            verify(&params, &poly, &proof),
+            Err(VerificationError::ReceiptFormatError)
+        ));
+    }
+
+    #[test]
+    fn wrong_poly() {
+        let params = ProofParams::default();
+        let iop = prove(&params, &random_poly());
+        assert!(matches!(
+            verify(&params, &random_poly(), &iop.proof),
+            Err(VerificationError::InvalidProof)
+        ));
     }
 }
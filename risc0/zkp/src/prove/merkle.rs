@@ -51,8 +51,21 @@ impl<H: Hal> MerkleTreeProver<H> {
     /// cols: `count = circuit_cols`
     #[tracing::instrument(name = "MerkleTreeProver", skip_all)]
     pub fn new(hal: &H, matrix: &H::BufferElem, rows: usize, cols: usize, queries: usize) -> Self {
+        Self::new_with_cap(hal, matrix, rows, cols, queries, None)
+    }
+
+    /// Generate a merkle tree from a matrix of values, with an explicit cap
+    /// height. See [MerkleTreeParams::new_with_cap].
+    pub fn new_with_cap(
+        hal: &H,
+        matrix: &H::BufferElem,
+        rows: usize,
+        cols: usize,
+        queries: usize,
+        cap: Option<usize>,
+    ) -> Self {
         assert_eq!(matrix.size(), rows * cols);
-        let params = MerkleTreeParams::new(rows, cols, queries);
+        let params = MerkleTreeParams::new_with_cap(rows, cols, queries, cap);
         // Allocate nodes
         let nodes = hal.alloc_digest("nodes", rows * 2);
         // SHA-256 hash each column
@@ -116,6 +129,53 @@ impl<H: Hal> MerkleTreeProver<H> {
         }
         out
     }
+
+    /// Generate a multi-proof for a batch of indexes.
+    ///
+    /// The format of the proof is:
+    /// 1) The columns of each distinct index, in increasing order
+    /// 2) For each layer from the leaves up to the top, the 'other' digests
+    ///    which cannot be computed from the layer below, in increasing order
+    ///
+    /// Repeated indexes and shared siblings are only written once. A batch of
+    /// a single index produces exactly the same proof as [Self::prove].
+    pub fn prove_batch(&self, iop: &mut WriteIOP<H::Field, H::Rng>, idxs: &[usize]) {
+        let mut leaves = idxs.to_vec();
+        leaves.sort_unstable();
+        leaves.dedup();
+        self.matrix.view(|view| {
+            for &idx in leaves.iter() {
+                assert!(idx < self.params.row_size);
+                let col: Vec<H::Elem> = (0..self.params.col_size)
+                    .map(|i| view[idx + i * self.params.row_size])
+                    .collect();
+                iop.write_field_elem_slice::<H::Elem>(col.as_slice());
+            }
+        });
+        let mut layer: Vec<usize> = leaves
+            .iter()
+            .map(|idx| idx + self.params.row_size)
+            .collect();
+        while layer
+            .first()
+            .map_or(false, |idx| *idx >= 2 * self.params.top_size)
+        {
+            let mut next = Vec::with_capacity(layer.len());
+            let mut i = 0;
+            while i < layer.len() {
+                let idx = layer[i];
+                if idx % 2 == 0 && layer.get(i + 1) == Some(&(idx + 1)) {
+                    // Both children are known to the verifier.
+                    i += 2;
+                } else {
+                    iop.write_pod_slice(&[self.nodes[idx ^ 1]]);
+                    i += 1;
+                }
+                next.push(idx / 2);
+            }
+            layer = next;
+        }
+    }
 }
 
 #[cfg(test)]
@@ -211,7 +271,8 @@ mod tests {
             iop.proof[manip_idx] ^= 1;
         }
         let mut r_iop = ReadIOP::<BabyBear, HS::Rng>::new(&iop.proof);
-        let verifier = MerkleTreeVerifier::<VerifierHal<HS>>::new(&mut r_iop, rows, cols, queries);
+        let verifier =
+            MerkleTreeVerifier::<VerifierHal<HS>>::new(&mut r_iop, rows, cols, queries).unwrap();
         assert_eq!(verifier.root(), prover.root());
         let mut err = false;
         for query in 0..queries {
@@ -245,7 +306,7 @@ mod tests {
             }
         }
         if !err {
-            r_iop.verify_complete();
+            r_iop.verify_complete().unwrap();
         }
     }
 
@@ -260,6 +321,57 @@ mod tests {
         possibly_bad_verify::<PoseidonSuite>(rows, cols, queries, bad_query, manipulate_proof);
     }
 
+    fn batch_verify<HS: HashSuite<BabyBear>>(
+        rows: usize,
+        cols: usize,
+        queries: usize,
+        cap: Option<usize>,
+        manipulate_proof: bool,
+    ) -> Result<(), VerificationError> {
+        let hal = CpuHal::<BabyBear, HS>::new();
+        let data: Vec<BabyBearElem> = (0..rows * cols)
+            .map(|val| BabyBearElem::from_u64((u32::MAX / 2) as u64 - val as u64))
+            .collect();
+        let matrix = hal.copy_from_elem("matrix", data.as_slice());
+        let prover = MerkleTreeProver::new_with_cap(&hal, &matrix, rows, cols, queries, cap);
+
+        let mut iop = WriteIOP::<BabyBear, HS::Rng>::new();
+        prover.commit(&mut iop);
+        let idxs: Vec<usize> = (0..queries)
+            .map(|_| iop.rng.random_bits(log2_ceil(rows)) as usize)
+            .collect();
+        prover.prove_batch(&mut iop, &idxs);
+        if manipulate_proof {
+            let mut rng = rand::thread_rng();
+            let manip_idx = rng.gen::<usize>() % iop.proof.len();
+            iop.proof[manip_idx] ^= 1;
+        }
+
+        let mut r_iop = ReadIOP::<BabyBear, HS::Rng>::new(&iop.proof);
+        let verifier = MerkleTreeVerifier::<VerifierHal<HS>>::new_with_cap(
+            &mut r_iop, rows, cols, queries, cap,
+        )?;
+        let r_idxs: Vec<usize> = (0..queries)
+            .map(|_| r_iop.random_bits(log2_ceil(rows)) as usize)
+            .collect();
+        assert_eq!(r_idxs, idxs);
+        let out = verifier.verify_batch(&mut r_iop, &r_idxs)?;
+        for (col, r_idx) in out.iter().zip(r_idxs.iter()) {
+            for c_idx in 0..cols {
+                assert_eq!(col[c_idx], data[r_idx + c_idx * rows]);
+            }
+        }
+        if verifier.root() != prover.root() {
+            return Err(VerificationError::InvalidProof);
+        }
+        r_iop.verify_complete()
+    }
+
+    fn batch_verify_all(rows: usize, cols: usize, queries: usize, cap: Option<usize>) {
+        batch_verify::<ShaSuite>(rows, cols, queries, cap, false).unwrap();
+        batch_verify::<PoseidonSuite>(rows, cols, queries, cap, false).unwrap();
+    }
+
     fn randomize_sizes() -> (usize, usize, usize) {
         // Chooses random values of `rows`, `cols`, and `queries` such that:
         // `rows` is a power of 2
@@ -341,6 +453,83 @@ mod tests {
         possibly_bad_verify_all(rows, cols, queries, bad_query, false);
     }
 
+    #[test]
+    fn merkle_cpu_batch_verify() {
+        batch_verify_all(1, 1, 1, None);
+        batch_verify_all(4, 4, 2, None);
+        batch_verify_all(1024, 8, 50, None);
+        batch_verify_all(1024, 8, 50, Some(0));
+        batch_verify_all(1024, 8, 50, Some(4));
+        for _rep in 0..100 {
+            let (rows, cols, queries) = randomize_sizes();
+            let cap = rand::thread_rng().gen::<usize>() % 12;
+            batch_verify_all(rows, cols, queries, Some(cap));
+        }
+    }
+
+    #[test]
+    fn merkle_cpu_batch_matches_single() {
+        // A batch of one index must produce exactly the same proof as `prove`.
+        let hal = CpuHal::<BabyBear, ShaSuite>::new();
+        let prover = init_prover(&hal, 256, 3, 16);
+        for idx in [0, 1, 100, 255] {
+            let mut single = WriteIOP::<BabyBear, <ShaSuite as HashSuite<BabyBear>>::Rng>::new();
+            prover.prove(&mut single, idx);
+            let mut batch = WriteIOP::<BabyBear, <ShaSuite as HashSuite<BabyBear>>::Rng>::new();
+            prover.prove_batch(&mut batch, &[idx]);
+            assert_eq!(single.proof, batch.proof);
+        }
+    }
+
+    #[test]
+    fn merkle_cpu_batch_is_smaller() {
+        let hal = CpuHal::<BabyBear, ShaSuite>::new();
+        let prover = init_prover(&hal, 1 << 12, 4, 50);
+        let mut rng = rand::thread_rng();
+        let idxs: Vec<usize> = (0..50).map(|_| rng.gen::<usize>() % (1 << 12)).collect();
+        let mut single = WriteIOP::<BabyBear, <ShaSuite as HashSuite<BabyBear>>::Rng>::new();
+        for idx in idxs.iter() {
+            prover.prove(&mut single, *idx);
+        }
+        let mut batch = WriteIOP::<BabyBear, <ShaSuite as HashSuite<BabyBear>>::Rng>::new();
+        prover.prove_batch(&mut batch, &idxs);
+        assert!(batch.proof.len() < single.proof.len());
+    }
+
+    #[test]
+    fn merkle_cpu_batch_bad_row() {
+        let hal = CpuHal::<BabyBear, ShaSuite>::new();
+        let prover = init_prover(&hal, 64, 4, 8);
+        let mut iop = WriteIOP::<BabyBear, <ShaSuite as HashSuite<BabyBear>>::Rng>::new();
+        prover.commit(&mut iop);
+        prover.prove_batch(&mut iop, &[3, 17, 40]);
+        let mut r_iop =
+            ReadIOP::<BabyBear, <ShaSuite as HashSuite<BabyBear>>::Rng>::new(&iop.proof);
+        let verifier =
+            MerkleTreeVerifier::<VerifierHal<ShaSuite>>::new(&mut r_iop, 64, 4, 8).unwrap();
+        assert!(matches!(
+            verifier.verify_batch(&mut r_iop, &[3, 18, 40]),
+            Err(VerificationError::InvalidProof)
+        ));
+        let mut r_iop =
+            ReadIOP::<BabyBear, <ShaSuite as HashSuite<BabyBear>>::Rng>::new(&iop.proof);
+        let verifier =
+            MerkleTreeVerifier::<VerifierHal<ShaSuite>>::new(&mut r_iop, 64, 4, 8).unwrap();
+        assert!(matches!(
+            verifier.verify_batch(&mut r_iop, &[3, 64]),
+            Err(VerificationError::MerkleQueryOutOfRange { idx: 64, rows: 64 })
+        ));
+    }
+
+    #[test]
+    #[should_panic]
+    fn merkle_cpu_batch_verify_manipulated() {
+        for _rep in 0..50 {
+            let (rows, cols, queries) = randomize_sizes();
+            batch_verify::<ShaSuite>(rows, cols, queries, Some(2), true).unwrap();
+        }
+    }
+
     #[test]
     #[should_panic]
     fn merkle_cpu_1_1_1_verify_manipulated() {
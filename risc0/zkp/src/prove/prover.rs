@@ -16,20 +16,42 @@ use log::debug;
 use risc0_core::field::{Elem, ExtElem, RootsOfUnity};
 
 use crate::{
+    adapter::{
+        CircuitDef, CircuitStepHandler, REGISTER_GROUP_ACCUM, REGISTER_GROUP_CODE,
+        REGISTER_GROUP_DATA,
+    },
+    core::log2_ceil,
     core::{
         config::ConfigHash,
         poly::{poly_divide, poly_interpolate},
     },
     hal::{Buffer, EvalCheck, Hal},
-    prove::{fri::fri_prove, poly_group::PolyGroup, write_iop::WriteIOP},
+    params::ProofParams,
+    prove::{
+        adapter::ProveAdapter, executor::Executor, fri::fri_prove, poly_group::PolyGroup,
+        write_iop::WriteIOP,
+    },
     taps::TapSet,
+    transcript::Transcript,
     INV_RATE,
 };
 
 /// Object to generate a zero-knowledge proof of the execution of some circuit.
+///
+/// Memory use is dominated by the committed groups. For each group of `count`
+/// columns the prover keeps the coefficients (`count * cycles` elements), the
+/// low-degree extension (`count * cycles * inv_rate` elements) and its Merkle
+/// tree (`2 * cycles * inv_rate` digests). The check polynomial is evaluated
+/// over the whole extended domain at once and reads every group, so all of
+/// them must be resident at that point, which sets the peak. If `inv_rate`
+/// differs from [INV_RATE], each group is additionally re-evaluated on the
+/// `cycles * INV_RATE` domain for the check. For the rv32im circuit (265
+/// columns) on the CPU HAL, the peak resident set measured ~310 MiB at po2 15
+/// and ~2.3 GiB at po2 18 with the default [ProofParams].
 pub struct Prover<'a, H: Hal> {
     hal: &'a H,
     taps: &'a TapSet<'a>,
+    params: ProofParams,
     iop: WriteIOP<H::Field, H::Rng>,
     groups: Vec<Option<PolyGroup<H>>>,
     cycles: usize,
@@ -45,13 +67,56 @@ fn make_coeffs<H: Hal>(hal: &H, buf: H::BufferElem, count: usize) -> H::BufferEl
     buf
 }
 
+// Evaluates the coefficients of a group on the domain used to compute the
+// check polynomial, which is always `steps * INV_RATE` points regardless of
+// the rate used for the commitment.
+fn eval_for_check<H: Hal>(hal: &H, coeffs: &H::BufferElem, count: usize) -> H::BufferElem {
+    let evaluated = hal.alloc_elem("eval_for_check", coeffs.size() * INV_RATE);
+    let coeffs_rev = hal.alloc_elem("coeffs_rev", coeffs.size());
+    hal.eltwise_copy_elem(&coeffs_rev, coeffs);
+    hal.batch_bit_reverse(&coeffs_rev, count);
+    hal.batch_expand(&evaluated, &coeffs_rev, count);
+    hal.batch_evaluate_ntt(&evaluated, count, log2_ceil(INV_RATE));
+    evaluated
+}
+
 impl<'a, H: Hal> Prover<'a, H> {
-    /// Creates a new prover.
+    /// Creates a new prover using the default [ProofParams].
     pub fn new(hal: &'a H, taps: &'a TapSet) -> Self {
+        Self::new_with_params(hal, taps, ProofParams::default())
+    }
+
+    /// Creates a new prover which uses the given [ProofParams].
+    ///
+    /// The parameters are written to the start of the seal and committed to
+    /// the IOP so that the verifier can check them.
+    pub fn new_with_params(hal: &'a H, taps: &'a TapSet, params: ProofParams) -> Self {
+        Self::new_with_iop(hal, taps, params, WriteIOP::new())
+    }
+
+    /// Creates a new prover which records a [Transcript] of its IOP; see
+    /// [Prover::finalize_with_transcript].
+    pub fn new_with_transcript(hal: &'a H, taps: &'a TapSet, params: ProofParams) -> Self {
+        Self::new_with_iop(hal, taps, params, WriteIOP::with_transcript())
+    }
+
+    fn new_with_iop(
+        hal: &'a H,
+        taps: &'a TapSet,
+        params: ProofParams,
+        mut iop: WriteIOP<H::Field, H::Rng>,
+    ) -> Self {
+        if let Err(err) = params.validate() {
+            panic!("Invalid proof parameters: {err}");
+        }
+        iop.label("params");
+        iop.write_u32_slice(&params.encode());
+        iop.commit(&params.digest());
         Self {
             hal,
             taps,
-            iop: WriteIOP::new(),
+            params,
+            iop,
             groups: std::iter::repeat_with(|| None)
                 .take(taps.num_groups())
                 .collect(),
@@ -60,6 +125,11 @@ impl<'a, H: Hal> Prover<'a, H> {
         }
     }
 
+    /// The [ProofParams] used by this prover.
+    pub fn params(&self) -> &ProofParams {
+        &self.params
+    }
+
     /// Accesses the prover's IOP to commit or read random data.
     pub fn iop(&mut self) -> &mut WriteIOP<H::Field, H::Rng> {
         &mut self.iop
@@ -70,6 +140,11 @@ impl<'a, H: Hal> Prover<'a, H> {
     pub fn set_po2(&mut self, po2: usize) {
         assert_eq!(self.po2, usize::MAX);
         assert_eq!(self.cycles, 0);
+        assert!(
+            po2 + log2_ceil(self.params.inv_rate()) <= H::Elem::MAX_ROU_PO2,
+            "po2 {po2} is too large for inv_rate {}",
+            self.params.inv_rate()
+        );
         self.po2 = po2;
         self.cycles = 1 << po2;
     }
@@ -93,9 +168,11 @@ impl<'a, H: Hal> Prover<'a, H> {
             coeffs,
             group_size,
             self.cycles,
+            &self.params,
             "data",
         ));
 
+        self.iop.label(self.taps.group_name(tap_group_index));
         group_ref.merkle.commit(&mut self.iop);
 
         debug!(
@@ -105,12 +182,78 @@ impl<'a, H: Hal> Prover<'a, H> {
         );
     }
 
+    /// Proves the execution of a circuit and returns the seal.
+    ///
+    /// The `executor` must have been run to completion and finalized. This
+    /// writes its outputs and commits to its code, data and accum groups in
+    /// the order expected by [verify](crate::verify::verify), then finalizes
+    /// the proof.
+    #[tracing::instrument(skip_all)]
+    pub fn prove_circuit<C, S, E>(
+        mut self,
+        executor: &mut Executor<H::Field, C, S>,
+        eval: &E,
+    ) -> Vec<u32>
+    where
+        C: 'static + CircuitDef<H::Field>,
+        S: CircuitStepHandler<H::Elem>,
+        E: EvalCheck<H>,
+    {
+        let hal = self.hal;
+        let mut adapter = ProveAdapter::new(executor);
+        adapter.execute(self.iop());
+        self.set_po2(adapter.po2() as usize);
+        self.commit_group(
+            REGISTER_GROUP_CODE,
+            hal.copy_from_elem("code", &adapter.get_code().as_slice()),
+        );
+        self.commit_group(
+            REGISTER_GROUP_DATA,
+            hal.copy_from_elem("data", &adapter.get_data().as_slice()),
+        );
+        adapter.accumulate(self.iop());
+        self.commit_group(
+            REGISTER_GROUP_ACCUM,
+            hal.copy_from_elem("accum", &adapter.get_accum().as_slice()),
+        );
+        let mix = hal.copy_from_elem("mix", &adapter.get_mix().as_slice());
+        let out = hal.copy_from_elem("out", &adapter.get_io().as_slice());
+        self.finalize(&[&mix, &out], eval)
+    }
+
     /// Generates the proof and returns the seal.
     #[tracing::instrument(skip_all)]
-    pub fn finalize<E>(mut self, globals: &[&H::BufferElem], eval: &E) -> Vec<u32>
+    pub fn finalize<E>(self, globals: &[&H::BufferElem], eval: &E) -> Vec<u32>
     where
         E: EvalCheck<H>,
     {
+        let proof = self.finalize_iop(globals, eval).proof;
+        debug!("Proof size = {}", proof.len());
+        proof
+    }
+
+    /// Generates the proof and returns the seal, together with the transcript
+    /// of the IOP. The transcript is empty unless the prover was created with
+    /// [Prover::new_with_transcript].
+    pub fn finalize_with_transcript<E>(
+        self,
+        globals: &[&H::BufferElem],
+        eval: &E,
+    ) -> (Vec<u32>, Transcript)
+    where
+        E: EvalCheck<H>,
+    {
+        let mut iop = self.finalize_iop(globals, eval);
+        let transcript = iop.take_transcript().unwrap_or_default();
+        debug!("Proof size = {}", iop.proof.len());
+        (iop.proof, transcript)
+    }
+
+    fn finalize_iop<E>(mut self, globals: &[&H::BufferElem], eval: &E) -> WriteIOP<H::Field, H::Rng>
+    where
+        E: EvalCheck<H>,
+    {
+        self.iop.label("check");
         // Set the poly mix value, which is used for constraint compression in the
         // DEEP-ALI protocol.
         let poly_mix = self.iop.random_ext_elem();
@@ -124,11 +267,27 @@ impl<'a, H: Hal> Prover<'a, H> {
             .hal
             .alloc_elem("check_poly", H::ExtElem::EXT_SIZE * domain);
 
-        let groups: Vec<&_> = self
-            .groups
-            .iter()
-            .map(|pg| &pg.as_ref().unwrap().evaluated)
-            .collect();
+        // The committed evaluations can only be reused if they were computed on
+        // the same domain as the check polynomial.
+        let check_evals: Vec<H::BufferElem> = if self.params.inv_rate() == INV_RATE {
+            Vec::new()
+        } else {
+            self.groups
+                .iter()
+                .map(|pg| {
+                    let pg = pg.as_ref().unwrap();
+                    eval_for_check(self.hal, &pg.coeffs, pg.count)
+                })
+                .collect()
+        };
+        let groups: Vec<&_> = if check_evals.is_empty() {
+            self.groups
+                .iter()
+                .map(|pg| &pg.as_ref().unwrap().evaluated)
+                .collect()
+        } else {
+            check_evals.iter().collect()
+        };
         eval.eval_check(
             &check_poly,
             groups.as_slice(),
@@ -167,11 +326,20 @@ impl<'a, H: Hal> Prover<'a, H> {
         // invRate*size to 16 polys of size, without actually doing anything.
 
         // Make the PolyGroup + add it to the IOP;
-        let check_group = PolyGroup::new(self.hal, check_poly, H::CHECK_SIZE, self.cycles, "check");
+        drop(check_evals);
+        let check_group = PolyGroup::new(
+            self.hal,
+            check_poly,
+            H::CHECK_SIZE,
+            self.cycles,
+            &self.params,
+            "check",
+        );
         check_group.merkle.commit(&mut self.iop);
         debug!("checkGroup: {}", check_group.merkle.root());
 
         // Now pick a value for Z, which is used as the DEEP-ALI query point.
+        self.iop.label("deep");
         let z = self.iop.random_ext_elem();
         // #ifdef CIRCUIT_DEBUG
         //   if (badZ != Fp4(0)) {
@@ -227,7 +395,7 @@ impl<'a, H: Hal> Prover<'a, H> {
         }
 
         // Add in the coeffs of the check polynomials.
-        let z_pow = z.pow(H::ExtElem::EXT_SIZE);
+        let z_pow = z.pow(INV_RATE);
         let which = Vec::from_iter(0u32..H::CHECK_SIZE as u32);
         let xs = vec![z_pow; H::CHECK_SIZE];
         let out = self.hal.alloc_extelem("out", H::CHECK_SIZE);
@@ -318,7 +486,7 @@ impl<'a, H: Hal> Prover<'a, H> {
                     );
                 }
             }
-            // Divide check polys by z^EXT_SIZE
+            // Divide check polys by z^INV_RATE
             assert_eq!(
                 poly_divide(
                     &mut combos[combo_count * self.cycles..combo_count * self.cycles + self.cycles],
@@ -343,18 +511,22 @@ impl<'a, H: Hal> Prover<'a, H> {
             final_poly_coeffs.size() / H::ExtElem::EXT_SIZE
         );
 
-        fri_prove(self.hal, &mut self.iop, &final_poly_coeffs, |iop, idx| {
-            for pg in self.groups.iter() {
-                let pg = pg.as_ref().unwrap();
-
-                pg.merkle.prove(iop, idx);
-            }
-            check_group.merkle.prove(iop, idx);
-        });
+        let params = self.params;
+        fri_prove(
+            self.hal,
+            &mut self.iop,
+            &params,
+            &final_poly_coeffs,
+            |iop, idxs| {
+                for pg in self.groups.iter() {
+                    let pg = pg.as_ref().unwrap();
+
+                    pg.merkle.prove_batch(iop, idxs);
+                }
+                check_group.merkle.prove_batch(iop, idxs);
+            },
+        );
 
-        // Return final proof
-        let proof = self.iop.proof;
-        debug!("Proof size = {}", proof.len());
-        proof
+        self.iop
     }
 }
@@ -17,11 +17,15 @@ use core::marker::PhantomData;
 
 use risc0_core::field::{Elem, Field};
 
-use crate::core::{config::ConfigRng, sha::Digest};
+use crate::{
+    core::{config::ConfigRng, sha::Digest},
+    transcript::Transcript,
+};
 
 pub struct WriteIOP<F: Field, R: ConfigRng<F>> {
     pub proof: Vec<u32>,
     pub rng: R,
+    transcript: Option<Transcript>,
     phantom: PhantomData<F>,
 }
 
@@ -31,46 +35,105 @@ impl<F: Field, R: ConfigRng<F>> WriteIOP<F, R> {
         Self {
             proof: Vec::new(),
             rng: R::new(),
+            transcript: None,
             phantom: PhantomData,
         }
     }
 
+    /// Create a new empty proof which records a [Transcript] of everything
+    /// written, committed and drawn.
+    pub fn with_transcript() -> Self {
+        Self {
+            transcript: Some(Transcript::new()),
+            ..Self::new()
+        }
+    }
+
+    /// Sets the label of the transcript events from now on. This has no
+    /// effect unless a transcript is being recorded.
+    pub fn label(&mut self, label: &str) {
+        if let Some(transcript) = self.transcript.as_mut() {
+            transcript.label(label);
+        }
+    }
+
+    /// The transcript recorded so far, if enabled.
+    pub fn transcript(&self) -> Option<&Transcript> {
+        self.transcript.as_ref()
+    }
+
+    /// Removes and returns the transcript, if enabled.
+    pub fn take_transcript(&mut self) -> Option<Transcript> {
+        self.transcript.take()
+    }
+
+    fn record_data(&mut self, start: usize) {
+        if let Some(transcript) = self.transcript.as_mut() {
+            transcript.data(&self.proof[start..]);
+        }
+    }
+
+    fn record_random(&mut self, kind: impl core::fmt::Display, value: &[u32]) {
+        if let Some(transcript) = self.transcript.as_mut() {
+            transcript.random(kind, value);
+        }
+    }
+
     /// Called by the prover to write some data.
     ///
     /// This data is not cryptographically verifiable on its own,
     /// but is typically committed by a later hash which includes it.
     pub fn write_u32_slice(&mut self, slice: &[u32]) {
+        let start = self.proof.len();
         self.proof.extend_from_slice(slice);
+        self.record_data(start);
     }
 
     /// Write some field elements to this IOP.
     pub fn write_field_elem_slice<T: Elem>(&mut self, slice: &[T]) {
-        self.proof.extend(Elem::as_u32_slice(slice))
+        let start = self.proof.len();
+        self.proof.extend(Elem::as_u32_slice(slice));
+        self.record_data(start);
     }
 
     /// Write some plain old data to this IOP.
     pub fn write_pod_slice<T: bytemuck::Pod>(&mut self, slice: &[T]) {
-        self.proof.extend(bytemuck::cast_slice(slice))
+        let start = self.proof.len();
+        self.proof.extend(bytemuck::cast_slice(slice));
+        self.record_data(start);
     }
 
     /// Called by the prover to commit to some hash (usually data written
     /// earlier or a Merkle root).
     pub fn commit(&mut self, message: &Digest) {
+        if let Some(transcript) = self.transcript.as_mut() {
+            transcript.commit(message);
+        }
         self.rng.mix(message);
     }
 
     /// Get a cryptographically uniform u32
     pub fn random_bits(&mut self, bits: usize) -> u32 {
-        self.rng.random_bits(bits)
+        let value = self.rng.random_bits(bits);
+        self.record_random(format_args!("random_bits({bits})"), &[value]);
+        value
     }
 
     /// Get a cryptographically uniform field element
     pub fn random_elem(&mut self) -> F::Elem {
-        self.rng.random_elem()
+        let value = self.rng.random_elem();
+        if self.transcript.is_some() {
+            self.record_random("random_elem", &value.to_u32_words());
+        }
+        value
     }
 
     /// Get a cryptographically uniform extension field element
     pub fn random_ext_elem(&mut self) -> F::ExtElem {
-        self.rng.random_ext_elem()
+        let value = self.rng.random_ext_elem();
+        if self.transcript.is_some() {
+            self.record_random("random_ext_elem", &value.to_u32_words());
+        }
+        value
     }
 }
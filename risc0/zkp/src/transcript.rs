@@ -0,0 +1,365 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Transcripts of the interactions with an IOP, for debugging.
+//!
+//! When enabled, the prover's [WriteIOP](crate::prove::write_iop::WriteIOP)
+//! and the verifier's [ReadIOP](crate::verify::read_iop::ReadIOP) record every
+//! write or read of proof data, every commitment and every random value drawn.
+//! For a valid proof, both transcripts are identical; otherwise, [diff] finds
+//! the first event where they disagree, e.g. a Fiat-Shamir mismatch or a read
+//! past the data written by the prover.
+//!
+//! Transcripts are formatted as text with one event per line:
+//!
+//! ```text
+//! 0 params data 6 9c1b3e2a0d7f5e41
+//! 1 params commit 5b1a...
+//! 2 main random_ext_elem 1 2 3 4
+//! ```
+//!
+//! Consecutive reads or writes with the same label are merged into a single
+//! `data` event, so the prover and verifier may use different chunk sizes.
+//! Each data event records the number of words and a fingerprint of them.
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+use anyhow::{anyhow, bail, Result};
+use hex::FromHex;
+
+use crate::core::digest::Digest;
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// A single event in a [Transcript].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// Proof data written by the prover or read by the verifier.
+    Data { words: usize, fingerprint: u64 },
+
+    /// A digest mixed into the Fiat-Shamir RNG.
+    Commit(Digest),
+
+    /// A random value drawn from the RNG, e.g. `random_bits(20)`, as words.
+    Random { kind: String, value: Vec<u32> },
+
+    /// A read of `words` words when only `remaining` were left.
+    Overrun { words: usize, remaining: usize },
+}
+
+/// An [Event], together with the label which was current when it occurred.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub label: String,
+    pub event: Event,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ", self.label)?;
+        match &self.event {
+            Event::Data { words, fingerprint } => write!(f, "data {words} {fingerprint:016x}"),
+            Event::Commit(digest) => write!(f, "commit {digest}"),
+            Event::Random { kind, value } => {
+                write!(f, "{kind}")?;
+                for word in value {
+                    write!(f, " {word}")?;
+                }
+                Ok(())
+            }
+            Event::Overrun { words, remaining } => write!(f, "overrun {words} {remaining}"),
+        }
+    }
+}
+
+/// The ordered events recorded by an IOP. See the [module](self)
+/// documentation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transcript {
+    entries: Vec<Entry>,
+    label: String,
+}
+
+impl Transcript {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            label: "main".into(),
+        }
+    }
+
+    /// The recorded entries.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Sets the label of the events recorded from now on.
+    pub fn label(&mut self, label: &str) {
+        self.label = label.into();
+    }
+
+    /// Records proof data.
+    pub fn data(&mut self, data: &[u32]) {
+        if let Some(Entry {
+            label,
+            event: Event::Data { words, fingerprint },
+        }) = self.entries.last_mut()
+        {
+            if *label == self.label {
+                *words += data.len();
+                *fingerprint = fingerprint_words(*fingerprint, data);
+                return;
+            }
+        }
+        self.push(Event::Data {
+            words: data.len(),
+            fingerprint: fingerprint_words(FNV_OFFSET, data),
+        });
+    }
+
+    /// Records a commitment.
+    pub fn commit(&mut self, digest: &Digest) {
+        self.push(Event::Commit(*digest));
+    }
+
+    /// Records a random value.
+    pub fn random(&mut self, kind: impl ToString, value: &[u32]) {
+        self.push(Event::Random {
+            kind: kind.to_string(),
+            value: value.to_vec(),
+        });
+    }
+
+    /// Records a read past the end of the proof.
+    pub fn overrun(&mut self, words: usize, remaining: usize) {
+        self.push(Event::Overrun { words, remaining });
+    }
+
+    fn push(&mut self, event: Event) {
+        self.entries.push(Entry {
+            label: self.label.clone(),
+            event,
+        });
+    }
+
+    /// Parses a transcript in the text format produced by [fmt::Display].
+    pub fn parse(text: &str) -> Result<Vec<Entry>> {
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(idx, line)| {
+                parse_entry(idx, line).map_err(|err| anyhow!("line {}: {err}: {line}", idx + 1))
+            })
+            .collect()
+    }
+}
+
+impl Default for Transcript {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for Transcript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, entry) in self.entries.iter().enumerate() {
+            writeln!(f, "{idx} {entry}")?;
+        }
+        Ok(())
+    }
+}
+
+fn fingerprint_words(mut hash: u64, words: &[u32]) -> u64 {
+    for word in words {
+        for byte in word.to_le_bytes() {
+            hash = (hash ^ byte as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+    hash
+}
+
+fn parse_entry(idx: usize, line: &str) -> Result<Entry> {
+    let mut fields = line.split_whitespace();
+    let mut next = || fields.next().ok_or_else(|| anyhow!("missing field"));
+    if next()?.parse::<usize>()? != idx {
+        bail!("unexpected index");
+    }
+    let label = next()?.into();
+    let kind = next()?;
+    let event = match kind {
+        "data" => Event::Data {
+            words: next()?.parse()?,
+            fingerprint: u64::from_str_radix(next()?, 16)?,
+        },
+        "commit" => Event::Commit(Digest::from_hex(next()?).map_err(|err| anyhow!("{err}"))?),
+        "overrun" => Event::Overrun {
+            words: next()?.parse()?,
+            remaining: next()?.parse()?,
+        },
+        kind if kind.starts_with("random") => Event::Random {
+            kind: kind.into(),
+            value: fields.map(str::parse).collect::<Result<_, _>>()?,
+        },
+        kind => bail!("unknown event {kind}"),
+    };
+    Ok(Entry { label, event })
+}
+
+/// The first point at which two transcripts differ.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    /// The index of the first differing entry.
+    pub index: usize,
+
+    /// The entry of the first transcript, or `None` if it ended.
+    pub lhs: Option<Entry>,
+
+    /// The entry of the second transcript, or `None` if it ended.
+    pub rhs: Option<Entry>,
+}
+
+impl Mismatch {
+    /// Describes the likely cause of the mismatch.
+    pub fn reason(&self) -> String {
+        match (&self.lhs, &self.rhs) {
+            (
+                _,
+                Some(Entry {
+                    event: Event::Overrun { .. },
+                    ..
+                }),
+            ) => "the verifier read past the end of the proof".into(),
+            (None, Some(_)) => "the second transcript has extra events".into(),
+            (Some(_), None) => "the second transcript ended early".into(),
+            (Some(lhs), Some(rhs)) => match (&lhs.event, &rhs.event) {
+                (Event::Random { .. }, Event::Random { .. }) => {
+                    "the random values differ; the RNG states diverged (Fiat-Shamir mismatch)"
+                        .into()
+                }
+                (Event::Commit(_), Event::Commit(_)) => "the committed digests differ".into(),
+                (Event::Data { words: a, .. }, Event::Data { words: b, .. }) if a != b => {
+                    format!("{a} words were written but {b} were read")
+                }
+                (Event::Data { .. }, Event::Data { .. }) => "the proof data differs".into(),
+                _ => "the IOP operations differ".into(),
+            },
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entry = |entry: &Option<Entry>| match entry {
+            Some(entry) => entry.to_string(),
+            None => "<end>".into(),
+        };
+        writeln!(
+            f,
+            "first mismatch at event {}: {}",
+            self.index,
+            self.reason()
+        )?;
+        writeln!(f, "< {}", entry(&self.lhs))?;
+        write!(f, "> {}", entry(&self.rhs))
+    }
+}
+
+/// Finds the first entry at which two transcripts differ, typically those of
+/// a prover and a verifier. Returns `None` if they are identical.
+pub fn diff(lhs: &[Entry], rhs: &[Entry]) -> Option<Mismatch> {
+    let index = lhs
+        .iter()
+        .zip(rhs)
+        .position(|(a, b)| a != b)
+        .unwrap_or_else(|| lhs.len().min(rhs.len()));
+    if index == lhs.len() && index == rhs.len() {
+        return None;
+    }
+    Some(Mismatch {
+        index,
+        lhs: lhs.get(index).cloned(),
+        rhs: rhs.get(index).cloned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcript() -> Transcript {
+        let mut transcript = Transcript::new();
+        transcript.label("params");
+        transcript.data(&[1, 2, 3]);
+        transcript.data(&[4]);
+        transcript.commit(&Digest::from([1, 2, 3, 4, 5, 6, 7, 8]));
+        transcript.label("fri");
+        transcript.random("random_bits(20)", &[12345]);
+        transcript.data(&[5, 6]);
+        transcript
+    }
+
+    #[test]
+    fn data_is_merged() {
+        let mut chunked = Transcript::new();
+        chunked.label("params");
+        chunked.data(&[1]);
+        chunked.data(&[2, 3, 4]);
+        assert_eq!(chunked.entries()[0], transcript().entries()[0]);
+        assert_eq!(
+            chunked.entries()[0].event,
+            Event::Data {
+                words: 4,
+                fingerprint: fingerprint_words(FNV_OFFSET, &[1, 2, 3, 4])
+            }
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let transcript = transcript();
+        let text = transcript.to_string();
+        assert_eq!(Transcript::parse(&text).unwrap(), transcript.entries());
+        assert!(Transcript::parse("0 main data x").is_err());
+        assert!(Transcript::parse("1 main data 1 0").is_err());
+    }
+
+    #[test]
+    fn diff_transcripts() {
+        let lhs = transcript();
+        assert_eq!(diff(lhs.entries(), lhs.entries()), None);
+
+        let mut rhs = transcript();
+        rhs.entries[2].event = Event::Random {
+            kind: "random_bits(20)".into(),
+            value: vec![54321],
+        };
+        let mismatch = diff(lhs.entries(), rhs.entries()).unwrap();
+        assert_eq!(mismatch.index, 2);
+        assert!(mismatch.reason().contains("Fiat-Shamir"));
+
+        let mut rhs = transcript();
+        rhs.overrun(4, 0);
+        let mismatch = diff(lhs.entries(), rhs.entries()).unwrap();
+        assert_eq!(mismatch.index, 4);
+        assert_eq!(mismatch.lhs, None);
+        assert!(mismatch.reason().contains("past the end"));
+    }
+}
@@ -12,21 +12,21 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use alloc::vec::Vec;
+use alloc::{borrow::Cow, vec::Vec};
 
 use risc0_core::field::Field;
 
 use crate::{
     adapter::{CircuitInfo, TapsProvider},
     taps::TapSet,
-    verify::{read_iop::ReadIOP, ConfigRng},
+    verify::{read_iop::ReadIOP, ConfigRng, VerificationError},
 };
 
 pub struct VerifyAdapter<'a, F: Field, C: CircuitInfo + TapsProvider> {
     circuit: &'a C,
     po2: u32,
     steps: usize,
-    pub out: Option<&'a [F::Elem]>,
+    pub out: Option<Cow<'a, [F::Elem]>>,
     pub mix: Vec<F::Elem>,
 }
 
@@ -45,18 +45,24 @@ impl<'a, F: Field, C: CircuitInfo + TapsProvider> VerifyAdapter<'a, F, C> {
         self.circuit.get_taps()
     }
 
-    pub fn execute<R: ConfigRng<F>>(&mut self, iop: &mut ReadIOP<'a, F, R>) {
+    pub fn execute<R: ConfigRng<F>>(
+        &mut self,
+        iop: &mut ReadIOP<'a, F, R>,
+    ) -> Result<(), VerificationError> {
         // Read the outputs + size
-        self.out = Some(iop.read_field_elem_slice(C::OUTPUT_SIZE));
-        self.po2 = match iop.read_u32s(1) {
+        iop.label("execute");
+        self.out = Some(iop.read_field_elem_slice(C::OUTPUT_SIZE)?);
+        self.po2 = match iop.read_u32s(1)? {
             &[po2] => po2,
             _ => unreachable!(),
         };
         self.steps = 1 << self.po2;
+        Ok(())
     }
 
     pub fn accumulate<R: ConfigRng<F>>(&mut self, iop: &mut ReadIOP<'a, F, R>) {
         // Fill in accum mix
+        iop.label("accumulate");
         self.mix = (0..C::MIX_SIZE).map(|_| iop.random_elem()).collect();
     }
 
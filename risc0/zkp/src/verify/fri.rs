@@ -19,8 +19,10 @@ use risc0_core::field::{Elem, ExtElem, RootsOfUnity};
 use super::VerifyHal;
 use crate::{
     core::{config::ConfigHash, log2_ceil},
+    params::ProofParams,
+    pow::{pow_check, pow_digest, CHALLENGE_ELEMS, MAX_NONCE},
     verify::{merkle::MerkleTreeVerifier, read_iop::ReadIOP, VerificationError},
-    FRI_FOLD, FRI_FOLD_PO2, FRI_MIN_DEGREE, INV_RATE, QUERIES,
+    FRI_MIN_DEGREE,
 };
 
 /// VerifyRoundInfo contains the data against which the queries for a particular
@@ -28,36 +30,63 @@ use crate::{
 /// the size of the domain of the polynomial, and the mixing parameter.
 struct VerifyRoundInfo<'a, H: VerifyHal> {
     domain: usize,
+    fold: usize,
     merkle: MerkleTreeVerifier<'a, H>,
     mix: H::ExtElem,
 }
 
 impl<'a, H: VerifyHal> VerifyRoundInfo<'a, H> {
-    pub fn new(iop: &mut ReadIOP<'a, H::Field, H::Rng>, in_domain: usize) -> Self {
-        let domain = in_domain / FRI_FOLD;
-        VerifyRoundInfo {
+    pub fn new(
+        iop: &mut ReadIOP<'a, H::Field, H::Rng>,
+        params: &ProofParams,
+        in_domain: usize,
+    ) -> Result<Self, VerificationError> {
+        let fold = params.fri_fold();
+        let domain = in_domain / fold;
+        Ok(VerifyRoundInfo {
             domain,
-            merkle: MerkleTreeVerifier::new(iop, domain, FRI_FOLD * H::ExtElem::EXT_SIZE, QUERIES),
+            fold,
+            merkle: MerkleTreeVerifier::new_with_cap(
+                iop,
+                domain,
+                fold * H::ExtElem::EXT_SIZE,
+                params.queries(),
+                params.merkle_cap(),
+            )?,
             mix: iop.random_ext_elem(),
-        }
+        })
     }
 
-    pub fn verify_query(
+    pub fn verify_queries(
         &mut self,
         hal: &H,
         iop: &mut ReadIOP<'a, H::Field, H::Rng>,
+        pos: &mut [usize],
+        goals: &mut [H::ExtElem],
+    ) -> Result<(), VerificationError> {
+        let groups: Vec<usize> = pos.iter().map(|pos| *pos % self.domain).collect();
+        // Get the column data
+        let rows = self.merkle.verify_batch(iop, &groups)?;
+        for ((pos, goal), data) in pos.iter_mut().zip(goals.iter_mut()).zip(rows) {
+            self.fold_query(hal, &data, pos, goal)?;
+        }
+        Ok(())
+    }
+
+    fn fold_query(
+        &self,
+        hal: &H,
+        data: &[H::Elem],
         pos: &mut usize,
         goal: &mut H::ExtElem,
     ) -> Result<(), VerificationError> {
         let quot = *pos / self.domain;
         let group = *pos % self.domain;
-        // Get the column data
-        let data = self.merkle.verify(iop, group)?;
-        let data_ext: Vec<_> = (0..FRI_FOLD)
+        let mut data_ext: Vec<_> = (0..self.fold)
             .map(|i| {
                 let mut inps = Vec::with_capacity(H::ExtElem::EXT_SIZE);
                 for j in 0..H::ExtElem::EXT_SIZE {
-                    inps.push(data[j * FRI_FOLD + i]);
+                    inps.push(data[j * self.fold + i]);
                 }
                 H::ExtElem::from_subelems(inps)
             })
@@ -67,9 +96,9 @@ impl<'a, H: VerifyHal> VerifyRoundInfo<'a, H> {
             return Err(VerificationError::InvalidProof);
         }
         // Compute the new goal + pos
-        let root_po2 = log2_ceil(FRI_FOLD * self.domain);
+        let root_po2 = log2_ceil(self.fold * self.domain);
         let inv_wk = H::Elem::ROU_REV[root_po2].pow(group);
-        *goal = hal.fold_eval(&mut data_ext.try_into().unwrap(), self.mix * inv_wk);
+        *goal = hal.fold_eval(&mut data_ext, self.mix * inv_wk);
         *pos = group;
         Ok(())
     }
@@ -78,60 +107,96 @@ impl<'a, H: VerifyHal> VerifyRoundInfo<'a, H> {
 pub fn fri_verify<'a, H: VerifyHal + 'a, F>(
     hal: &'a H,
     iop: &mut ReadIOP<'a, H::Field, H::Rng>,
+    params: &ProofParams,
     mut degree: usize,
     mut inner: F,
 ) -> Result<(), VerificationError>
 where
-    F: FnMut(&mut ReadIOP<'a, H::Field, H::Rng>, usize) -> Result<H::ExtElem, VerificationError>,
+    F: FnMut(
+        &mut ReadIOP<'a, H::Field, H::Rng>,
+        &[usize],
+    ) -> Result<Vec<H::ExtElem>, VerificationError>,
 {
-    let orig_domain = INV_RATE * degree;
+    iop.label("fri");
+    let fold = params.fri_fold();
+    let fold_po2 = params.fri_fold_po2();
+    let orig_domain = params.inv_rate() * degree;
     let mut domain = orig_domain;
     // Prep the folding verfiers
+    // Each round divides the degree by `fold` until it reaches FRI_MIN_DEGREE.
     let rounds_capacity =
-        (log2_ceil((degree + FRI_FOLD - 1) / FRI_FOLD) + FRI_FOLD_PO2 - 1) / FRI_FOLD_PO2;
+        (log2_ceil(degree).saturating_sub(log2_ceil(FRI_MIN_DEGREE)) + fold_po2 - 1) / fold_po2;
     let mut rounds = Vec::with_capacity(rounds_capacity);
     while degree > FRI_MIN_DEGREE {
-        rounds.push(VerifyRoundInfo::new(iop, domain));
-        domain /= FRI_FOLD;
-        degree /= FRI_FOLD;
+        rounds.push(VerifyRoundInfo::new(iop, params, domain)?);
+        domain /= fold;
+        degree /= fold;
     }
     // We want to minimize reallocation in verify, so make sure we
     // didn't have to reallocate.
     assert!(
-        rounds.len() < rounds_capacity,
+        rounds.len() <= rounds_capacity,
         "Did not allocate enough rounds; needed {} for degree {} but only allocated {}",
         rounds.len(),
         degree,
         rounds_capacity
     );
     // Grab the final coeffs + commit
-    let final_coeffs = iop.read_field_elem_slice(H::ExtElem::EXT_SIZE * degree);
-    let final_digest = H::Hash::hash_elem_slice(final_coeffs);
+    let final_coeffs = iop.read_field_elem_slice(H::ExtElem::EXT_SIZE * degree)?;
+    let final_digest = H::Hash::hash_elem_slice(&final_coeffs);
     iop.commit(&final_digest);
+    // Check the proof-of-work before sampling the queries
+    if params.pow_bits() > 0 {
+        iop.label("fri.pow");
+        let seed: Vec<H::Elem> = (0..CHALLENGE_ELEMS).map(|_| iop.random_elem()).collect();
+        let challenge = H::Hash::hash_elem_slice(&seed);
+        let nonce = match iop.read_u32s(1)? {
+            &[nonce] => nonce,
+            _ => unreachable!(),
+        };
+        if nonce >= MAX_NONCE {
+            return Err(VerificationError::InvalidProof);
+        }
+        let digest = pow_digest::<H::Field, H::Hash>(&challenge, nonce);
+        if !pow_check(&digest, params.pow_bits()) {
+            return Err(VerificationError::InvalidProof);
+        }
+        iop.commit(&digest);
+    }
     // Get the generator for the final polynomial evaluations
     let gen = <H::Elem as RootsOfUnity>::ROU_FWD[log2_ceil(domain)];
-    // Do queries
+    // Do queries, either one at a time or all together as a single batch
+    let batch = if params.merkle_batch() {
+        params.queries()
+    } else {
+        1
+    };
     let mut poly_buf: Vec<H::ExtElem> = Vec::with_capacity(degree);
-    for _ in 0..QUERIES {
-        let mut pos = iop.random_bits(log2_ceil(orig_domain)) as usize;
-        // Do the 'inner' verification for this index
-        let mut goal = inner(iop, pos)?;
+    iop.label("fri.query");
+    for _ in 0..params.queries() / batch {
+        let mut pos: Vec<usize> = (0..batch)
+            .map(|_| iop.random_bits(log2_ceil(orig_domain)) as usize)
+            .collect();
+        // Do the 'inner' verification for these indexes
+        let mut goals = inner(iop, &pos)?;
         // Verify the per-round proofs
         for round in &mut rounds {
-            round.verify_query(hal, iop, &mut pos, &mut goal)?;
+            round.verify_queries(hal, iop, &mut pos, &mut goals)?;
         }
         // Do final verification
-        let x = gen.pow(pos);
+        for (pos, goal) in pos.iter().zip(goals.iter()) {
+            let x = gen.pow(*pos);
 
-        poly_buf.clear();
-        poly_buf.extend((0..degree).map(|i| {
-            H::ExtElem::from_subelems(
-                (0..H::ExtElem::EXT_SIZE).map(|j| final_coeffs[j * degree + i]),
-            )
-        }));
-        let fx = hal.poly_eval(poly_buf.as_slice(), H::ExtElem::from_subfield(&x));
-        if fx != goal {
-            return Err(VerificationError::InvalidProof);
+            poly_buf.clear();
+            poly_buf.extend((0..degree).map(|i| {
+                H::ExtElem::from_subelems(
+                    (0..H::ExtElem::EXT_SIZE).map(|j| final_coeffs[j * degree + i]),
+                )
+            }));
+            let fx = hal.poly_eval(poly_buf.as_slice(), H::ExtElem::from_subfield(&x));
+            if fx != *goal {
+                return Err(VerificationError::InvalidProof);
+            }
         }
     }
     Ok(())
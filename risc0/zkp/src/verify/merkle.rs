@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use alloc::vec::Vec;
+use alloc::{borrow::Cow, vec::Vec};
 use core::marker::PhantomData;
 
 use super::VerifyHal;
@@ -82,16 +82,29 @@ impl SplitMerkleIndex for MerkleTreeParams {
 impl<'a, H: VerifyHal> MerkleTreeVerifier<'a, H> {
     /// Constructs a new MerkleTreeVerifier by making the params, and then
     /// computing the root hashes from the top level hashes.
+    #[cfg(test)]
     pub fn new(
         iop: &mut ReadIOP<'a, H::Field, H::Rng>,
         row_size: usize,
         col_size: usize,
         queries: usize,
-    ) -> Self {
-        let params = MerkleTreeParams::new(row_size, col_size, queries);
+    ) -> Result<Self, VerificationError> {
+        Self::new_with_cap(iop, row_size, col_size, queries, None)
+    }
+
+    /// Constructs a new MerkleTreeVerifier with an explicit cap height. See
+    /// [MerkleTreeParams::new_with_cap].
+    pub fn new_with_cap(
+        iop: &mut ReadIOP<'a, H::Field, H::Rng>,
+        row_size: usize,
+        col_size: usize,
+        queries: usize,
+        cap: Option<usize>,
+    ) -> Result<Self, VerificationError> {
+        let params = MerkleTreeParams::new_with_cap(row_size, col_size, queries, cap);
 
         // Fill top vector with digests from IOP.
-        let top = iop.read_pod_slice(params.top_size);
+        let top = iop.read_pod_slice(params.top_size)?;
         // Populate hashes up to the root of the tree.
         let mut rest =
             Vec::<<H::Hash as ConfigHash<H::Field>>::DigestPtr>::with_capacity(params.top_size - 1);
@@ -129,7 +142,7 @@ impl<'a, H: VerifyHal> MerkleTreeVerifier<'a, H> {
             phantom_hal: PhantomData,
         };
         iop.commit(verifier.root());
-        verifier
+        Ok(verifier)
     }
 
     /// Returns the root hash of the tree.
@@ -142,11 +155,12 @@ impl<'a, H: VerifyHal> MerkleTreeVerifier<'a, H> {
     }
 
     /// Verifies a branch provided by an IOP.
+    #[cfg(test)]
     pub fn verify(
         &self,
         iop: &mut ReadIOP<'a, H::Field, H::Rng>,
         mut idx: usize,
-    ) -> Result<&'a [H::Elem], VerificationError> {
+    ) -> Result<Cow<'a, [H::Elem]>, VerificationError> {
         if idx >= self.params.row_size {
             return Err(VerificationError::MerkleQueryOutOfRange {
                 idx: idx,
@@ -154,9 +168,9 @@ impl<'a, H: VerifyHal> MerkleTreeVerifier<'a, H> {
             });
         }
         // Initialize a vector to hold field elements.
-        let out: &[H::Elem] = iop.read_field_elem_slice(self.params.col_size);
+        let out: Cow<[H::Elem]> = iop.read_field_elem_slice(self.params.col_size)?;
         // Get the hash at the leaf of the tree by hashing these field elements.
-        let mut cur = H::Hash::hash_elem_slice(out);
+        let mut cur = H::Hash::hash_elem_slice(&out);
         // Shift idx to start of the row
         idx += self.params.row_size;
         while idx >= 2 * self.params.top_size {
@@ -164,7 +178,7 @@ impl<'a, H: VerifyHal> MerkleTreeVerifier<'a, H> {
             // child.
             let low_bit = idx % 2;
             // Retrieve the other parent from the IOP.
-            let other: &Digest = match iop.read_pod_slice(1) {
+            let other: &Digest = match iop.read_pod_slice(1)? {
                 [other] => other,
                 _ => unreachable!(),
             };
@@ -178,15 +192,86 @@ impl<'a, H: VerifyHal> MerkleTreeVerifier<'a, H> {
         }
         // Once we reduce to an index for which we have the hash, check that it's
         // correct.
-        let present_hash: &Digest = if idx >= self.params.top_size {
-            &self.top[self.params.idx_to_top(idx)]
-        } else {
-            &self.rest[self.params.idx_to_rest(idx)]
-        };
-        if *present_hash == *cur {
+        if *self.present_hash(idx) == *cur {
             Ok(out)
         } else {
             Err(VerificationError::InvalidProof)
         }
     }
+
+    /// Verifies a multi-proof for a batch of indexes, as produced by
+    /// `MerkleTreeProver::prove_batch`, and returns the rows in the order of
+    /// `idxs`.
+    pub fn verify_batch(
+        &self,
+        iop: &mut ReadIOP<'a, H::Field, H::Rng>,
+        idxs: &[usize],
+    ) -> Result<Vec<Cow<'a, [H::Elem]>>, VerificationError> {
+        if let Some(&idx) = idxs.iter().find(|idx| **idx >= self.params.row_size) {
+            return Err(VerificationError::MerkleQueryOutOfRange {
+                idx,
+                rows: self.params.row_size,
+            });
+        }
+        let mut leaves = idxs.to_vec();
+        leaves.sort_unstable();
+        leaves.dedup();
+        // Read the distinct rows, and hash them to get the leaves of the tree.
+        let rows: Vec<Cow<'a, [H::Elem]>> = leaves
+            .iter()
+            .map(|_| iop.read_field_elem_slice(self.params.col_size))
+            .collect::<Result<_, _>>()?;
+        let mut layer: Vec<_> = leaves
+            .iter()
+            .zip(rows.iter())
+            .map(|(idx, row)| (idx + self.params.row_size, H::Hash::hash_elem_slice(row)))
+            .collect();
+        // Ascend one layer at a time, reading only the siblings which we can't
+        // compute ourselves.
+        while layer
+            .first()
+            .map_or(false, |(idx, _)| *idx >= 2 * self.params.top_size)
+        {
+            let mut next = Vec::with_capacity(layer.len());
+            let mut iter = layer.into_iter().peekable();
+            while let Some((idx, cur)) = iter.next() {
+                let parent =
+                    if idx % 2 == 0 && iter.peek().map(|(other, _)| *other) == Some(idx + 1) {
+                        let (_, other) = iter.next().unwrap();
+                        H::Hash::hash_pair(&cur, &other)
+                    } else {
+                        let other: &Digest = match iop.read_pod_slice(1)? {
+                            [other] => other,
+                            _ => unreachable!(),
+                        };
+                        if idx % 2 == 1 {
+                            H::Hash::hash_pair(other, &cur)
+                        } else {
+                            H::Hash::hash_pair(&cur, other)
+                        }
+                    };
+                next.push((idx / 2, parent));
+            }
+            layer = next;
+        }
+        // Check each of the remaining hashes against the ones we have.
+        for (idx, cur) in layer.iter() {
+            if *self.present_hash(*idx) != **cur {
+                return Err(VerificationError::InvalidProof);
+            }
+        }
+        Ok(idxs
+            .iter()
+            .map(|idx| rows[leaves.binary_search(idx).unwrap()].clone())
+            .collect())
+    }
+
+    // Returns the hash we hold for a virtual index at or above the top layer.
+    fn present_hash(&self, idx: usize) -> &Digest {
+        if idx >= self.params.top_size {
+            &self.top[self.params.idx_to_top(idx)]
+        } else {
+            &self.rest[self.params.idx_to_rest(idx)]
+        }
+    }
 }
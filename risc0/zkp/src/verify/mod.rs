@@ -20,7 +20,7 @@
 //! [`Receipt::verify`]: https://docs.rs/risc0-zkvm/latest/risc0_zkvm/receipt/struct.Receipt.html#method.verify
 
 pub mod adapter;
-mod fri;
+pub(crate) mod fri;
 pub(crate) mod merkle;
 pub mod read_iop;
 
@@ -44,10 +44,13 @@ use crate::{
         config::{ConfigHash, ConfigRng},
         digest::Digest,
         log2_ceil,
+        ntt::bit_rev_32,
     },
+    params::ProofParams,
     taps::TapSet,
+    transcript::Transcript,
     verify::{fri::fri_verify, merkle::MerkleTreeVerifier, read_iop::ReadIOP},
-    FRI_FOLD, INV_RATE, MAX_CYCLES_PO2, QUERIES,
+    INV_RATE, MAX_CYCLES_PO2,
 };
 
 #[derive(Debug)]
@@ -59,6 +62,8 @@ pub enum VerificationError {
     InvalidProof,
     JournalSealRootMismatch,
     SealJournalLengthMismatch { seal_len: usize, journal_len: usize },
+    InvalidProofParams,
+    InsufficientSecurity { bits: usize, required: usize },
 }
 
 impl fmt::Display for VerificationError {
@@ -82,6 +87,11 @@ impl fmt::Display for VerificationError {
                 f,
                 "Seal's output length ({seal_len}) does not match journal length ({journal_len})"
             ),
+            VerificationError::InvalidProofParams => write!(f, "invalid proof parameters"),
+            VerificationError::InsufficientSecurity { bits, required } => write!(
+                f,
+                "Proof parameters give {bits} bits of security, but {required} are required"
+            ),
         }
     }
 }
@@ -105,7 +115,9 @@ pub trait VerifyHal {
         mix: &[Self::Elem],
     ) -> Self::ExtElem;
 
-    fn fold_eval(&self, io: &mut [Self::ExtElem; FRI_FOLD], x: Self::ExtElem) -> Self::ExtElem;
+    /// Interpolate a single FRI fold (`io.len()` is the folding factor) and
+    /// evaluate the result at `x`.
+    fn fold_eval(&self, io: &mut [Self::ExtElem], x: Self::ExtElem) -> Self::ExtElem;
 
     /// Evaluate a polynomial whose coefficients are in the extension field at a
     /// point.
@@ -135,7 +147,6 @@ mod host {
     use crate::{
         adapter::PolyExt,
         core::ntt::{bit_reverse, interpolate_ntt},
-        FRI_FOLD,
     };
 
     struct TapCache<F: Field> {
@@ -172,7 +183,7 @@ mod host {
             log::debug!("{}", msg);
         }
 
-        fn fold_eval(&self, io: &mut [Self::ExtElem; FRI_FOLD], x: Self::ExtElem) -> Self::ExtElem {
+        fn fold_eval(&self, io: &mut [Self::ExtElem], x: Self::ExtElem) -> Self::ExtElem {
             interpolate_ntt::<Self::Elem, Self::ExtElem>(io);
             bit_reverse(io);
             self.poly_eval(io, x)
@@ -275,21 +286,85 @@ mod host {
 }
 
 /// Verify a seal is valid for the given circuit, code, and globals
+///
+/// `check_params` is given the [ProofParams] the seal was generated with, and
+/// can be used to enforce a minimum security level (see
+/// [ProofParams::check_security]).
+#[tracing::instrument(skip_all)]
+pub fn verify<'a, H, C, CheckParams, CheckCode, CheckGlobals>(
+    hal: &'a H,
+    circuit: &C,
+    seal: &'a [u32],
+    check_params: CheckParams,
+    check_code: CheckCode,
+    check_globals: CheckGlobals,
+) -> Result<(), VerificationError>
+where
+    H: VerifyHal,
+    C: CircuitInfo + TapsProvider,
+    CheckParams: Fn(&ProofParams) -> Result<(), VerificationError>,
+    CheckCode: Fn(u32, &Digest) -> Result<(), VerificationError>,
+    CheckGlobals: Fn(&[H::Elem]) -> Result<(), VerificationError>,
+{
+    verify_with_iop(
+        hal,
+        circuit,
+        seal,
+        None,
+        check_params,
+        check_code,
+        check_globals,
+    )
+}
+
+/// Like [verify], but records the [Transcript] of the IOP as verification
+/// proceeds, so that it can be compared with the prover's transcript using
+/// [diff](crate::transcript::diff) when verification fails.
 #[tracing::instrument(skip_all)]
-pub fn verify<'a, H, C, CheckCode, CheckGlobals>(
+pub fn verify_with_transcript<'a, H, C, CheckParams, CheckCode, CheckGlobals>(
     hal: &'a H,
     circuit: &C,
     seal: &'a [u32],
+    transcript: &'a mut Transcript,
+    check_params: CheckParams,
     check_code: CheckCode,
     check_globals: CheckGlobals,
 ) -> Result<(), VerificationError>
 where
     H: VerifyHal,
     C: CircuitInfo + TapsProvider,
+    CheckParams: Fn(&ProofParams) -> Result<(), VerificationError>,
     CheckCode: Fn(u32, &Digest) -> Result<(), VerificationError>,
     CheckGlobals: Fn(&[H::Elem]) -> Result<(), VerificationError>,
 {
-    if seal.len() == 0 {
+    verify_with_iop(
+        hal,
+        circuit,
+        seal,
+        Some(transcript),
+        check_params,
+        check_code,
+        check_globals,
+    )
+}
+
+fn verify_with_iop<'a, H, C, CheckParams, CheckCode, CheckGlobals>(
+    hal: &'a H,
+    circuit: &C,
+    seal: &'a [u32],
+    transcript: Option<&'a mut Transcript>,
+    check_params: CheckParams,
+    check_code: CheckCode,
+    check_globals: CheckGlobals,
+) -> Result<(), VerificationError>
+where
+    H: VerifyHal,
+    C: CircuitInfo + TapsProvider,
+    CheckParams: Fn(&ProofParams) -> Result<(), VerificationError>,
+    CheckCode: Fn(u32, &Digest) -> Result<(), VerificationError>,
+    CheckGlobals: Fn(&[H::Elem]) -> Result<(), VerificationError>,
+{
+    if seal.len() < ProofParams::WORDS {
         return Err(VerificationError::ReceiptFormatError);
     }
 
@@ -297,19 +372,34 @@ where
     let taps = adapter.taps();
 
     // Make IOP
-    let mut iop = ReadIOP::<H::Field, H::Rng>::new(seal);
+    let mut iop = match transcript {
+        Some(transcript) => ReadIOP::<H::Field, H::Rng>::with_transcript(seal, transcript),
+        None => ReadIOP::<H::Field, H::Rng>::new(seal),
+    };
+
+    // Read the proof parameters and commit to them
+    iop.label("params");
+    let params = ProofParams::decode(iop.read_u32s(ProofParams::WORDS)?)?;
+    check_params(&params)?;
+    iop.commit(&params.digest());
 
     // Read any execution state
-    adapter.execute(&mut iop);
+    adapter.execute(&mut iop)?;
 
-    let io = adapter.out.ok_or(VerificationError::ReceiptFormatError)?;
-    check_globals(&io)?;
+    let io = adapter
+        .out
+        .as_deref()
+        .ok_or(VerificationError::ReceiptFormatError)?;
+    check_globals(io)?;
 
     // Get the size
     let po2 = adapter.po2();
     assert!(po2 as usize <= MAX_CYCLES_PO2);
+    if po2 as usize + log2_ceil(params.inv_rate()) > H::Elem::MAX_ROU_PO2 {
+        return Err(VerificationError::InvalidProofParams);
+    }
     let size = 1 << po2;
-    let domain = INV_RATE * size;
+    let domain = params.inv_rate() * size;
     // debug!("size = {size}, po2 = {po2}");
 
     // Get taps and compute sizes
@@ -320,7 +410,14 @@ where
     // Get merkle root for the code merkle tree.
     // The code merkle tree contains the control instructions for the zkVM.
     hal.debug("code_merkle");
-    let code_merkle = MerkleTreeVerifier::<H>::new(&mut iop, domain, code_size, QUERIES);
+    iop.label(taps.group_name(REGISTER_GROUP_CODE));
+    let code_merkle = MerkleTreeVerifier::<H>::new_with_cap(
+        &mut iop,
+        domain,
+        code_size,
+        params.queries(),
+        params.merkle_cap(),
+    )?;
     // debug!("codeRoot = {}", code_merkle.root());
     check_code(po2, code_merkle.root())?;
 
@@ -329,7 +426,14 @@ where
     // including memory accesses as well as the permutation of those memory
     // accesses sorted by location used by PLONK.
     hal.debug("data_merkle");
-    let data_merkle = MerkleTreeVerifier::<H>::new(&mut iop, domain, data_size, QUERIES);
+    iop.label(taps.group_name(REGISTER_GROUP_DATA));
+    let data_merkle = MerkleTreeVerifier::<H>::new_with_cap(
+        &mut iop,
+        domain,
+        data_size,
+        params.queries(),
+        params.merkle_cap(),
+    )?;
     // debug!("dataRoot = {}", data_merkle.root());
 
     // Prep accumulation
@@ -346,27 +450,42 @@ where
     // values (see PLOOKUP paper for details). This permutation is used to
     // implement a look-up table.
     hal.debug("accum_merkle");
-    let accum_merkle = MerkleTreeVerifier::<H>::new(&mut iop, domain, accum_size, QUERIES);
+    iop.label(taps.group_name(REGISTER_GROUP_ACCUM));
+    let accum_merkle = MerkleTreeVerifier::<H>::new_with_cap(
+        &mut iop,
+        domain,
+        accum_size,
+        params.queries(),
+        params.merkle_cap(),
+    )?;
     // debug!("accumRoot = {}", accum_merkle.root());
 
     // Get a pseudorandom value with which to mix the constraint polynomials.
     // See DEEP-ALI protocol from DEEP-FRI paper for details on constraint mixing.
+    iop.label("check");
     let poly_mix = iop.random_ext_elem();
 
     hal.debug("check_merkle");
-    let check_merkle = MerkleTreeVerifier::<H>::new(&mut iop, domain, H::CHECK_SIZE, QUERIES);
+    let check_merkle = MerkleTreeVerifier::<H>::new_with_cap(
+        &mut iop,
+        domain,
+        H::CHECK_SIZE,
+        params.queries(),
+        params.merkle_cap(),
+    )?;
     // debug!("checkRoot = {}", check_merkle.root());
 
     // Get a pseudorandom DEEP query point
     // See DEEP-ALI protocol from DEEP-FRI paper for details on DEEP query.
+    iop.label("deep");
     let z = iop.random_ext_elem();
     // debug!("Z = {z:?}");
     let back_one = <H::Elem as RootsOfUnity>::ROU_REV[po2 as usize];
 
     // Read the U coeffs (the interpolations of the taps) + commit their hash.
     let num_taps = taps.tap_size();
-    let coeff_u = iop.read_field_elem_slice(num_taps + H::CHECK_SIZE);
-    let hash_u = *H::Hash::hash_ext_elem_slice(coeff_u);
+    let coeff_u = iop.read_field_elem_slice(num_taps + H::CHECK_SIZE)?;
+    let hash_u = *H::Hash::hash_ext_elem_slice(&coeff_u);
     iop.commit(&hash_u);
 
     // Now, convert U polynomials from coefficient form to evaluation form
@@ -388,36 +507,32 @@ where
     let result = hal.compute_polynomial(
         &eval_u,
         poly_mix,
-        bytemuck::cast_slice(&adapter.out.unwrap()),
+        bytemuck::cast_slice(adapter.out.as_deref().unwrap()),
         bytemuck::cast_slice(&adapter.mix),
     );
     hal.debug("< compute_polynomial");
     // debug!("Result = {result:?}");
 
-    // Now generate the check polynomial
-    // TODO: This currently treats the extension degree as hardcoded at 4, with
-    // the structure of the code and the value of `remap` (and how it is
-    // accessed) only working in the extension degree = 4 case.
-    // However, for generic fields the extension degree may be different
-    // TODO: Therefore just using the to/from baby bear shims for now
+    // Now generate the check polynomial.
+    // The check polynomial is committed as INV_RATE polynomials for each
+    // component of the extension field, so that
+    // f(x) = g0(x^INV_RATE) + g1(x^INV_RATE) x + ... (see the prover).
+    // Since the coefficients are bit reversed, g_i is stored at position
+    // bit_rev(i) among the INV_RATE polynomials of each component.
     let mut check = H::ExtElem::default();
-    let remap = [0, 2, 1, 3];
-    let fp0 = H::Elem::ZERO;
-    let fp1 = H::Elem::ONE;
-    for i in 0..4 {
-        let rmi = remap[i];
-        check += coeff_u[num_taps + rmi + 0]
-            * z.pow(i)
-            * H::ExtElem::from_subelems([fp1, fp0, fp0, fp0]);
-        check += coeff_u[num_taps + rmi + 4]
-            * z.pow(i)
-            * H::ExtElem::from_subelems([fp0, fp1, fp0, fp0]);
-        check += coeff_u[num_taps + rmi + 8]
-            * z.pow(i)
-            * H::ExtElem::from_subelems([fp0, fp0, fp1, fp0]);
-        check += coeff_u[num_taps + rmi + 12]
-            * z.pow(i)
-            * H::ExtElem::from_subelems([fp0, fp0, fp0, fp1]);
+    let rate_po2 = log2_ceil(INV_RATE);
+    for i in 0..INV_RATE {
+        let rmi = (bit_rev_32(i as u32) >> (32 - rate_po2)) as usize;
+        for j in 0..H::ExtElem::EXT_SIZE {
+            let unit = H::ExtElem::from_subelems((0..H::ExtElem::EXT_SIZE).map(|k| {
+                if k == j {
+                    H::Elem::ONE
+                } else {
+                    H::Elem::ZERO
+                }
+            }));
+            check += coeff_u[num_taps + j * INV_RATE + rmi] * z.pow(i) * unit;
+        }
     }
     let three = H::Elem::from_u64(3);
     check *= (H::ExtElem::from_subfield(&three) * z).pow(size) - H::ExtElem::ONE;
@@ -474,20 +589,26 @@ where
     fri_verify(
         hal,
         &mut iop,
+        &params,
         size,
-        |iop: &mut ReadIOP<H::Field, _>, idx: usize| -> Result<H::ExtElem, VerificationError> {
+        |iop: &mut ReadIOP<H::Field, _>,
+         idxs: &[usize]|
+         -> Result<Vec<H::ExtElem>, VerificationError> {
             hal.debug("fri_verify");
-            let x = gen.pow(idx);
-            let rows = [
-                accum_merkle.verify(iop, idx)?,
-                code_merkle.verify(iop, idx)?,
-                data_merkle.verify(iop, idx)?,
-            ];
-            let check_row = check_merkle.verify(iop, idx)?;
-            let ret = hal.fri_eval_taps(taps, mix, &combo_u, check_row, back_one, x, z, rows);
-            Ok(ret)
+            let accum_rows = accum_merkle.verify_batch(iop, idxs)?;
+            let code_rows = code_merkle.verify_batch(iop, idxs)?;
+            let data_rows = data_merkle.verify_batch(iop, idxs)?;
+            let check_rows = check_merkle.verify_batch(iop, idxs)?;
+            Ok(idxs
+                .iter()
+                .enumerate()
+                .map(|(i, idx)| {
+                    let x = gen.pow(*idx);
+                    let rows = [&*accum_rows[i], &code_rows[i], &data_rows[i]];
+                    hal.fri_eval_taps(taps, mix, &combo_u, &check_rows[i], back_one, x, z, rows)
+                })
+                .collect())
         },
     )?;
-    iop.verify_complete();
-    Ok(())
+    iop.verify_complete()
 }
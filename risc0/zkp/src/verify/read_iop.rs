@@ -12,16 +12,22 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use alloc::{borrow::Cow, vec::Vec};
 use core::marker::PhantomData;
 
 use risc0_core::field::{Elem, Field};
 
-use crate::core::{config::ConfigRng, sha::Digest};
+use crate::{
+    core::{config::ConfigRng, sha::Digest},
+    transcript::Transcript,
+    verify::VerificationError,
+};
 
 #[derive(Debug)]
 pub struct ReadIOP<'a, F: Field, R: ConfigRng<F>> {
     proof: &'a [u32],
     rng: R,
+    transcript: Option<&'a mut Transcript>,
     phantom: PhantomData<F>,
 }
 
@@ -30,55 +36,128 @@ impl<'a, F: Field, R: ConfigRng<F>> ReadIOP<'a, F, R> {
         ReadIOP {
             proof,
             rng: R::new(),
+            transcript: None,
             phantom: PhantomData,
         }
     }
 
-    pub fn read_u32s(&mut self, n: usize) -> &'a [u32] {
+    /// Like [ReadIOP::new], but records everything read, committed and drawn
+    /// to `transcript`. The transcript is written as verification proceeds,
+    /// so it is complete up to the point of any failure.
+    pub fn with_transcript(proof: &'a [u32], transcript: &'a mut Transcript) -> Self {
+        ReadIOP {
+            transcript: Some(transcript),
+            ..Self::new(proof)
+        }
+    }
+
+    /// Sets the label of the transcript events from now on. This has no
+    /// effect unless a transcript is being recorded.
+    pub fn label(&mut self, label: &str) {
+        if let Some(transcript) = self.transcript.as_mut() {
+            transcript.label(label);
+        }
+    }
+
+    // Splits `n` words off the front of the proof, recording them in the
+    // transcript. Fails if the proof is too short.
+    fn take(&mut self, n: usize) -> Result<&'a [u32], VerificationError> {
+        if n > self.proof.len() {
+            if let Some(transcript) = self.transcript.as_mut() {
+                transcript.overrun(n, self.proof.len());
+            }
+            return Err(VerificationError::ReceiptFormatError);
+        }
         let u32s;
         (u32s, self.proof) = self.proof.split_at(n);
-        u32s
+        if let Some(transcript) = self.transcript.as_mut() {
+            transcript.data(u32s);
+        }
+        Ok(u32s)
+    }
+
+    pub fn read_u32s(&mut self, n: usize) -> Result<&'a [u32], VerificationError> {
+        self.take(n)
     }
 
     /// Read some field elements from this IOP, and check to make sure
     /// they're not INVALID.
-    pub fn read_field_elem_slice<T: Elem>(&mut self, n: usize) -> &'a [T] {
-        let u32s = self.read_u32s(n * T::WORDS);
-        T::from_u32_slice(u32s)
+    ///
+    /// The elements are borrowed from the proof when it is suitably aligned
+    /// for `T`, and copied otherwise, as for fields whose elements are wider
+    /// than a word.
+    pub fn read_field_elem_slice<T: Elem>(
+        &mut self,
+        n: usize,
+    ) -> Result<Cow<'a, [T]>, VerificationError> {
+        let u32s = self.read_u32s(n * T::WORDS)?;
+        Ok(if bytemuck::try_cast_slice::<u32, T>(u32s).is_ok() {
+            Cow::Borrowed(T::from_u32_slice(u32s))
+        } else {
+            let elems: Vec<T> = u32s.chunks_exact(T::WORDS).map(T::from_u32_words).collect();
+            if cfg!(debug_assertions) {
+                for elem in elems.iter() {
+                    elem.ensure_valid();
+                }
+            }
+            Cow::Owned(elems)
+        })
     }
 
     /// Read some plain old data from this IOP without doing any
     /// validation.  Prefer to use read_field_elem_slice if reading
     /// field elements.
-    pub fn read_pod_slice<T: bytemuck::Pod>(&mut self, n: usize) -> &'a [T] {
-        let u32s;
-        (u32s, self.proof) = self
-            .proof
-            .split_at(n * core::mem::size_of::<T>() / core::mem::size_of::<u32>());
-        bytemuck::cast_slice(u32s)
+    pub fn read_pod_slice<T: bytemuck::Pod>(
+        &mut self,
+        n: usize,
+    ) -> Result<&'a [T], VerificationError> {
+        let u32s = self.take(n * core::mem::size_of::<T>() / core::mem::size_of::<u32>())?;
+        Ok(bytemuck::cast_slice(u32s))
     }
 
     pub fn commit(&mut self, digest: &Digest) {
+        if let Some(transcript) = self.transcript.as_mut() {
+            transcript.commit(digest);
+        }
         self.rng.mix(digest);
     }
 
     /// Checks that the entire data of the IOP has been read.
-    pub fn verify_complete(&self) {
-        assert_eq!(self.proof.len(), 0);
+    pub fn verify_complete(&self) -> Result<(), VerificationError> {
+        if !self.proof.is_empty() {
+            return Err(VerificationError::ReceiptFormatError);
+        }
+        Ok(())
     }
 
     /// Get a cryptographically uniform u32
     pub fn random_bits(&mut self, bits: usize) -> u32 {
-        self.rng.random_bits(bits)
+        let value = self.rng.random_bits(bits);
+        self.record_random(format_args!("random_bits({bits})"), &[value]);
+        value
     }
 
     /// Get a cryptographically uniform field element
     pub fn random_elem(&mut self) -> F::Elem {
-        self.rng.random_elem()
+        let value = self.rng.random_elem();
+        if self.transcript.is_some() {
+            self.record_random("random_elem", &value.to_u32_words());
+        }
+        value
     }
 
     /// Get a cryptographically uniform extension field element
     pub fn random_ext_elem(&mut self) -> F::ExtElem {
-        self.rng.random_ext_elem()
+        let value = self.rng.random_ext_elem();
+        if self.transcript.is_some() {
+            self.record_random("random_ext_elem", &value.to_u32_words());
+        }
+        value
+    }
+
+    fn record_random(&mut self, kind: impl core::fmt::Display, value: &[u32]) {
+        if let Some(transcript) = self.transcript.as_mut() {
+            transcript.random(kind, value);
+        }
     }
 }
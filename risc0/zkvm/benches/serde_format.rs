@@ -0,0 +1,98 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `serde_format` compares the word and compact formats of
+//! `risc0_zkvm::serde`. For each format, it prints the size of the serialized
+//! input and the cycles the guest takes to decode it, then times running the
+//! guest. Like `guest_run`, it does not generate seals.
+
+use std::time::{Duration, Instant};
+
+use criterion::{
+    black_box, criterion_group, criterion_main, BenchmarkId, Criterion, SamplingMode, Throughput,
+};
+use risc0_zkvm::{
+    serde::{to_vec, to_vec_with_format, Format},
+    Prover, ProverOpts,
+};
+use risc0_zkvm_methods::{
+    bench::{BenchmarkSpec, Record, SpecWithIters},
+    BENCH_ELF, BENCH_ID,
+};
+use risc0_zkvm_platform::WORD_SIZE;
+
+fn records(count: u32) -> Vec<Record> {
+    (0..count)
+        .map(|i| Record {
+            id: i,
+            balance: i as i64 * 1000 - 5000,
+            active: i % 2 == 0,
+            verified: i % 3 == 0,
+            tags: (0..4).map(|tag| (i * 4 + tag) as u16).collect(),
+            payload: (0..64).map(|byte| (i + byte) as u8).collect(),
+            name: format!("record {i}"),
+        })
+        .collect()
+}
+
+// Decodes `words` `iters` times in the guest, returning the prover.
+fn run_guest(format: Format, words: &[u32], iters: u64) -> Prover<'static> {
+    let spec = BenchmarkSpec::Decode {
+        compact: format == Format::Compact,
+        words: words.to_vec(),
+    };
+    let mut prover = Prover::new_with_opts(
+        BENCH_ELF,
+        BENCH_ID,
+        ProverOpts::default().with_skip_seal(true),
+    )
+    .unwrap();
+    prover.add_input_u32_slice(&to_vec(&SpecWithIters(spec, iters)).unwrap());
+    black_box(prover.run().unwrap());
+    prover
+}
+
+pub fn bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serde_decode");
+    group.sampling_mode(SamplingMode::Flat);
+    for count in [1, 16, 128] {
+        for format in [Format::Word, Format::Compact] {
+            let words = to_vec_with_format(&records(count), format).unwrap();
+            let bytes = words.len() * WORD_SIZE;
+            let cycles = run_guest(format, &words, 1).cycles - run_guest(format, &words, 0).cycles;
+            println!(
+                "serde_decode/{format:?}/{count}: {bytes} input bytes, {cycles} decode cycles"
+            );
+
+            group.throughput(Throughput::Bytes(bytes as u64));
+            group.bench_with_input(
+                BenchmarkId::new(format!("{format:?}"), count),
+                &words,
+                |b, words| {
+                    b.iter_custom(|iters| {
+                        let start = Instant::now();
+                        run_guest(format, words, iters);
+                        start.elapsed()
+                    })
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(name = benches;
+                 config = Criterion::default().measurement_time(Duration::new(20, 0));
+                 targets = bench);
+criterion_main!(benches);
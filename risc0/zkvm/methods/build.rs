@@ -30,6 +30,7 @@ fn main() {
             GuestOptions {
                 features: Vec::new(),
                 std: false,
+                patch_sha2: false,
             },
         ),
         (
@@ -37,6 +38,39 @@ fn main() {
             GuestOptions {
                 features: vec!["test_feature1".to_string(), "test_feature2".to_string()],
                 std: true,
+                patch_sha2: false,
+            },
+        ),
+        (
+            "risc0-zkvm-methods-sha2-accel",
+            GuestOptions {
+                features: Vec::new(),
+                std: false,
+                patch_sha2: true,
+            },
+        ),
+        (
+            "risc0-zkvm-methods-sha2-soft",
+            GuestOptions {
+                features: Vec::new(),
+                std: false,
+                patch_sha2: false,
+            },
+        ),
+        (
+            "risc0-zkvm-methods-heap-free",
+            GuestOptions {
+                features: Vec::new(),
+                std: false,
+                patch_sha2: false,
+            },
+        ),
+        (
+            "risc0-zkvm-methods-heap-bump",
+            GuestOptions {
+                features: Vec::new(),
+                std: false,
+                patch_sha2: false,
             },
         ),
     ]);
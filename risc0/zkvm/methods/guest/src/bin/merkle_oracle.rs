@@ -0,0 +1,41 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use risc0_zkvm::{
+    guest::env,
+    merkle::{VectorOracle, SYS_VECTOR_ORACLE},
+    sha::Digest,
+};
+
+risc0_zkvm::entry!(main);
+
+// Reads the elements at the given indices of a vector held by the host, and
+// commits them.
+pub fn main() {
+    let root: Digest = env::read();
+    let indices: Vec<u32> = env::read();
+    let oracle = VectorOracle::<(u32, u64)>::new(SYS_VECTOR_ORACLE, root);
+    let elements: Vec<(u32, u64)> = indices
+        .iter()
+        .map(|&index| oracle.get(index as usize))
+        .collect();
+    env::commit(&elements);
+}
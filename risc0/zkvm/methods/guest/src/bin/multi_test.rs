@@ -18,14 +18,16 @@
 #![no_std]
 
 extern crate alloc;
-use alloc::vec;
+use alloc::{vec, vec::Vec};
 use core::arch::asm;
 
 use getrandom::getrandom;
 use risc0_zeroio::deserialize::Deserialize;
 use risc0_zkp::core::sha::{testutil::test_sha_impl, Digest, Sha256};
-use risc0_zkvm::guest::{env, memory_barrier, sha};
-use risc0_zkvm_methods::multi_test::{MultiTestSpec, MultiTestSpecRef, SYS_MULTI_TEST};
+use risc0_zkvm::guest::{env, logger, memory_barrier, sha};
+use risc0_zkvm_methods::multi_test::{
+    KeyValue, KeyValueClient, KeyValueError, MultiTestSpec, MultiTestSpecRef, SYS_MULTI_TEST,
+};
 use risc0_zkvm_platform::syscall::{nr::SYS_INITIAL_INPUT, sys_read};
 
 risc0_zkvm::entry!(main);
@@ -90,6 +92,10 @@ pub fn main() {
         MultiTestSpecRef::Fail(_) => {
             panic!("MultiTestSpec::Fail invoked");
         }
+        MultiTestSpecRef::Exit(exit) => {
+            env::commit(&exit.journal());
+            env::exit(exit.code() as u8);
+        }
         MultiTestSpecRef::ReadWriteMem(values) => {
             for (addr, value) in values.values().iter() {
                 if value != 0 {
@@ -124,6 +130,42 @@ pub fn main() {
             env::commit_slice(&rand_buf);
             assert_ne!(rand_buf, vec![0u8; rand_buf.len()].as_slice());
         }
+        MultiTestSpecRef::Log(_) => {
+            logger::init();
+            log::error!("error from the guest");
+            log::info!("info from the guest");
+            log::trace!("trace from the guest: {}", env::get_cycle_count());
+            env::commit(&(log::max_level() as u32));
+        }
+        MultiTestSpecRef::Regions(regions) => {
+            env::region("outer", || {
+                for i in 0..regions.count() {
+                    env::region("inner", || {
+                        memory_barrier(&sha::Impl::hash_bytes(&i.to_le_bytes()));
+                    });
+                }
+            });
+            let _region = env::Region::enter("last");
+            memory_barrier(&sha::Impl::hash_bytes(&[]));
+        }
+        MultiTestSpecRef::Rpc(_) => {
+            KeyValueClient.put("answer".into(), 42);
+            assert_eq!(
+                KeyValueClient.get("question".into()),
+                Err(KeyValueError::NotFound("question".into()))
+            );
+            env::commit(&KeyValueClient.get("answer".into()).unwrap());
+        }
+        MultiTestSpecRef::StreamDigest(stream) => {
+            let bytes: Vec<u8> = (0..stream.len()).map(|i| i as u8).collect();
+            let (head, tail) = bytes.split_at(bytes.len() / 2);
+            env::commit(&stream.len());
+            let mut writer = env::stream();
+            writer.write_bytes(head);
+            writer.write_bytes(tail);
+            let digest = writer.commit();
+            assert_eq!(env::commit_digest(&bytes), digest);
+        }
         MultiTestSpecRef::SysRead(sysread) => {
             let mut orig = sysread.orig().to_vec();
 
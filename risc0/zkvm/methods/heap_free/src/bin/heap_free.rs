@@ -0,0 +1,45 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::hint::black_box;
+
+use risc0_zkvm::guest::env;
+
+risc0_zkvm::entry!(main);
+
+// Repeatedly allocates and frees a large buffer, along with some small
+// vectors which grow as they are filled, and commits a checksum of their
+// contents.
+pub fn main() {
+    let iterations: u32 = env::read();
+    let size: u32 = env::read();
+
+    let mut sum = 0u32;
+    for i in 0..iterations {
+        let mut buf: Vec<u8> = Vec::with_capacity(size as usize);
+        buf.push(i as u8);
+        let small: Vec<u32> = (0..100).map(|j| i ^ j).collect();
+        let buf = black_box(buf);
+        sum = sum
+            .wrapping_add(buf[0] as u32)
+            .wrapping_add(small.iter().fold(0, |acc, x| acc.wrapping_add(*x)));
+    }
+    env::commit(&sum);
+}
@@ -0,0 +1,40 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use hmac::{Hmac, Mac};
+use risc0_zkvm::guest::env;
+use sha2::{Digest, Sha256};
+
+risc0_zkvm::entry!(main);
+
+// Hashes a message with SHA-256 and HMAC-SHA256, both of which use the `sha2`
+// crate.
+pub fn main() {
+    let key: Vec<u8> = env::read();
+    let msg: Vec<u8> = env::read();
+
+    let digest: [u8; 32] = Sha256::digest(&msg).into();
+    let mut mac = Hmac::<Sha256>::new_from_slice(&key).unwrap();
+    mac.update(&msg);
+    let tag: [u8; 32] = mac.finalize().into_bytes().into();
+
+    env::commit(&(digest, tag));
+}
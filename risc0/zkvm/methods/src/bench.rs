@@ -14,7 +14,7 @@
 
 extern crate alloc;
 
-use alloc::vec::Vec;
+use alloc::{string::String, vec::Vec};
 
 use serde::{Deserialize, Serialize};
 
@@ -33,6 +33,25 @@ pub enum BenchmarkSpec {
     Memset {
         len: usize,
     },
+    Decode {
+        // Whether `words` is in the compact format rather than the word format
+        compact: bool,
+        // A serialized `Vec<Record>`
+        words: Vec<u32>,
+    },
+}
+
+/// A record mixing small integers, flags and bytes, for comparing the formats
+/// of `risc0_zkvm::serde`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Record {
+    pub id: u32,
+    pub balance: i64,
+    pub active: bool,
+    pub verified: bool,
+    pub tags: Vec<u16>,
+    pub payload: Vec<u8>,
+    pub name: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -15,7 +15,7 @@
 // Definitions for test selection codes used by the "multi_test" test.
 extern crate alloc;
 
-use alloc::vec::Vec;
+use alloc::{string::String, vec::Vec};
 
 use risc0_zeroio::{Deserialize, Serialize};
 use risc0_zkvm::declare_syscall;
@@ -31,6 +31,11 @@ pub enum MultiTestSpec {
     EventTrace,
     Profiler,
     Fail,
+    Exit {
+        // Committed to the journal before exiting
+        journal: u32,
+        code: u32,
+    },
     ReadWriteMem {
         /// Tuples of (address, value). Zero means read the value and
         /// output it; nonzero means write that value.
@@ -40,6 +45,14 @@ pub enum MultiTestSpec {
         count: u32,
     },
     DoRandom,
+    Log,
+    Regions {
+        count: u32,
+    },
+    Rpc,
+    StreamDigest {
+        len: u32,
+    },
     SysRead {
         // Buffer to read to
         orig: Vec<u8>,
@@ -50,3 +63,15 @@ pub enum MultiTestSpec {
 }
 
 declare_syscall!(pub SYS_MULTI_TEST);
+
+/// A key-value store which the host serves to the guest, for testing RPC.
+#[risc0_zkvm::rpc]
+pub trait KeyValue {
+    fn get(&self, key: String) -> Result<u32, KeyValueError>;
+    fn put(&self, key: String, value: u32);
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq)]
+pub enum KeyValueError {
+    NotFound(String),
+}
@@ -15,8 +15,11 @@
 #![no_main]
 
 use risc0_zkp::core::sha::Sha256;
-use risc0_zkvm::guest::{env, memory_barrier, sha};
-use risc0_zkvm_methods::bench::{BenchmarkSpec, SpecWithIters};
+use risc0_zkvm::{
+    guest::{env, memory_barrier, sha},
+    serde::{from_slice_with_format, Format},
+};
+use risc0_zkvm_methods::bench::{BenchmarkSpec, Record, SpecWithIters};
 
 risc0_zkvm::entry!(main);
 
@@ -59,5 +62,16 @@ pub fn main() {
                 memory_barrier(&dst_slice);
             }
         }
+        BenchmarkSpec::Decode { compact, words } => {
+            let format = if compact {
+                Format::Compact
+            } else {
+                Format::Word
+            };
+            for _ in 0..iters {
+                let records: Vec<Record> = from_slice_with_format(&words, format).unwrap();
+                memory_barrier(&records);
+            }
+        }
     }
 }
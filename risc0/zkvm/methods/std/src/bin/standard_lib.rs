@@ -12,7 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::io::{stdin, stdout, Read, Write};
+use std::io::{stdin, stdout, BufRead, BufReader, Read, Write};
+
+use risc0_zkvm::guest::env;
 
 risc0_zkvm::entry!(main);
 
@@ -39,6 +41,48 @@ pub fn main() {
                 risc0_zkvm::guest::env::commit_slice(msg.as_bytes());
             }
         }
+        "GUEST_IO" => {
+            // Stream lines from stdin through the guest's own I/O handles,
+            // upper-casing them to stdout, and counting them to stderr and
+            // another file descriptor.
+            let mut count = 0;
+            for line in BufReader::new(env::stdin()).lines() {
+                writeln!(env::stdout(), "{}", line.unwrap().to_uppercase()).unwrap();
+                count += 1;
+            }
+            write!(env::stderr(), "{count} lines").unwrap();
+            env::get_writer(100, |_| {})
+                .write_all(&[count as u8])
+                .unwrap();
+        }
+        "ARGS" => {
+            // Print the command-line arguments, which std and the guest's
+            // env read alike.
+            let args: Vec<String> = std::env::args().collect();
+            assert_eq!(args, env::args().collect::<Vec<_>>());
+            for arg in args {
+                println!("{arg}");
+            }
+        }
+        "VFS" => {
+            // Sum the numbers in the files named on stdin, and write the sum
+            // to a new file if the host allows it, or commit it otherwise.
+            let mut sum = 0;
+            for name in stdin().lines() {
+                let mut file = env::open(&name.unwrap()).unwrap();
+                let mut numbers = String::new();
+                file.read_to_string(&mut numbers).unwrap();
+                sum += numbers
+                    .split_whitespace()
+                    .map(|n| n.parse::<u32>().unwrap())
+                    .sum::<u32>();
+            }
+            assert!(env::open("missing").is_none());
+            match env::create("sum.txt") {
+                Some(mut file) => write!(file, "{sum}").unwrap(),
+                None => env::commit_slice(format!("{sum}").as_bytes()),
+            }
+        }
         _ => {
             panic!("Unknown test mode {test_mode}");
         }
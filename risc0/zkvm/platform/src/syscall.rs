@@ -113,6 +113,8 @@ macro_rules! declare_syscall {
 pub mod nr {
     declare_syscall!(pub SYS_PANIC);
     declare_syscall!(pub SYS_LOG);
+    declare_syscall!(pub SYS_LOG_LEVEL);
+    declare_syscall!(pub SYS_LOG_RECORD);
     declare_syscall!(pub SYS_CYCLE_COUNT);
     declare_syscall!(pub SYS_INITIAL_INPUT);
     declare_syscall!(pub SYS_RANDOM);
@@ -120,6 +122,19 @@ pub mod nr {
     declare_syscall!(pub SYS_READ);
     declare_syscall!(pub SYS_WRITE);
     declare_syscall!(pub SYS_GETENV);
+    declare_syscall!(pub SYS_ARGC);
+    declare_syscall!(pub SYS_ARGV);
+    declare_syscall!(pub SYS_HEAP_STATS);
+    declare_syscall!(pub SYS_OPEN);
+    declare_syscall!(pub SYS_CLOSE);
+    declare_syscall!(pub SYS_REGION_ENTER);
+    declare_syscall!(pub SYS_REGION_EXIT);
+}
+
+/// Modes for opening files with sys_open.
+pub mod open_mode {
+    pub const READ: u32 = 0;
+    pub const WRITE: u32 = 1;
 }
 
 impl SyscallName {
@@ -405,6 +420,30 @@ pub unsafe extern "C" fn sys_write(fd: u32, write_buf: *const u8, nbytes: usize)
     );
 }
 
+/// Opens the named file, which the host provides, for reading or writing
+/// according to `mode` (see [open_mode]).  Returns a file descriptor for use
+/// with sys_read or sys_write, or u32::MAX if the host does not provide the
+/// file in that mode.
+#[no_mangle]
+pub unsafe extern "C" fn sys_open(path: *const u8, path_len: usize, mode: u32) -> u32 {
+    let Return(a0, _) = syscall_3(
+        nr::SYS_OPEN,
+        null_mut(),
+        0,
+        path as u32,
+        path_len as u32,
+        mode,
+    );
+    a0
+}
+
+/// Closes a file descriptor returned by sys_open, so that the host can release
+/// the file.
+#[no_mangle]
+pub unsafe extern "C" fn sys_close(fd: u32) {
+    syscall_1(nr::SYS_CLOSE, null_mut(), 0, fd);
+}
+
 /// Retrieves the value of an environment variable, and stores as much
 /// of it as it can it in the memory at [out_words, out_words +
 /// out_nwords).  Returns the length of the value.
@@ -433,6 +472,30 @@ pub unsafe extern "C" fn sys_getenv(
     }
 }
 
+/// Returns the number of program arguments, including the program name in
+/// the first argument.
+#[no_mangle]
+pub unsafe extern "C" fn sys_argc() -> usize {
+    let Return(a0, _) = syscall_0(nr::SYS_ARGC, null_mut(), 0);
+    a0 as usize
+}
+
+/// Retrieves the program argument with index `arg_index`, and stores as much
+/// of it as it can in the memory at [out_words, out_words + out_nwords).
+/// Returns the length of the argument in bytes.
+///
+/// Like sys_getenv, this is normally called twice to read an argument: once
+/// to get its length, and once to fill in allocated memory.
+#[no_mangle]
+pub unsafe extern "C" fn sys_argv(
+    out_words: *mut u32,
+    out_nwords: usize,
+    arg_index: usize,
+) -> usize {
+    let Return(a0, _) = syscall_1(nr::SYS_ARGV, out_words, out_nwords, arg_index as u32);
+    a0 as usize
+}
+
 // Number of words remaining in the heap that haven't yet been allocated.
 static mut HEAP_WORDS_REMAINING: usize = crate::memory::HEAP.len_words();
 
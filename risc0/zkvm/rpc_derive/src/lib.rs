@@ -0,0 +1,241 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![doc = include_str!("../README.md")]
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, spanned::Spanned, Error, FnArg, Ident, ItemTrait, Pat, Result, ReturnType,
+    TraitItem, TraitItemMethod, Type,
+};
+
+// A method of the RPC trait.
+struct Method<'a> {
+    ident: &'a Ident,
+    arg_idents: Vec<&'a Ident>,
+    arg_types: Vec<&'a Type>,
+    output: TokenStream,
+}
+
+fn parse_method(method: &TraitItemMethod) -> Result<Method<'_>> {
+    let sig = &method.sig;
+    if !sig.generics.params.is_empty() || sig.asyncness.is_some() || sig.variadic.is_some() {
+        return Err(Error::new(
+            sig.span(),
+            "RPC methods cannot be generic, async, or variadic",
+        ));
+    }
+    let mut inputs = sig.inputs.iter();
+    match inputs.next() {
+        Some(FnArg::Receiver(receiver))
+            if receiver.reference.is_some() && receiver.mutability.is_none() => {}
+        _ => return Err(Error::new(sig.span(), "RPC methods must take &self")),
+    }
+    let mut arg_idents = Vec::new();
+    let mut arg_types = Vec::new();
+    for input in inputs {
+        let arg = match input {
+            FnArg::Typed(arg) => arg,
+            FnArg::Receiver(receiver) => {
+                return Err(Error::new(receiver.span(), "Unexpected receiver"))
+            }
+        };
+        match arg.pat.as_ref() {
+            Pat::Ident(pat) if pat.by_ref.is_none() && pat.subpat.is_none() => {
+                arg_idents.push(&pat.ident)
+            }
+            pat => {
+                return Err(Error::new(
+                    pat.span(),
+                    "RPC method arguments must be plain identifiers",
+                ))
+            }
+        }
+        if let Type::Reference(ty) = arg.ty.as_ref() {
+            return Err(Error::new(
+                ty.span(),
+                "RPC method arguments must be owned types",
+            ));
+        }
+        arg_types.push(arg.ty.as_ref());
+    }
+    let output = match &sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+    Ok(Method {
+        ident: &sig.ident,
+        arg_idents,
+        arg_types,
+        output,
+    })
+}
+
+// Converts a trait name such as `KeyValue` to `KEY_VALUE`.
+fn screaming_snake_case(ident: &Ident) -> String {
+    let mut out = String::new();
+    for (i, c) in ident.to_string().chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            out.push('_');
+        }
+        out.extend(c.to_uppercase());
+    }
+    out
+}
+
+fn expand(item: ItemTrait) -> Result<TokenStream> {
+    if !item.generics.params.is_empty() {
+        return Err(Error::new(
+            item.generics.span(),
+            "RPC traits cannot be generic",
+        ));
+    }
+    let mut methods = Vec::new();
+    for trait_item in &item.items {
+        match trait_item {
+            TraitItem::Method(method) => methods.push(parse_method(method)?),
+            other => {
+                return Err(Error::new(
+                    other.span(),
+                    "RPC traits can only contain methods",
+                ))
+            }
+        }
+    }
+
+    let vis = &item.vis;
+    let name = &item.ident;
+    let syscall = format_ident!("{}_SYSCALL", screaming_snake_case(name));
+    let client = format_ident!("{}Client", name);
+    let server = format_ident!("{}Server", name);
+    let syscall_doc = format!("The syscall which carries calls to [{name}].");
+    let client_doc = format!("Calls the host's implementation of [{name}] from the guest.");
+    let server_doc = format!(
+        "Serves an implementation of [{name}] to the guest, \
+         with `ProverOpts::with_rpc`."
+    );
+
+    let client_methods = methods.iter().enumerate().map(|(index, method)| {
+        let Method {
+            ident,
+            arg_idents,
+            arg_types,
+            output,
+        } = method;
+        let index = index as u32;
+        quote! {
+            fn #ident(&self, #(#arg_idents: #arg_types),*) -> #output {
+                risc0_zkvm::rpc::call(#syscall, #index, &(#(#arg_idents,)*))
+            }
+        }
+    });
+    let server_arms = methods.iter().enumerate().map(|(index, method)| {
+        let Method {
+            ident,
+            arg_idents,
+            arg_types,
+            ..
+        } = method;
+        let index = index as u32;
+        quote! {
+            #index => {
+                let (#(#arg_idents,)*): (#(#arg_types,)*) = risc0_zkvm::rpc::decode(args)?;
+                risc0_zkvm::rpc::encode(&self.0.#ident(#(#arg_idents),*))
+            }
+        }
+    });
+
+    Ok(quote! {
+        #item
+
+        #[doc = #syscall_doc]
+        #vis const #syscall: risc0_zkvm::rpc::SyscallName = unsafe {
+            risc0_zkvm::rpc::SyscallName::from_bytes_with_nul(
+                concat!(module_path!(), "::", stringify!(#name), "\0").as_ptr(),
+            )
+        };
+
+        #[doc = #client_doc]
+        #[cfg(target_os = "zkvm")]
+        #vis struct #client;
+
+        #[cfg(target_os = "zkvm")]
+        impl #name for #client {
+            #(#client_methods)*
+        }
+
+        #[doc = #server_doc]
+        #[cfg(not(target_os = "zkvm"))]
+        #vis struct #server<T: #name>(pub T);
+
+        #[cfg(not(target_os = "zkvm"))]
+        impl<T: #name> risc0_zkvm::rpc::Service for #server<T> {
+            const SYSCALL: risc0_zkvm::rpc::SyscallName = #syscall;
+
+            fn dispatch(
+                &self,
+                method: u32,
+                args: &[u32],
+            ) -> risc0_zkvm::Result<risc0_zkvm::rpc::Response> {
+                match method {
+                    #(#server_arms)*
+                    _ => risc0_zkvm::rpc::unknown_method(stringify!(#name), method),
+                }
+            }
+        }
+    })
+}
+
+/// Generates typed RPC between the guest and the host from a trait.
+///
+/// Each method of the trait must take `&self` and owned arguments, and its
+/// arguments and return type must implement `serde::Serialize` and
+/// `serde::Deserialize`. For a trait named `KeyValue`, this generates:
+///
+/// * `KEY_VALUE_SYSCALL`, the `SyscallName` which carries its calls.
+/// * `KeyValueClient`, in the guest, which implements `KeyValue` by sending its
+///   arguments to the host and returning what the host replied.
+/// * `KeyValueServer<T: KeyValue>`, on the host, which dispatches calls from
+///   the guest to `T`, and is registered with `ProverOpts::with_rpc`.
+///
+/// Errors are typed by the methods themselves: a method returning
+/// `Result<T, E>` returns the host's `Err(E)` to the guest. Calls which the
+/// host cannot decode fail the proof.
+///
+/// ```ignore
+/// #[risc0_zkvm::rpc]
+/// pub trait KeyValue {
+///     fn get(&self, key: String) -> Result<u32, KeyValueError>;
+///     fn put(&self, key: String, value: u32);
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn rpc(
+    attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    if !attr.is_empty() {
+        return Error::new(
+            TokenStream::from(attr).span(),
+            "#[rpc] does not take arguments",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let item = parse_macro_input!(item as ItemTrait);
+    expand(item)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
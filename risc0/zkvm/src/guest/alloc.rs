@@ -12,26 +12,95 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! The guest's global allocator.
+//!
+//! By default, the guest uses a bump allocator which never frees memory, so
+//! a guest can allocate at most the size of the
+//! [HEAP](risc0_zkvm_platform::memory::HEAP) region over its whole run. With
+//! the `free_list_alloc` feature, the guest instead uses a size-class
+//! allocator which keeps freed blocks on a free list for each power-of-two
+//! size and reuses them, so long-running guests which allocate and free
+//! repeatedly only need as much heap as they use at any one time.
+//!
+//! Either way, the allocator reports its usage to the host when the guest
+//! exits; see `Prover::stats`.
+
 use core::{
     alloc::{GlobalAlloc, Layout},
     cell::UnsafeCell,
+    ptr::null_mut,
+};
+
+use risc0_zkvm_platform::{
+    memory,
+    syscall::{self, nr::SYS_HEAP_STATS, syscall_2},
+    WORD_SIZE,
 };
 
-use risc0_zkvm_platform::{memory, syscall, WORD_SIZE};
+/// Heap usage in bytes, as reported to the host.
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct HeapStats {
+    /// Bytes allocated and not yet freed.
+    in_use: u32,
+    /// The largest value of `in_use` so far.
+    peak_in_use: u32,
+    /// Bytes taken from the heap region, which is never returned to it.
+    reserved: u32,
+}
 
-struct BumpPointerAlloc;
+impl HeapStats {
+    fn on_alloc(&mut self, bytes: usize) {
+        self.in_use += bytes as u32;
+        self.peak_in_use = self.peak_in_use.max(self.in_use);
+    }
+
+    fn on_dealloc(&mut self, bytes: usize) {
+        self.in_use -= bytes as u32;
+    }
+}
+
+/// Reports the allocator's heap usage to the host.
+pub(crate) fn report_stats() {
+    #[cfg(target_os = "zkvm")]
+    unsafe {
+        let stats = *HEAP.stats.get();
+        syscall_2(
+            SYS_HEAP_STATS,
+            null_mut(),
+            0,
+            &stats as *const HeapStats as u32,
+            core::mem::size_of::<HeapStats>() as u32,
+        );
+    }
+}
+
+/// Takes `nbytes`, which must be a multiple of the word size, from the heap
+/// region.
+unsafe fn reserve(stats: &mut HeapStats, nbytes: usize) -> *mut u8 {
+    stats.reserved += nbytes as u32;
+    syscall::sys_alloc_words(nbytes / WORD_SIZE) as *mut u8
+}
+
+struct BumpPointerAlloc {
+    stats: UnsafeCell<HeapStats>,
+}
+
+// SAFETY: The guest is single threaded.
+unsafe impl Sync for BumpPointerAlloc {}
 
 #[cfg(target_os = "zkvm")]
 unsafe impl GlobalAlloc for BumpPointerAlloc {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        let nwords = layout
+        let nbytes = layout
             .align_to(WORD_SIZE)
             .expect("Unable to align allocation to word size")
             .pad_to_align()
-            .size()
-            / WORD_SIZE;
+            .size();
 
-        syscall::sys_alloc_words(nwords) as *mut u8
+        let stats = &mut *self.stats.get();
+        stats.on_alloc(nbytes);
+        reserve(stats, nbytes)
     }
 
     unsafe fn dealloc(&self, _: *mut u8, _: Layout) {
@@ -39,6 +108,166 @@ unsafe impl GlobalAlloc for BumpPointerAlloc {
     }
 }
 
+/// The smallest block is two words, to leave room for the free list link.
+const MIN_CLASS: usize = 3;
+
+/// Size classes are powers of two up to 2^31 bytes, more than the heap.
+const NUM_CLASSES: usize = 32;
+
+/// A free block, which links to the next free block of the same size.
+struct FreeBlock {
+    next: *mut FreeBlock,
+}
+
+/// A size-class allocator.
+///
+/// Each allocation is rounded up to a power-of-two block size of at least its
+/// alignment, and every block is aligned to its own size, so any free block of
+/// the right size can satisfy a request. Freed blocks go on the free list for
+/// their size. When that list is empty, a larger free block is split in
+/// halves, and only if there is none is new memory taken from the heap
+/// region. Free blocks are never merged, so a guest which frees many small
+/// blocks and then makes a large allocation still needs new memory for it.
+struct FreeListAlloc {
+    free: UnsafeCell<[*mut FreeBlock; NUM_CLASSES]>,
+    stats: UnsafeCell<HeapStats>,
+}
+
+// SAFETY: The guest is single threaded.
+unsafe impl Sync for FreeListAlloc {}
+
+impl FreeListAlloc {
+    /// Returns the size class of the given layout, whose blocks are
+    /// `1 << class` bytes.
+    fn class(layout: &Layout) -> Option<usize> {
+        let size = layout
+            .size()
+            .max(layout.align())
+            .checked_next_power_of_two()?;
+        let class = (size.trailing_zeros() as usize).max(MIN_CLASS);
+        (class < NUM_CLASSES).then_some(class)
+    }
+
+    unsafe fn push(&self, class: usize, block: *mut u8) {
+        let free = &mut *self.free.get();
+        let block = block as *mut FreeBlock;
+        (*block).next = free[class];
+        free[class] = block;
+    }
+
+    unsafe fn pop(&self, class: usize) -> *mut u8 {
+        let free = &mut *self.free.get();
+        let block = free[class];
+        if !block.is_null() {
+            free[class] = (*block).next;
+        }
+        block as *mut u8
+    }
+
+    /// Puts the memory from `start` to `end` on the free lists, as the
+    /// largest blocks which are aligned to their size.
+    unsafe fn release(&self, mut start: usize, end: usize) {
+        while end - start >= 1 << MIN_CLASS {
+            let class = (start.trailing_zeros() as usize)
+                .min((usize::BITS - 1 - (end - start).leading_zeros()) as usize)
+                .min(NUM_CLASSES - 1);
+            if class < MIN_CLASS {
+                // Too small and unaligned to be a block; skip a word.
+                start += WORD_SIZE;
+                continue;
+            }
+            self.push(class, start as *mut u8);
+            start += 1 << class;
+        }
+    }
+
+    /// Returns a free block of the given class, splitting a larger one if
+    /// needed.
+    unsafe fn take_free(&self, class: usize) -> *mut u8 {
+        for larger in class..NUM_CLASSES {
+            let block = self.pop(larger);
+            if !block.is_null() {
+                let start = block as usize;
+                self.release(start + (1 << class), start + (1 << larger));
+                return block;
+            }
+        }
+        null_mut()
+    }
+
+    /// Takes a new block of the given class from the heap region, putting
+    /// the memory skipped to align it on the free lists.
+    unsafe fn take_new(&self, class: usize) -> *mut u8 {
+        let stats = &mut *self.stats.get();
+        let size = 1 << class;
+        let next = syscall::sys_alloc_words(0) as usize;
+        let start = (next + size - 1) & !(size - 1);
+        if start
+            .checked_add(size)
+            .map_or(true, |end| end > memory::HEAP.end())
+        {
+            return null_mut();
+        }
+        reserve(stats, start + size - next);
+        self.release(next, start);
+        start as *mut u8
+    }
+}
+
 #[cfg(target_os = "zkvm")]
+unsafe impl GlobalAlloc for FreeListAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let class = match Self::class(&layout) {
+            Some(class) => class,
+            None => return null_mut(),
+        };
+        let mut block = self.take_free(class);
+        if block.is_null() {
+            block = self.take_new(class);
+        }
+        if !block.is_null() {
+            (*self.stats.get()).on_alloc(1 << class);
+        }
+        block
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let class = Self::class(&layout).unwrap();
+        (*self.stats.get()).on_dealloc(1 << class);
+        self.push(class, ptr);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        if Self::class(&layout) == Self::class(&new_layout) {
+            return ptr;
+        }
+        let new_ptr = self.alloc(new_layout);
+        if !new_ptr.is_null() {
+            core::ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+            self.dealloc(ptr, layout);
+        }
+        new_ptr
+    }
+}
+
+#[cfg(all(target_os = "zkvm", not(feature = "free_list_alloc")))]
+#[global_allocator]
+static HEAP: BumpPointerAlloc = BumpPointerAlloc {
+    stats: UnsafeCell::new(HeapStats {
+        in_use: 0,
+        peak_in_use: 0,
+        reserved: 0,
+    }),
+};
+
+#[cfg(all(target_os = "zkvm", feature = "free_list_alloc"))]
 #[global_allocator]
-static HEAP: BumpPointerAlloc = BumpPointerAlloc;
+static HEAP: FreeListAlloc = FreeListAlloc {
+    free: UnsafeCell::new([null_mut(); NUM_CLASSES]),
+    stats: UnsafeCell::new(HeapStats {
+        in_use: 0,
+        peak_in_use: 0,
+        reserved: 0,
+    }),
+};
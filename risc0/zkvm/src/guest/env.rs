@@ -14,16 +14,17 @@
 
 //! Functions for interacting with the host environment.
 
+use alloc::{string::String, vec};
 use core::{cell::UnsafeCell, default::Default, mem::MaybeUninit, ptr, ptr::null_mut, slice};
 
 use bytemuck::Pod;
-use risc0_zkp::core::sha::{Digest, DIGEST_BYTES, DIGEST_WORDS};
+use risc0_zkp::core::sha::{Digest, DIGEST_WORDS};
 use risc0_zkvm_platform::{
     fileno, memory, syscall,
     syscall::{
-        nr::{SYS_INITIAL_INPUT, SYS_LOG},
-        sys_alloc_words, sys_cycle_count, sys_halt, sys_log, sys_output, sys_write, syscall_0,
-        syscall_2, SyscallName,
+        nr::{SYS_INITIAL_INPUT, SYS_LOG, SYS_REGION_ENTER, SYS_REGION_EXIT},
+        open_mode, sys_alloc_words, sys_argc, sys_argv, sys_close, sys_cycle_count, sys_halt,
+        sys_log, sys_open, sys_output, sys_read, sys_write, syscall_0, syscall_2, SyscallName,
     },
     WORD_SIZE,
 };
@@ -31,6 +32,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     guest::{align_up, memory_barrier, sha},
+    receipt::ExitCode,
     serde::{Deserializer, Result as SerdeResult, Serializer, StreamWriter},
     sha::rust_crypto::{Digest as _, Output, Sha256},
 };
@@ -86,17 +88,33 @@ pub(crate) fn init() {
     unsafe { HASHER = Some(Sha256::new()) };
 }
 
-pub(crate) fn finalize() {
+pub(crate) fn finalize(exit_code: ExitCode) -> ! {
+    crate::guest::alloc::report_stats();
     unsafe {
-        let hasher = core::mem::take(&mut HASHER);
-        let output = hasher.unwrap_unchecked().finalize();
+        // The hasher is missing if the guest aborts before `init`, when
+        // nothing can have been committed yet.
+        let hasher = core::mem::take(&mut HASHER).unwrap_or_else(Sha256::new);
+        let output = hasher.finalize();
         let words = bytemuck::cast_slice(output.as_slice());
         for i in 0..DIGEST_WORDS {
             sys_output(i as u32, words[i]);
         }
-        sys_output(DIGEST_WORDS as u32, DIGEST_BYTES as u32);
-        sys_halt()
+        sys_output(DIGEST_WORDS as u32, exit_code.into_u32());
+        sys_halt();
     }
+    unreachable!()
+}
+
+/// Exits the guest with the given code.
+///
+/// The code is committed in the receipt alongside the journal, in the same way
+/// as returning from the entry point, which exits with code zero. A nonzero
+/// code means the guest failed: the host only makes a receipt for it with
+/// [ProverOpts::with_failure_receipts](crate::prove::ProverOpts::with_failure_receipts),
+/// and it is only accepted by
+/// [Receipt::verify_exit_code](crate::Receipt::verify_exit_code).
+pub fn exit(code: u8) -> ! {
+    finalize(ExitCode::Halted(code))
 }
 
 /// Exchange data with the host.
@@ -179,7 +197,40 @@ pub fn get_cycle_count() -> usize {
     unsafe { sys_cycle_count() }
 }
 
+/// Run `f` as the region `name`, counting its cycles on the host.
+///
+/// Regions can be nested, and the host reports the inclusive and exclusive
+/// cycles and the number of calls of each region separately for each place
+/// it is nested, in `Prover::stats`. This is the same as holding a
+/// [Region] while calling `f`.
+pub fn region<R>(name: &str, f: impl FnOnce() -> R) -> R {
+    let _region = Region::enter(name);
+    f()
+}
+
+/// A region of the guest whose cycles are counted on the host, from when it
+/// is entered until it is dropped. See [region].
+pub struct Region(());
+
+impl Region {
+    /// Enter the region `name`, nested in any region which is already open.
+    #[must_use = "the region is exited when it is dropped"]
+    pub fn enter(name: &str) -> Self {
+        syscall(SYS_REGION_ENTER, name.as_bytes(), &mut []);
+        Region(())
+    }
+}
+
+impl Drop for Region {
+    fn drop(&mut self) {
+        syscall(SYS_REGION_EXIT, &[], &mut []);
+    }
+}
+
 /// Print a message to the debug console.
+///
+/// For leveled logging which the host can filter, use the `log` crate with
+/// [logger](crate::guest::logger).
 pub fn log(msg: &str) {
     let msg = msg.as_bytes();
     unsafe {
@@ -187,24 +238,117 @@ pub fn log(msg: &str) {
     }
 }
 
-/// Return a StreamWriter writing data using the specified syscall, which must
-/// accept a buffer and byte count as its arguments.
-pub fn get_writer<F: Fn(&[u8])>(fd: u32, hook: F) -> impl StreamWriter {
-    OutputStreamWriter::new(fd, hook)
+/// Return a writer for the given host file descriptor, which calls `hook` with
+/// all data written.
+pub fn get_writer<F: Fn(&[u8])>(fd: u32, hook: F) -> FdWriter<F> {
+    FdWriter::new(fd, hook)
+}
+
+/// Return an iterator over the guest's command-line arguments, as set by
+/// `ProverOpts::with_args` on the host.
+pub fn args() -> Args {
+    Args {
+        index: 0,
+        count: unsafe { sys_argc() },
+    }
+}
+
+/// An iterator over the guest's command-line arguments; see [args].
+pub struct Args {
+    index: usize,
+    count: usize,
+}
+
+impl Iterator for Args {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if self.index == self.count {
+            return None;
+        }
+        let len = unsafe { sys_argv(null_mut(), 0, self.index) };
+        let nwords = align_up(len, WORD_SIZE) / WORD_SIZE;
+        let mut words = vec![0u32; nwords];
+        unsafe { sys_argv(words.as_mut_ptr(), nwords, self.index) };
+        self.index += 1;
+        let bytes: &[u8] = bytemuck::cast_slice(&words);
+        Some(String::from_utf8(bytes[..len].to_vec()).expect("Argument is not valid UTF-8"))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.count - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Args {}
+
+/// Return a reader for STDIN.
+pub fn stdin() -> FdReader {
+    FdReader::new(fileno::STDIN)
 }
 
 /// Return a writer for STDOUT.
-pub fn stdout() -> impl StreamWriter {
-    get_writer(fileno::STDOUT, |_| {})
+pub fn stdout() -> FdWriter<fn(&[u8])> {
+    get_writer::<fn(&[u8])>(fileno::STDOUT, |_| {})
+}
+
+/// Return a writer for STDERR.
+pub fn stderr() -> FdWriter<fn(&[u8])> {
+    get_writer::<fn(&[u8])>(fileno::STDERR, |_| {})
+}
+
+/// Open the named file of the host's virtual file system for reading, or
+/// return None if the host does not serve it. The file is closed when the
+/// reader is dropped.
+pub fn open(path: &str) -> Option<FdReader> {
+    let fd = unsafe { sys_open(path.as_ptr(), path.len(), open_mode::READ) };
+    (fd != u32::MAX).then(|| FdReader {
+        fd,
+        close_on_drop: true,
+    })
+}
+
+/// Create the named file in the host's virtual file system, or return None if
+/// the host's file system is read-only. The file is closed when the writer is
+/// dropped.
+pub fn create(path: &str) -> Option<FdWriter<fn(&[u8])>> {
+    let fd = unsafe { sys_open(path.as_ptr(), path.len(), open_mode::WRITE) };
+    (fd != u32::MAX).then(|| {
+        let mut writer = get_writer::<fn(&[u8])>(fd, |_| {});
+        writer.close_on_drop = true;
+        writer
+    })
 }
 
 /// Return a writer for the JOURNAL.
-pub fn journal() -> impl StreamWriter {
-    get_writer(fileno::JOURNAL, |bytes| {
+pub fn journal() -> FdWriter<fn(&[u8])> {
+    get_writer::<fn(&[u8])>(fileno::JOURNAL, |bytes| {
         unsafe { HASHER.as_mut().unwrap_unchecked().update(bytes) };
     })
 }
 
+/// Return a writer for output too large to commit to the journal.
+///
+/// The bytes are streamed to the host's [fileno::STREAM], which it serves
+/// with [ProverOpts::with_stream_writer](crate::prove::ProverOpts::with_stream_writer),
+/// and [DigestWriter::commit] commits only their SHA-256 digest. Given the
+/// digest from the journal, the host checks the streamed bytes with
+/// [receipt::verify_stream](crate::receipt::verify_stream).
+pub fn stream() -> DigestWriter {
+    DigestWriter {
+        hasher: Sha256::new(),
+    }
+}
+
+/// Stream `bytes` to the host and commit only their SHA-256 digest to the
+/// journal, returning the digest; see [stream].
+pub fn commit_digest(bytes: &[u8]) -> Digest {
+    let mut writer = stream();
+    writer.write_bytes(bytes);
+    writer.commit()
+}
+
 #[derive(Default)]
 struct Env {
     initial_input_reader: Option<Reader>,
@@ -235,34 +379,154 @@ impl Env {
     }
 }
 
-/// Provides a StreamWriter which can write to one of the standard
-/// output syscalls (STDOUT, STDERR, JOURNAL, LOG) or any other
-/// syscall which accepts slices of bytes.
-struct OutputStreamWriter<F: Fn(&[u8])> {
+/// Reads from a host file descriptor, such as [stdin], which the host serves
+/// with [ProverOpts::with_read_fd](crate::prove::ProverOpts::with_read_fd).
+///
+/// With the `std` feature, this implements [std::io::Read], so it can be
+/// passed to parsers which stream their input. Each read is a round trip to
+/// the host, so wrap it in a `BufReader` to make many small reads.
+pub struct FdReader {
+    fd: u32,
+    close_on_drop: bool,
+}
+
+impl FdReader {
+    /// Return a reader for the given host file descriptor.
+    pub fn new(fd: u32) -> Self {
+        Self {
+            fd,
+            close_on_drop: false,
+        }
+    }
+
+    /// Read up to `buf.len()` bytes into `buf`, returning the number of bytes
+    /// read. This only returns 0 at the end of the file, or if `buf` is
+    /// empty.
+    pub fn read_bytes(&mut self, buf: &mut [u8]) -> usize {
+        unsafe { sys_read(self.fd, buf.as_mut_ptr(), buf.len()) }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::Read for FdReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        Ok(self.read_bytes(buf))
+    }
+}
+
+impl Drop for FdReader {
+    fn drop(&mut self) {
+        if self.close_on_drop {
+            unsafe { sys_close(self.fd) }
+        }
+    }
+}
+
+/// Writes to a host file descriptor, such as [stdout], [stderr] or the
+/// [journal], which the host serves with
+/// [ProverOpts::with_write_fd](crate::prove::ProverOpts::with_write_fd).
+///
+/// This is a [StreamWriter] for serializing data, and with the `std` feature,
+/// it also implements [std::io::Write].
+pub struct FdWriter<F: Fn(&[u8])> {
     fd: u32,
     hook: F,
+    close_on_drop: bool,
 }
 
-impl<F: Fn(&[u8])> OutputStreamWriter<F> {
+impl<F: Fn(&[u8])> FdWriter<F> {
+    /// Return a writer for the given host file descriptor, which calls
+    /// `hook` with all data written.
     pub fn new(fd: u32, hook: F) -> Self {
-        Self { fd, hook }
+        Self {
+            fd,
+            hook,
+            close_on_drop: false,
+        }
+    }
+
+    /// Write all of `bytes`.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        unsafe { sys_write(self.fd, bytes.as_ptr(), bytes.len()) }
+        (self.hook)(bytes);
     }
 }
 
-impl<F: Fn(&[u8])> StreamWriter for OutputStreamWriter<F> {
+impl<F: Fn(&[u8])> StreamWriter for FdWriter<F> {
     type Output = ();
 
     fn write_u32(&mut self, data: u32) -> SerdeResult<()> {
-        let bytes = data.to_ne_bytes();
-        unsafe { sys_write(self.fd, bytes.as_ptr(), bytes.len()) }
-        (self.hook)(&bytes);
+        self.write_bytes(&data.to_ne_bytes());
         Ok(())
     }
 
     fn write_slice<T: Pod>(&mut self, slice: &[T]) -> SerdeResult<()> {
-        let bytes: &[u8] = bytemuck::cast_slice(slice);
-        unsafe { sys_write(self.fd, bytes.as_ptr(), bytes.len()) }
-        (self.hook)(bytes);
+        self.write_bytes(bytemuck::cast_slice(slice));
+        Ok(())
+    }
+
+    fn release(&mut self) -> SerdeResult<Self::Output> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<F: Fn(&[u8])> std::io::Write for FdWriter<F> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.write_bytes(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<F: Fn(&[u8])> Drop for FdWriter<F> {
+    fn drop(&mut self) {
+        if self.close_on_drop {
+            unsafe { sys_close(self.fd) }
+        }
+    }
+}
+
+/// Streams bulk output to the host, committing only its digest; see
+/// [stream].
+///
+/// The host receives the output of every [DigestWriter] on the same file
+/// descriptor, so write to one at a time and commit it before starting the
+/// next. Like [FdWriter], this is a [StreamWriter], and with the `std`
+/// feature, it also implements [std::io::Write].
+pub struct DigestWriter {
+    hasher: Sha256,
+}
+
+impl DigestWriter {
+    /// Write all of `bytes`.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        unsafe { sys_write(fileno::STREAM, bytes.as_ptr(), bytes.len()) }
+        self.hasher.update(bytes);
+    }
+
+    /// Commit the SHA-256 digest of the bytes written to the journal, and
+    /// return it.
+    pub fn commit(self) -> Digest {
+        let digest = Digest::try_from(self.hasher.finalize().as_slice()).unwrap();
+        commit(&digest);
+        digest
+    }
+}
+
+impl StreamWriter for DigestWriter {
+    type Output = ();
+
+    fn write_u32(&mut self, data: u32) -> SerdeResult<()> {
+        self.write_bytes(&data.to_ne_bytes());
+        Ok(())
+    }
+
+    fn write_slice<T: Pod>(&mut self, slice: &[T]) -> SerdeResult<()> {
+        self.write_bytes(bytemuck::cast_slice(slice));
         Ok(())
     }
 
@@ -270,3 +534,15 @@ impl<F: Fn(&[u8])> StreamWriter for OutputStreamWriter<F> {
         Ok(())
     }
 }
+
+#[cfg(feature = "std")]
+impl std::io::Write for DigestWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.write_bytes(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A backend for the [log] crate which sends records to the host.
+//!
+//! Call [init] once at the start of the guest, then log with the usual
+//! macros:
+//! ```ignore
+//! use risc0_zkvm::guest::logger;
+//!
+//! risc0_zkvm::entry!(main);
+//!
+//! pub fn main() {
+//!     logger::init();
+//!     log::debug!("starting");
+//! }
+//! ```
+//! The host passes each record to its own logger with the record's level,
+//! target and module path, so guest records are filtered like those of any
+//! host module, e.g. with `RUST_LOG=my_guest::parser=debug`, and reach
+//! `tracing` subscribers through `tracing-log`. [init] asks the host for its
+//! maximum level, so records which the host would discard are neither
+//! formatted nor sent, and guest logging can stay compiled in while it is
+//! turned off.
+
+use alloc::{fmt::format, vec::Vec};
+
+use log::{LevelFilter, Log, Metadata, Record};
+use risc0_zkvm_platform::syscall::{
+    nr::{SYS_LOG_LEVEL, SYS_LOG_RECORD},
+    Return,
+};
+
+use crate::guest::env;
+
+struct Logger;
+
+static LOGGER: Logger = Logger;
+
+/// Installs the guest logger, at the maximum level enabled on the host.
+pub fn init() {
+    let Return(level, _) = env::syscall(SYS_LOG_LEVEL, &[], &mut []);
+    let level = match level {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Error,
+        2 => LevelFilter::Warn,
+        3 => LevelFilter::Info,
+        4 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    // SAFETY: the guest is single-threaded.
+    if unsafe { log::set_logger_racy(&LOGGER) }.is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        // The record is sent as its level and the lengths of its target and
+        // module path, followed by those strings and the message.
+        let target = record.target();
+        let module_path = record.module_path().unwrap_or("");
+        let message = format(*record.args());
+        let mut buf = Vec::with_capacity(12 + target.len() + module_path.len() + message.len());
+        for word in [
+            record.level() as u32,
+            target.len() as u32,
+            module_path.len() as u32,
+        ] {
+            buf.extend_from_slice(&word.to_le_bytes());
+        }
+        buf.extend_from_slice(target.as_bytes());
+        buf.extend_from_slice(module_path.as_bytes());
+        buf.extend_from_slice(message.as_bytes());
+        env::syscall(SYS_LOG_RECORD, &buf, &mut []);
+    }
+
+    fn flush(&self) {}
+}
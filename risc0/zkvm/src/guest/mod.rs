@@ -59,6 +59,7 @@
 
 mod alloc;
 pub mod env;
+pub mod logger;
 pub mod sha;
 
 use core::{arch::asm, mem, ptr};
@@ -96,23 +97,17 @@ core::arch::global_asm!(include_str!("memset.s"));
 #[cfg(target_os = "zkvm")]
 core::arch::global_asm!(include_str!("memcpy.s"));
 
-fn _fault() -> ! {
-    #[cfg(target_os = "zkvm")]
-    unsafe {
-        asm!("sw x0, 1(x0)")
-    };
-    unreachable!();
-}
-
 /// Aborts the guest with the given message.
 pub fn abort(msg: &str) -> ! {
-    // A compliant host should fault when it receives this syscall.
+    // A compliant host should fault when it receives this syscall, unless it
+    // is making a receipt which proves that the guest panicked.
     unsafe {
         sys_panic(msg.as_ptr(), msg.len());
     }
 
-    // As a fallback for non-compliant hosts, issue an illegal instruction.
-    _fault()
+    // Otherwise, halt with an exit code which shows the guest panicked, so
+    // the receipt is rejected by verifiers expecting success.
+    env::finalize(crate::ExitCode::Panicked)
 }
 
 #[cfg(all(not(feature = "std"), target_os = "zkvm"))]
@@ -173,7 +168,7 @@ unsafe extern "C" fn __start() {
     }
     __main();
 
-    env::finalize();
+    env::finalize(crate::ExitCode::Halted(0));
 }
 
 /// Align the given address `addr` upwards to alignment `align`.
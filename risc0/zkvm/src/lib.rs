@@ -25,9 +25,11 @@ pub mod binfmt;
 mod control_id;
 #[cfg(any(target_os = "zkvm", doc))]
 pub mod guest;
+pub mod merkle;
 #[cfg(feature = "prove")]
 pub mod prove;
 pub mod receipt;
+pub mod rpc;
 pub mod serde;
 pub mod sha;
 #[cfg(test)]
@@ -43,13 +45,14 @@ use risc0_zkp::core::digest::Digest;
 use risc0_zkp::core::sha::Sha256;
 pub use risc0_zkvm_platform::declare_syscall;
 pub use risc0_zkvm_platform::{memory::MEM_SIZE, PAGE_SIZE};
+pub use risc0_zkvm_rpc_derive::rpc;
 
 #[cfg(feature = "binfmt")]
 pub use crate::binfmt::{elf::Program, image::MemoryImage};
 use crate::control_id::BLAKE2B_CONTROL_ID;
 #[cfg(feature = "prove")]
 pub use crate::prove::{loader::Loader, Prover, ProverOpts};
-pub use crate::receipt::Receipt;
+pub use crate::receipt::{ExitCode, Receipt};
 
 const CIRCUIT: risc0_circuit_rv32im::CircuitImpl = risc0_circuit_rv32im::CircuitImpl::new();
 
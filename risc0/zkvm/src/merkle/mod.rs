@@ -0,0 +1,436 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Merkle trees which are built on the host and verified in the guest
+//!
+//! A [MerkleTree] commits to a vector of elements with a single [Digest], its
+//! root. The host builds the tree and generates a [Proof] that an element is at
+//! a given index, or a [MultiProof] for several elements at once, and the guest
+//! checks these against a root it trusts. Leaves are the SHA-256 hash of each
+//! element serialized with [crate::serde], and interior nodes are a single
+//! SHA-256 compression of their two children, so verification in the guest
+//! runs on the SHA-256 accelerator.
+//!
+//! A [SparseMerkleTree] commits to a key-value map instead, and its proofs
+//! show either the value of a key or that the key is absent.
+//!
+//! To give a guest authenticated access to a large dataset, the host serves
+//! the elements of a tree with a [MerkleOracle], and the guest reads them with
+//! a `VectorOracle`, which checks the proof of each element it receives.
+//!
+//! # Usage
+//!
+//! ```
+//! use risc0_zkvm::merkle::MerkleTree;
+//!
+//! let elements: Vec<u32> = (0..100).collect();
+//! let tree = MerkleTree::new(&elements);
+//!
+//! let proof = tree.prove(42);
+//! assert!(proof.verify(&tree.root(), &42u32));
+//! assert!(!proof.verify(&tree.root(), &43u32));
+//!
+//! let multi_proof = tree.prove_many(&[7, 3, 42]);
+//! assert!(multi_proof.verify(&tree.root(), &[3u32, 7, 42]));
+//! ```
+
+mod oracle;
+mod sparse;
+
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "prove")]
+pub use self::oracle::MerkleOracle;
+#[cfg(target_os = "zkvm")]
+pub use self::oracle::VectorOracle;
+pub use self::{
+    oracle::SYS_VECTOR_ORACLE,
+    sparse::{SparseMerkleTree, SparseProof},
+};
+use crate::sha::{Digest, Impl, Sha256};
+
+/// Returns the leaf of a Merkle tree for the given element: the SHA-256 hash
+/// of the element serialized with [crate::serde].
+pub fn leaf_hash<T: Serialize>(element: &T) -> Digest {
+    leaf_hash_words(&crate::serde::to_vec(element).unwrap())
+}
+
+/// Returns the leaf of a Merkle tree for an element which is already
+/// serialized with [crate::serde].
+pub fn leaf_hash_words(words: &[u32]) -> Digest {
+    *Impl::hash_words(words)
+}
+
+/// Returns the parent of two nodes of a Merkle tree.
+fn hash_node(left: &Digest, right: &Digest) -> Digest {
+    *Impl::hash_pair(left, right)
+}
+
+/// A Merkle tree over a vector of elements.
+///
+/// The number of leaves is padded up to a power of two with zero digests, so a
+/// tree of `n` elements has depth `ceil(log2(n))`.
+#[derive(Clone, Debug)]
+pub struct MerkleTree {
+    // The nodes of each level, from the leaves up to the root.
+    levels: Vec<Vec<Digest>>,
+    len: usize,
+}
+
+impl MerkleTree {
+    /// Builds a Merkle tree over the given elements.
+    pub fn new<T: Serialize>(elements: &[T]) -> Self {
+        Self::from_leaves(elements.iter().map(leaf_hash).collect())
+    }
+
+    /// Builds a Merkle tree from its leaves, which are usually computed with
+    /// [leaf_hash].
+    pub fn from_leaves(mut leaves: Vec<Digest>) -> Self {
+        assert!(!leaves.is_empty(), "A Merkle tree needs at least one leaf");
+        let len = leaves.len();
+        leaves.resize(len.next_power_of_two(), Digest::default());
+        let mut levels = alloc::vec![leaves];
+        while levels.last().unwrap().len() > 1 {
+            let level = levels
+                .last()
+                .unwrap()
+                .chunks_exact(2)
+                .map(|pair| hash_node(&pair[0], &pair[1]))
+                .collect();
+            levels.push(level);
+        }
+        Self { levels, len }
+    }
+
+    /// Returns the root of the tree, which commits to all of its elements.
+    pub fn root(&self) -> Digest {
+        self.levels.last().unwrap()[0]
+    }
+
+    /// Returns the number of elements in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the tree has no elements. This is never the case, as a
+    /// tree needs at least one element.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of levels between the leaves and the root.
+    pub fn depth(&self) -> usize {
+        self.levels.len() - 1
+    }
+
+    /// Returns the leaves of the tree, excluding padding.
+    pub fn leaves(&self) -> &[Digest] {
+        &self.levels[0][..self.len]
+    }
+
+    /// Proves that the element at `index` is in the tree.
+    pub fn prove(&self, index: usize) -> Proof {
+        assert!(index < self.len, "Index {index} out of bounds");
+        let siblings = self.levels[..self.depth()]
+            .iter()
+            .enumerate()
+            .map(|(level, nodes)| nodes[(index >> level) ^ 1])
+            .collect();
+        Proof {
+            index: index as u32,
+            siblings,
+        }
+    }
+
+    /// Proves that the elements at each of `indices` are in the tree. The
+    /// proof shares the nodes which the individual proofs have in common, so it
+    /// is smaller than a [Proof] for each element.
+    pub fn prove_many(&self, indices: &[usize]) -> MultiProof {
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        assert!(
+            !indices.is_empty(),
+            "A multi-proof needs at least one index"
+        );
+        assert!(
+            indices[indices.len() - 1] < self.len,
+            "Index {} out of bounds",
+            indices[indices.len() - 1]
+        );
+
+        let mut siblings = Vec::new();
+        let mut nodes: Vec<(usize, Digest)> =
+            indices.iter().map(|&i| (i, self.levels[0][i])).collect();
+        for level in &self.levels[..self.depth()] {
+            nodes = next_level(&nodes, |pos| {
+                siblings.push(level[pos]);
+                Some(level[pos])
+            })
+            .unwrap();
+        }
+        MultiProof {
+            depth: self.depth() as u32,
+            indices: indices.into_iter().map(|i| i as u32).collect(),
+            siblings,
+        }
+    }
+}
+
+// Moves the given nodes, sorted by position, up one level of a tree. A node
+// whose sibling is not also given gets it from `sibling`, by its position.
+// Returns None if `sibling` does.
+fn next_level(
+    nodes: &[(usize, Digest)],
+    mut sibling: impl FnMut(usize) -> Option<Digest>,
+) -> Option<Vec<(usize, Digest)>> {
+    let mut parents = Vec::with_capacity(nodes.len());
+    let mut i = 0;
+    while i < nodes.len() {
+        let (pos, node) = nodes[i];
+        let parent = match nodes.get(i + 1) {
+            Some((next_pos, next)) if pos % 2 == 0 && *next_pos == pos + 1 => {
+                i += 2;
+                hash_node(&node, next)
+            }
+            _ => {
+                i += 1;
+                let sibling = sibling(pos ^ 1)?;
+                if pos % 2 == 0 {
+                    hash_node(&node, &sibling)
+                } else {
+                    hash_node(&sibling, &node)
+                }
+            }
+        };
+        parents.push((pos / 2, parent));
+    }
+    Some(parents)
+}
+
+/// A proof that an element is at a given index of a [MerkleTree].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Proof {
+    index: u32,
+    // The siblings of the path from the leaf to the root, starting at the leaf.
+    siblings: Vec<Digest>,
+}
+
+impl Proof {
+    /// Returns the index of the proven element.
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+
+    /// Returns the depth of the tree this proof is for.
+    pub fn depth(&self) -> usize {
+        self.siblings.len()
+    }
+
+    /// Returns the root of the tree in which `leaf` is at [Proof::index], or
+    /// None if the index is too large for a tree of this depth.
+    pub fn root_from_leaf(&self, leaf: &Digest) -> Option<Digest> {
+        let depth = self.siblings.len();
+        if depth > 32 || (depth < 32 && self.index >> depth != 0) {
+            return None;
+        }
+        let mut node = *leaf;
+        for (level, sibling) in self.siblings.iter().enumerate() {
+            node = if (self.index >> level) & 1 == 0 {
+                hash_node(&node, sibling)
+            } else {
+                hash_node(sibling, &node)
+            };
+        }
+        Some(node)
+    }
+
+    /// Checks that `leaf` is at [Proof::index] of the tree with the given root.
+    pub fn verify_leaf(&self, root: &Digest, leaf: &Digest) -> bool {
+        self.root_from_leaf(leaf).as_ref() == Some(root)
+    }
+
+    /// Checks that `element` is at [Proof::index] of the tree with the given
+    /// root.
+    pub fn verify<T: Serialize>(&self, root: &Digest, element: &T) -> bool {
+        self.verify_leaf(root, &leaf_hash(element))
+    }
+}
+
+/// A proof that several elements are in a [MerkleTree], each at a given index.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultiProof {
+    depth: u32,
+    // The proven indices, in ascending order.
+    indices: Vec<u32>,
+    // The siblings which aren't computed from the proven leaves, level by
+    // level from the leaves up, and in order of position within a level.
+    siblings: Vec<Digest>,
+}
+
+impl MultiProof {
+    /// Returns the indices of the proven elements, in ascending order.
+    pub fn indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.indices.iter().map(|&i| i as usize)
+    }
+
+    /// Returns the root of the tree in which `leaves` are at
+    /// [MultiProof::indices], or None if the proof is malformed.
+    pub fn root_from_leaves(&self, leaves: &[Digest]) -> Option<Digest> {
+        if leaves.len() != self.indices.len() || self.depth >= u32::BITS {
+            return None;
+        }
+        let mut nodes: Vec<(usize, Digest)> = self.indices().zip(leaves.iter().copied()).collect();
+        if nodes.windows(2).any(|w| w[0].0 >= w[1].0) || nodes.last()?.0 >> self.depth != 0 {
+            return None;
+        }
+
+        let mut siblings = self.siblings.iter();
+        for _ in 0..self.depth {
+            nodes = next_level(&nodes, |_| siblings.next().copied())?;
+        }
+        if siblings.next().is_some() {
+            return None;
+        }
+        Some(nodes[0].1)
+    }
+
+    /// Checks that `leaves` are at [MultiProof::indices] of the tree with the
+    /// given root.
+    pub fn verify_leaves(&self, root: &Digest, leaves: &[Digest]) -> bool {
+        self.root_from_leaves(leaves).as_ref() == Some(root)
+    }
+
+    /// Checks that `elements` are at [MultiProof::indices] of the tree with
+    /// the given root.
+    pub fn verify<T: Serialize>(&self, root: &Digest, elements: &[T]) -> bool {
+        let leaves: Vec<Digest> = elements.iter().map(leaf_hash).collect();
+        self.verify_leaves(root, &leaves)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{seq::index::sample, Rng};
+    use sha2::{Digest as _, Sha256};
+
+    use super::*;
+
+    fn random_tree(rng: &mut impl Rng) -> (Vec<u32>, MerkleTree) {
+        let elements: Vec<u32> = (0..rng.gen_range(1..1000)).map(|_| rng.gen()).collect();
+        let tree = MerkleTree::new(&elements);
+        (elements, tree)
+    }
+
+    #[test]
+    fn leaves() {
+        // A leaf is the SHA-256 hash of the serialized element.
+        let leaf = leaf_hash(&0x64636261u32);
+        assert_eq!(leaf.as_bytes(), Sha256::digest(b"abcd").as_slice());
+
+        let tree = MerkleTree::new(&[1u32, 2, 3]);
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.depth(), 2);
+        assert_eq!(
+            tree.leaves(),
+            [leaf_hash(&1u32), leaf_hash(&2u32), leaf_hash(&3u32)]
+        );
+        let left = hash_node(&leaf_hash(&1u32), &leaf_hash(&2u32));
+        let right = hash_node(&leaf_hash(&3u32), &Digest::default());
+        assert_eq!(tree.root(), hash_node(&left, &right));
+
+        let single = MerkleTree::new(&[7u32]);
+        assert_eq!(single.depth(), 0);
+        assert_eq!(single.root(), leaf_hash(&7u32));
+        assert!(single.prove(0).verify(&single.root(), &7u32));
+    }
+
+    #[test]
+    fn proofs() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..10 {
+            let (elements, tree) = random_tree(&mut rng);
+            let root = tree.root();
+            for (index, element) in elements.iter().enumerate() {
+                let proof = tree.prove(index);
+                assert_eq!(proof.index(), index);
+                assert!(proof.verify(&root, element));
+                assert!(!proof.verify(&root, &element.wrapping_add(1)));
+
+                let words = crate::serde::to_vec(&proof).unwrap();
+                let proof: Proof = crate::serde::from_slice(&words).unwrap();
+                assert!(proof.verify(&root, element));
+            }
+        }
+    }
+
+    #[test]
+    fn tampered_proofs() {
+        let tree = MerkleTree::new(&(0..10u32).collect::<Vec<_>>());
+        let root = tree.root();
+        let proof = tree.prove(5);
+
+        let mut wrong_index = proof.clone();
+        wrong_index.index = 4;
+        assert!(!wrong_index.verify(&root, &5u32));
+        wrong_index.index = 5 + 16;
+        assert!(!wrong_index.verify(&root, &5u32));
+
+        let mut wrong_sibling = proof.clone();
+        wrong_sibling.siblings[2] = Digest::default();
+        assert!(!wrong_sibling.verify(&root, &5u32));
+
+        let mut short = proof;
+        short.siblings.pop();
+        assert!(!short.verify(&root, &5u32));
+    }
+
+    #[test]
+    fn multi_proofs() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let (elements, tree) = random_tree(&mut rng);
+            let root = tree.root();
+            let count = rng.gen_range(1..=elements.len().min(50));
+            let indices = sample(&mut rng, elements.len(), count).into_vec();
+            let proof = tree.prove_many(&indices);
+
+            let mut sorted = indices.clone();
+            sorted.sort_unstable();
+            assert_eq!(proof.indices().collect::<Vec<_>>(), sorted);
+            let proven: Vec<u32> = sorted.iter().map(|&i| elements[i]).collect();
+            assert!(proof.verify(&root, &proven));
+
+            // A multi-proof is never larger than the individual proofs.
+            assert!(proof.siblings.len() <= count * tree.depth());
+
+            let mut wrong = proven.clone();
+            wrong[0] = wrong[0].wrapping_add(1);
+            assert!(!proof.verify(&root, &wrong));
+            assert!(!proof.verify(&root, &proven[1..]));
+
+            let mut extra = proof.clone();
+            extra.siblings.push(Digest::default());
+            assert!(!extra.verify(&root, &proven));
+        }
+
+        // Proving every element needs no siblings at all.
+        let tree = MerkleTree::new(&[1u32, 2, 3, 4]);
+        let proof = tree.prove_many(&[3, 2, 1, 0, 2]);
+        assert!(proof.siblings.is_empty());
+        assert!(proof.verify(&tree.root(), &[1u32, 2, 3, 4]));
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Authenticated access to the elements of a host-held vector
+//!
+//! The host serves the elements of a [MerkleTree](super::MerkleTree) with a
+//! [MerkleOracle], which answers each request with an element and the proof
+//! that it is in the tree. The guest makes these requests through a
+//! `VectorOracle`, which checks each proof against the root of the tree, so the
+//! guest only sees elements of the vector the root commits to.
+//!
+//! The reply to a request for an element is the length of the proof in words,
+//! followed by the proof and then the element, each serialized with
+//! [crate::serde]. The guest hashes the serialized element directly, so it
+//! doesn't have to serialize the element again to check it.
+
+#[cfg(target_os = "zkvm")]
+use core::marker::PhantomData;
+
+#[cfg(feature = "prove")]
+use anyhow::{bail, Result};
+#[cfg(target_os = "zkvm")]
+use risc0_zkvm_platform::syscall::SyscallName;
+#[cfg(target_os = "zkvm")]
+use serde::Deserialize;
+#[cfg(feature = "prove")]
+use serde::Serialize;
+
+#[cfg(feature = "prove")]
+use super::MerkleTree;
+#[cfg(target_os = "zkvm")]
+use super::{leaf_hash_words, Proof};
+use crate::declare_syscall;
+#[cfg(feature = "prove")]
+use crate::prove::io::SliceIo;
+#[cfg(any(feature = "prove", target_os = "zkvm"))]
+use crate::sha::Digest;
+
+declare_syscall!(
+    /// The default syscall for a guest to request elements from a
+    /// [MerkleOracle].
+    pub SYS_VECTOR_ORACLE);
+
+/// Serves the elements of a vector to the guest, each with a proof that it is
+/// in the Merkle tree of the vector.
+///
+/// Install it with
+/// [ProverOpts::with_slice_io](crate::prove::ProverOpts::with_slice_io), and
+/// read the elements in the guest with a `VectorOracle` for the same syscall
+/// and [MerkleOracle::root].
+#[cfg(feature = "prove")]
+pub struct MerkleOracle {
+    tree: MerkleTree,
+    elements: Vec<Vec<u32>>,
+}
+
+#[cfg(feature = "prove")]
+impl MerkleOracle {
+    /// Creates an oracle for the given elements.
+    pub fn new<T: Serialize>(elements: &[T]) -> Self {
+        let elements: Vec<Vec<u32>> = elements
+            .iter()
+            .map(|element| crate::serde::to_vec(element).unwrap())
+            .collect();
+        let tree = MerkleTree::from_leaves(
+            elements
+                .iter()
+                .map(|words| super::leaf_hash_words(words))
+                .collect(),
+        );
+        Self { tree, elements }
+    }
+
+    /// Returns the root of the Merkle tree of the elements, which the guest
+    /// checks each element against.
+    pub fn root(&self) -> Digest {
+        self.tree.root()
+    }
+
+    /// Returns the Merkle tree of the elements.
+    pub fn tree(&self) -> &MerkleTree {
+        &self.tree
+    }
+}
+
+#[cfg(feature = "prove")]
+impl SliceIo for MerkleOracle {
+    type FromGuest = u32;
+    type ToGuest = u32;
+
+    fn handle_io(&self, syscall: &str, from_guest: &[u32]) -> Vec<u32> {
+        self.try_handle_io(syscall, from_guest).unwrap()
+    }
+
+    fn try_handle_io(&self, _syscall: &str, from_guest: &[u32]) -> Result<Vec<u32>> {
+        let index = match from_guest {
+            [index] => *index as usize,
+            _ => bail!("Malformed vector oracle request: {from_guest:?}"),
+        };
+        if index >= self.elements.len() {
+            bail!(
+                "Vector oracle index {index} is out of bounds for {} elements",
+                self.elements.len()
+            );
+        }
+        let proof = crate::serde::to_vec(&self.tree.prove(index)).unwrap();
+        let element = &self.elements[index];
+        let mut reply = Vec::with_capacity(1 + proof.len() + element.len());
+        reply.push(proof.len() as u32);
+        reply.extend_from_slice(&proof);
+        reply.extend_from_slice(element);
+        Ok(reply)
+    }
+}
+
+/// Reads the elements of a vector which the host holds in a [MerkleOracle],
+/// checking that each is in the Merkle tree with the given root.
+///
+/// This doesn't check the length of the vector: reading past its end is an
+/// error on the host, which stops the guest, as there is no element to prove.
+#[cfg(target_os = "zkvm")]
+pub struct VectorOracle<T> {
+    syscall: SyscallName,
+    root: Digest,
+    phantom: PhantomData<T>,
+}
+
+#[cfg(target_os = "zkvm")]
+impl<T: Deserialize<'static>> VectorOracle<T> {
+    /// Creates an oracle which requests elements with the given syscall, and
+    /// checks them against `root`.
+    pub fn new(syscall: SyscallName, root: Digest) -> Self {
+        Self {
+            syscall,
+            root,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the root which elements are checked against.
+    pub fn root(&self) -> &Digest {
+        &self.root
+    }
+
+    /// Returns the element at `index`, after checking its proof.
+    pub fn get(&self, index: usize) -> T {
+        let request = [u32::try_from(index).unwrap()];
+        let reply: &'static [u32] = crate::guest::env::send_recv_slice(self.syscall, &request);
+        let (proof_len, reply) = reply.split_first().unwrap();
+        let (proof, element) = reply.split_at(*proof_len as usize);
+        let proof: Proof = crate::serde::from_slice(proof).unwrap();
+        assert_eq!(proof.index(), index, "Merkle proof is for the wrong index");
+        assert!(
+            proof.verify_leaf(&self.root, &leaf_hash_words(element)),
+            "Invalid Merkle proof for element {index}"
+        );
+        crate::serde::from_slice(element).unwrap()
+    }
+}
@@ -0,0 +1,329 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sparse Merkle trees
+
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use serde::{Deserialize, Serialize};
+
+use super::hash_node;
+use crate::sha::{Digest, DIGEST_BYTES};
+
+/// The depth of a sparse Merkle tree, with one level for each bit of a key.
+const DEPTH: usize = DIGEST_BYTES * 8;
+
+type Key = [u8; DIGEST_BYTES];
+
+// Returns the parent of two nodes of a sparse Merkle tree. An empty subtree
+// hashes to zero at every height, rather than to the hash of its empty
+// children, so that the tree only has to be computed where it has leaves.
+fn hash_sparse_node(left: &Digest, right: &Digest) -> Digest {
+    if *left == Digest::default() && *right == Digest::default() {
+        Digest::default()
+    } else {
+        hash_node(left, right)
+    }
+}
+
+// Returns the bit of `key` which picks a branch at the given depth, starting
+// with the most significant bit of the first byte.
+fn key_bit(key: &Key, depth: usize) -> bool {
+    (key[depth / 8] >> (7 - depth % 8)) & 1 == 1
+}
+
+// Returns the first `depth` bits of `key`, with the rest cleared, which
+// identifies the node at that depth on the path to `key`.
+fn key_prefix(key: &Key, depth: usize) -> Key {
+    let mut prefix = [0; DIGEST_BYTES];
+    prefix[..depth / 8].copy_from_slice(&key[..depth / 8]);
+    if depth % 8 != 0 {
+        prefix[depth / 8] = key[depth / 8] & !(0xff >> (depth % 8));
+    }
+    prefix
+}
+
+// Returns the children at depth `depth + 1` of the node at `depth` on the path
+// to `key`, as the prefixes which identify them.
+fn child_prefixes(key: &Key, depth: usize) -> (Key, Key) {
+    let left = key_prefix(key, depth);
+    let mut right = left;
+    right[depth / 8] |= 1 << (7 - depth % 8);
+    (left, right)
+}
+
+/// A Merkle tree which commits to a map from keys to leaves.
+///
+/// The tree has a leaf for every possible [Digest] key, so keys are usually
+/// the hash of some application-specific key, and leaves the [leaf
+/// hash](super::leaf_hash) of a value. Keys which aren't in the map have a
+/// zero leaf, so a [SparseProof] can show that a key is absent as well as what
+/// its leaf is.
+///
+/// The tree keeps the nodes above its leaves which aren't empty, and updates
+/// the path to a key when its leaf changes, so that the root and proofs don't
+/// have to be recomputed from all the leaves.
+#[derive(Clone, Debug, Default)]
+pub struct SparseMerkleTree {
+    leaves: BTreeMap<Key, Digest>,
+    // The nodes above the leaves which aren't empty, by depth and prefix.
+    nodes: BTreeMap<(usize, Key), Digest>,
+}
+
+impl SparseMerkleTree {
+    /// Creates an empty tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the leaf for `key`, returning its previous leaf. Setting a zero
+    /// leaf removes the key.
+    pub fn insert(&mut self, key: &Digest, leaf: Digest) -> Option<Digest> {
+        if leaf == Digest::default() {
+            return self.remove(key);
+        }
+        let key = to_key(key);
+        let prev = self.leaves.insert(key, leaf);
+        self.update_path(&key);
+        prev
+    }
+
+    /// Removes `key` from the map, returning its leaf.
+    pub fn remove(&mut self, key: &Digest) -> Option<Digest> {
+        let key = to_key(key);
+        let prev = self.leaves.remove(&key);
+        if prev.is_some() {
+            self.update_path(&key);
+        }
+        prev
+    }
+
+    /// Returns the leaf for `key`, if it is in the map.
+    pub fn get(&self, key: &Digest) -> Option<&Digest> {
+        self.leaves.get(&to_key(key))
+    }
+
+    /// Returns the number of keys in the map.
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Returns true if the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Returns the root of the tree, which commits to the whole map. The root
+    /// of an empty tree is zero.
+    pub fn root(&self) -> Digest {
+        self.node(0, &[0; DIGEST_BYTES])
+    }
+
+    /// Proves the leaf for `key`, or that it is absent.
+    pub fn prove(&self, key: &Digest) -> SparseProof {
+        let key = to_key(key);
+        let mut proof = SparseProof {
+            bitmap: [0; 8],
+            siblings: Vec::new(),
+        };
+        for depth in 0..DEPTH {
+            let (left, right) = child_prefixes(&key, depth);
+            let other = if key_bit(&key, depth) { left } else { right };
+            let sibling = self.node(depth + 1, &other);
+            if sibling != Digest::default() {
+                proof.bitmap[depth / 32] |= 1 << (depth % 32);
+                proof.siblings.push(sibling);
+            }
+        }
+        proof
+    }
+
+    // Returns the node at the given depth with the given prefix, which is zero
+    // if its subtree is empty.
+    fn node(&self, depth: usize, prefix: &Key) -> Digest {
+        let node = if depth == DEPTH {
+            self.leaves.get(prefix)
+        } else {
+            self.nodes.get(&(depth, *prefix))
+        };
+        node.copied().unwrap_or_default()
+    }
+
+    // Recomputes the nodes on the path from the leaf for `key` to the root.
+    fn update_path(&mut self, key: &Key) {
+        for depth in (0..DEPTH).rev() {
+            let (left, right) = child_prefixes(key, depth);
+            let node =
+                hash_sparse_node(&self.node(depth + 1, &left), &self.node(depth + 1, &right));
+            if node == Digest::default() {
+                self.nodes.remove(&(depth, left));
+            } else {
+                self.nodes.insert((depth, left), node);
+            }
+        }
+    }
+}
+
+fn to_key(key: &Digest) -> Key {
+    key.as_bytes().try_into().unwrap()
+}
+
+/// A proof of the leaf for a key of a [SparseMerkleTree], or that the key is
+/// absent.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SparseProof {
+    // Which siblings of the path from the root to the leaf are not empty,
+    // indexed by depth.
+    bitmap: [u32; 8],
+    // The siblings which are not empty, starting at the root.
+    siblings: Vec<Digest>,
+}
+
+impl SparseProof {
+    /// Returns the root of the tree in which `key` has the given leaf, where a
+    /// zero leaf means the key is absent. Returns None if the proof is
+    /// malformed.
+    pub fn root_from_leaf(&self, key: &Digest, leaf: &Digest) -> Option<Digest> {
+        let key = to_key(key);
+        let mut siblings = self.siblings.iter().rev();
+        let mut node = *leaf;
+        for depth in (0..DEPTH).rev() {
+            let sibling = if (self.bitmap[depth / 32] >> (depth % 32)) & 1 == 1 {
+                *siblings.next()?
+            } else {
+                Digest::default()
+            };
+            node = if key_bit(&key, depth) {
+                hash_sparse_node(&sibling, &node)
+            } else {
+                hash_sparse_node(&node, &sibling)
+            };
+        }
+        if siblings.next().is_some() {
+            return None;
+        }
+        Some(node)
+    }
+
+    /// Checks that `key` has the given leaf in the tree with the given root,
+    /// or is absent if `leaf` is None.
+    pub fn verify(&self, root: &Digest, key: &Digest, leaf: Option<&Digest>) -> bool {
+        let leaf = leaf.copied().unwrap_or_default();
+        self.root_from_leaf(key, &leaf).as_ref() == Some(root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{seq::SliceRandom, Rng};
+
+    use super::*;
+    use crate::merkle::leaf_hash;
+
+    fn random_digest(rng: &mut impl Rng) -> Digest {
+        Digest::from(rng.gen::<[u32; 8]>())
+    }
+
+    #[test]
+    fn empty() {
+        let tree = SparseMerkleTree::new();
+        let key = random_digest(&mut rand::thread_rng());
+        assert_eq!(tree.root(), Digest::default());
+        assert!(tree.prove(&key).verify(&tree.root(), &key, None));
+    }
+
+    #[test]
+    fn membership() {
+        let mut rng = rand::thread_rng();
+        let mut entries: Vec<(Digest, Digest)> = (0..50u32)
+            .map(|i| (random_digest(&mut rng), leaf_hash(&i)))
+            .collect();
+        let mut tree = SparseMerkleTree::new();
+        for (key, leaf) in entries.iter() {
+            tree.insert(key, *leaf);
+        }
+        let root = tree.root();
+
+        // The root doesn't depend on the order of insertion.
+        entries.shuffle(&mut rng);
+        let mut shuffled = SparseMerkleTree::new();
+        for (key, leaf) in entries.iter() {
+            shuffled.insert(key, *leaf);
+        }
+        assert_eq!(shuffled.root(), root);
+
+        for (key, leaf) in entries.iter().take(10) {
+            let proof = tree.prove(key);
+            assert!(proof.verify(&root, key, Some(leaf)));
+            assert!(!proof.verify(&root, key, None));
+            assert!(!proof.verify(&root, key, Some(&leaf_hash(&1000u32))));
+        }
+
+        let absent = random_digest(&mut rng);
+        let proof = tree.prove(&absent);
+        assert!(proof.verify(&root, &absent, None));
+        assert!(!proof.verify(&root, &absent, Some(&leaf_hash(&0u32))));
+        assert!(!proof.verify(&root, &entries[0].0, None));
+    }
+
+    #[test]
+    fn update() {
+        let mut rng = rand::thread_rng();
+        let mut tree = SparseMerkleTree::new();
+        let keys: Vec<Digest> = (0..10).map(|_| random_digest(&mut rng)).collect();
+        for (i, key) in keys.iter().enumerate() {
+            tree.insert(key, leaf_hash(&i));
+        }
+        let root = tree.root();
+
+        let proof = tree.prove(&keys[3]);
+        assert_eq!(
+            tree.insert(&keys[3], leaf_hash(&"new")),
+            Some(leaf_hash(&3usize))
+        );
+        assert_ne!(tree.root(), root);
+        // The siblings of a key don't change when its leaf does.
+        assert!(proof.verify(&tree.root(), &keys[3], Some(&leaf_hash(&"new"))));
+
+        tree.insert(&keys[3], leaf_hash(&3usize));
+        assert_eq!(tree.root(), root);
+        tree.remove(&keys[3]);
+        assert_eq!(tree.len(), 9);
+        assert!(tree.prove(&keys[3]).verify(&tree.root(), &keys[3], None));
+        assert!(proof.verify(&tree.root(), &keys[3], None));
+
+        // Removing every key leaves no nodes behind.
+        for key in keys.iter() {
+            tree.remove(key);
+        }
+        assert_eq!(tree.root(), Digest::default());
+        assert!(tree.nodes.is_empty());
+    }
+
+    #[test]
+    fn malformed_proof() {
+        let mut rng = rand::thread_rng();
+        let mut tree = SparseMerkleTree::new();
+        let key = random_digest(&mut rng);
+        tree.insert(&key, leaf_hash(&1u32));
+        tree.insert(&random_digest(&mut rng), leaf_hash(&2u32));
+        let root = tree.root();
+
+        let mut proof = tree.prove(&key);
+        proof.siblings.push(Digest::default());
+        assert!(!proof.verify(&root, &key, Some(&leaf_hash(&1u32))));
+        proof.siblings.clear();
+        assert!(!proof.verify(&root, &key, Some(&leaf_hash(&1u32))));
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Routes records from the guest's logger into the host's logger.
+
+use std::{rc::Rc, str::from_utf8};
+
+use anyhow::{anyhow, bail, Result};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use risc0_zkvm_platform::{
+    syscall::{
+        nr::SYS_LOG_LEVEL,
+        reg_abi::{REG_A3, REG_A4},
+    },
+    WORD_SIZE,
+};
+
+use super::io::{Syscall, SyscallContext};
+
+/// Handles SYS_LOG_LEVEL, by returning the host's maximum log level, and
+/// SYS_LOG_RECORD, by passing the guest's record to the host's logger.
+///
+/// If `logger` is set, records go there instead of to the global logger, and
+/// the guest sends records at every level, leaving `logger` to filter them.
+#[derive(Clone)]
+pub(crate) struct GuestLog<'a> {
+    pub logger: Option<Rc<dyn Log + 'a>>,
+}
+
+impl<'a> Syscall for GuestLog<'a> {
+    fn syscall(
+        &self,
+        syscall: &str,
+        ctx: &dyn SyscallContext,
+        _to_guest: &mut [u32],
+    ) -> Result<(u32, u32)> {
+        if syscall == SYS_LOG_LEVEL.as_str() {
+            let level = match self.logger {
+                Some(_) => LevelFilter::Trace,
+                None => log::max_level(),
+            };
+            return Ok((level as u32, 0));
+        }
+        let buf_ptr = ctx.load_register(REG_A3);
+        let buf_len = ctx.load_register(REG_A4);
+        let buf = ctx.load_region(buf_ptr, buf_len);
+        let record = GuestRecord::parse(&buf)?;
+        let metadata = Metadata::builder()
+            .level(record.level)
+            .target(record.target)
+            .build();
+        let logger = match &self.logger {
+            Some(logger) => logger.as_ref(),
+            None => log::logger(),
+        };
+        if logger.enabled(&metadata) {
+            logger.log(
+                &Record::builder()
+                    .metadata(metadata)
+                    .module_path((!record.module_path.is_empty()).then_some(record.module_path))
+                    .args(format_args!("{}", record.message))
+                    .build(),
+            );
+        }
+        Ok((0, 0))
+    }
+}
+
+/// A record sent by `risc0_zkvm::guest::logger`.
+#[derive(Debug, PartialEq)]
+struct GuestRecord<'a> {
+    level: Level,
+    target: &'a str,
+    module_path: &'a str,
+    message: &'a str,
+}
+
+impl<'a> GuestRecord<'a> {
+    fn parse(buf: &'a [u8]) -> Result<Self> {
+        let invalid = || anyhow!("Invalid log record from guest");
+        let word = |i: usize| -> Result<usize> {
+            let bytes = buf
+                .get(i * WORD_SIZE..(i + 1) * WORD_SIZE)
+                .ok_or_else(invalid)?;
+            Ok(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+        };
+        let level = match word(0)? {
+            1 => Level::Error,
+            2 => Level::Warn,
+            3 => Level::Info,
+            4 => Level::Debug,
+            5 => Level::Trace,
+            level => bail!("Invalid log level from guest: {level}"),
+        };
+        let (target_len, module_path_len) = (word(1)?, word(2)?);
+        let strings = from_utf8(&buf[3 * WORD_SIZE..])?;
+        let target = strings.get(..target_len).ok_or_else(invalid)?;
+        let strings = &strings[target_len..];
+        let module_path = strings.get(..module_path_len).ok_or_else(invalid)?;
+        let message = &strings[module_path_len..];
+        Ok(Self {
+            level,
+            target,
+            module_path,
+            message,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+
+    use super::GuestRecord;
+
+    fn encode(level: u32, target: &str, module_path: &str, message: &str) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for word in [level, target.len() as u32, module_path.len() as u32] {
+            buf.extend_from_slice(&word.to_le_bytes());
+        }
+        buf.extend_from_slice(target.as_bytes());
+        buf.extend_from_slice(module_path.as_bytes());
+        buf.extend_from_slice(message.as_bytes());
+        buf
+    }
+
+    #[test]
+    fn parse() {
+        let buf = encode(4, "guest::io", "guest::io::read", "read 3 bytes");
+        assert_eq!(
+            GuestRecord::parse(&buf).unwrap(),
+            GuestRecord {
+                level: Level::Debug,
+                target: "guest::io",
+                module_path: "guest::io::read",
+                message: "read 3 bytes",
+            }
+        );
+        let buf = encode(1, "guest", "", "");
+        assert_eq!(GuestRecord::parse(&buf).unwrap().level, Level::Error);
+
+        assert!(GuestRecord::parse(&buf[..8]).is_err());
+        assert!(GuestRecord::parse(&encode(0, "guest", "", "")).is_err());
+        assert!(GuestRecord::parse(&encode(6, "guest", "", "")).is_err());
+        let mut buf = encode(3, "guest", "guest", "");
+        buf[4] = 20;
+        assert!(GuestRecord::parse(&buf).is_err());
+    }
+}
@@ -36,6 +36,8 @@ use risc0_zkvm_platform::{
     WORD_SIZE,
 };
 
+use crate::rpc::{Response, Service};
+
 /// An IO handler that returns arbitrary data to the guest.  On the
 /// guest side, use env::send_slice, env::recv_slice, or
 /// end::send_recv_slice.
@@ -57,6 +59,18 @@ pub trait SliceIo: Sized {
 
     fn handle_io(&self, syscall: &str, from_guest: &[Self::FromGuest]) -> Vec<Self::ToGuest>;
 
+    /// Fallible host side I/O handling
+    ///
+    /// This is what the guest's requests are handled with; an error stops the
+    /// execution of the guest. The default calls [SliceIo::handle_io].
+    fn try_handle_io(
+        &self,
+        syscall: &str,
+        from_guest: &[Self::FromGuest],
+    ) -> Result<Vec<Self::ToGuest>> {
+        Ok(self.handle_io(syscall, from_guest))
+    }
+
     /// Makes a Syscall handler for this SliceIo definition.g
     fn to_syscall(self) -> SliceIoSyscall<Self> {
         SliceIoSyscall::new(self)
@@ -148,7 +162,7 @@ impl<H: SliceIo> Syscall for SliceIoSyscall<H> {
                 // and save what it returns.
                 assert_eq!(to_guest.len(), 0);
                 let from_guest: &[H::FromGuest] = bytemuck::cast_slice(from_guest);
-                let result = self.handler.handle_io(syscall, from_guest);
+                let result = self.handler.try_handle_io(syscall, from_guest)?;
                 let len = result.len();
                 *stored_result = Some(result);
                 Ok((len as u32, 0))
@@ -172,6 +186,27 @@ impl<H: SliceIo> Syscall for SliceIoSyscall<H> {
     }
 }
 
+/// A SliceIo which serves an RPC [Service] to the guest. Each request is the
+/// method number followed by its serialized arguments.
+pub(crate) struct RpcIo<S: Service>(pub S);
+
+impl<S: Service> SliceIo for RpcIo<S> {
+    type FromGuest = u32;
+    type ToGuest = u32;
+
+    fn handle_io(&self, syscall: &str, from_guest: &[u32]) -> Response {
+        self.try_handle_io(syscall, from_guest).unwrap()
+    }
+
+    fn try_handle_io(&self, _syscall: &str, from_guest: &[u32]) -> Result<Response> {
+        let (&method, args) = match from_guest.split_first() {
+            Some(split) => split,
+            None => bail!("Empty RPC request"),
+        };
+        self.0.dispatch(method, args)
+    }
+}
+
 /// Generates a Syscall from a simple slice function
 pub fn slice_io_from_fn<T: Pod, U: Pod, F: Fn(&[T]) -> Vec<U>>(f: F) -> impl SliceIo {
     FnWrapper {
@@ -196,8 +231,8 @@ impl<T: Pod, U: Pod, F: Fn(&[T]) -> Vec<U>> SliceIo for FnWrapper<T, U, F> {
 
 /// Posix-style IO
 pub(crate) struct PosixIo<'a> {
-    read_fds: RefCell<BTreeMap<u32, Box<dyn BufRead + 'a>>>,
-    write_fds: RefCell<BTreeMap<u32, Box<dyn Write + 'a>>>,
+    pub(super) read_fds: RefCell<BTreeMap<u32, Box<dyn BufRead + 'a>>>,
+    pub(super) write_fds: RefCell<BTreeMap<u32, Box<dyn Write + 'a>>>,
 }
 
 impl<'a> PosixIo<'a> {
@@ -209,14 +244,28 @@ impl<'a> PosixIo<'a> {
     }
 
     pub fn with_read_fd(self, fd: u32, reader: impl BufRead + 'a) -> Self {
-        self.read_fds.borrow_mut().insert(fd, Box::new(reader));
+        self.add_read_fd(fd, reader);
         self
     }
 
     pub fn with_write_fd(self, fd: u32, writer: impl Write + 'a) -> Self {
+        self.add_write_fd(fd, writer);
+        self
+    }
+
+    pub fn add_read_fd(&self, fd: u32, reader: impl BufRead + 'a) {
+        self.read_fds.borrow_mut().insert(fd, Box::new(reader));
+    }
+
+    pub fn add_write_fd(&self, fd: u32, writer: impl Write + 'a) {
         self.write_fds.borrow_mut().insert(fd, Box::new(writer));
+    }
 
-        self
+    /// Removes the reader or writer for `fd`, returning whether there was one.
+    pub fn close_fd(&self, fd: u32) -> bool {
+        let reader = self.read_fds.borrow_mut().remove(&fd);
+        let writer = self.write_fds.borrow_mut().remove(&fd);
+        reader.is_some() || writer.is_some()
     }
 }
 
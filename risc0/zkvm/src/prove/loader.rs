@@ -23,8 +23,8 @@ use anyhow::Result;
 use log::{debug, trace};
 use risc0_core::field::{baby_bear::BabyBearElem, Elem};
 use risc0_zkp::{
-    adapter::TapsProvider, core::sha::SHA256_INIT, hal::Hal, prove::poly_group::PolyGroup,
-    MAX_CYCLES_PO2, MIN_CYCLES_PO2, ZK_CYCLES,
+    adapter::TapsProvider, core::sha::SHA256_INIT, hal::Hal, params::ProofParams,
+    prove::poly_group::PolyGroup, MAX_CYCLES_PO2, MIN_CYCLES_PO2, ZK_CYCLES,
 };
 use risc0_zkvm_platform::{memory, WORD_SIZE};
 
@@ -378,6 +378,7 @@ impl Loader {
     /// Compute the [ControlId] associated with the given HAL
     pub fn compute_control_id<H: Hal<Elem = BabyBearElem>>(&self, hal: &H) -> ControlId {
         let code_size = CIRCUIT.code_size();
+        let params = ProofParams::default();
 
         // Start with an empty table
         let mut table = Vec::new();
@@ -395,7 +396,7 @@ impl Loader {
             hal.batch_interpolate_ntt(&coeffs, code_size);
             hal.zk_shift(&coeffs, code_size);
             // Make the poly-group & extract the root
-            let code_group = PolyGroup::new(hal, coeffs, code_size, cycles, "code");
+            let code_group = PolyGroup::new(hal, coeffs, code_size, cycles, &params, "code");
             table.push(code_group.merkle.root().clone());
         }
 
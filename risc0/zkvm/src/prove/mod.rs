@@ -30,32 +30,45 @@
 //! ```
 
 mod exec;
+mod guest_log;
 pub mod io;
 pub(crate) mod loader;
 mod plonk;
 mod preflight;
 #[cfg(feature = "profiler")]
 pub mod profiler;
+mod random;
+mod region;
+pub mod vfs;
 
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     cmp::min,
     collections::HashMap,
     fmt::Debug,
-    io::{stderr, stdin, stdout, BufRead, BufReader, Write},
+    fs,
+    io::{sink, stderr, stdin, stdout, BufRead, BufReader, Write},
     mem::take,
+    path::PathBuf,
     rc::Rc,
     str::from_utf8,
 };
 
-use anyhow::{bail, Result};
-use io::{PosixIo, SliceIo, Syscall, SyscallContext};
+use anyhow::{anyhow, bail, Result};
+use guest_log::GuestLog;
+use io::{PosixIo, RpcIo, SliceIo, Syscall, SyscallContext};
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use random::{RandomFn, RandomHandler};
+pub use region::RegionStats;
+use region::{RegionHandler, RegionTracker};
 use risc0_circuit_rv32im::{REGISTER_GROUP_ACCUM, REGISTER_GROUP_CODE, REGISTER_GROUP_DATA};
 use risc0_core::field::baby_bear::{BabyBear, BabyBearElem, BabyBearExtElem};
 use risc0_zkp::{
     adapter::TapsProvider,
     core::{config::HashSuite, sha::Digest},
     hal::{EvalCheck, Hal},
+    params::ProofParams,
     prove::adapter::ProveAdapter,
 };
 use risc0_zkvm_platform::{
@@ -63,18 +76,23 @@ use risc0_zkvm_platform::{
     memory::MEM_SIZE,
     syscall::{
         nr::{
-            SYS_CYCLE_COUNT, SYS_GETENV, SYS_LOG, SYS_PANIC, SYS_READ, SYS_READ_AVAIL, SYS_WRITE,
+            SYS_ARGC, SYS_ARGV, SYS_CLOSE, SYS_CYCLE_COUNT, SYS_GETENV, SYS_HEAP_STATS, SYS_LOG,
+            SYS_LOG_LEVEL, SYS_LOG_RECORD, SYS_OPEN, SYS_PANIC, SYS_RANDOM, SYS_READ,
+            SYS_READ_AVAIL, SYS_REGION_ENTER, SYS_REGION_EXIT, SYS_WRITE,
         },
         reg_abi::{REG_A3, REG_A4},
-        SyscallName,
+        SyscallName, DIGEST_BYTES, DIGEST_WORDS,
     },
     WORD_SIZE,
 };
+use vfs::{VfsSyscall, VirtualFs};
 
 use crate::{
     binfmt::elf::Program,
     prove::preflight::Preflight,
-    receipt::{insecure_skip_seal, Receipt},
+    receipt::{insecure_skip_seal, verify_seal, ExitCode, JournalRef, Receipt},
+    rpc::Service,
+    sha::rust_crypto::{Digest as _, Sha256},
     ControlIdLocator, MemoryImage, CIRCUIT, PAGE_SIZE,
 };
 
@@ -84,13 +102,24 @@ pub struct ProverOpts<'a> {
 
     pub(crate) skip_verify: bool,
 
+    pub(crate) failure_receipts: bool,
+
     pub(crate) syscall_handlers: HashMap<String, Box<dyn Syscall + 'a>>,
 
     pub(crate) io: PosixIo<'a>,
+    pub(crate) journal_writer: Option<Box<dyn Write + 'a>>,
     pub(crate) env_vars: HashMap<String, String>,
+    pub(crate) args: Vec<String>,
+    pub(crate) vfs: Option<VirtualFs<'a>>,
+    pub(crate) guest_logger: Option<Rc<dyn log::Log + 'a>>,
+    pub(crate) random: Option<RandomFn<'a>>,
     pub(crate) trace_callback: Option<Box<dyn FnMut(TraceEvent) -> Result<()> + 'a>>,
 
     pub(crate) preflight: bool,
+
+    pub(crate) proof_params: ProofParams,
+
+    pub(crate) iop_transcript: Option<PathBuf>,
 }
 
 impl<'a> ProverOpts<'a> {
@@ -112,6 +141,21 @@ impl<'a> ProverOpts<'a> {
         }
     }
 
+    /// If true, make a receipt even if the guest panics or exits with a
+    /// nonzero code, proving how it exited; otherwise [Prover::run] returns an
+    /// `Err` in that case. Such receipts are rejected by [Receipt::verify],
+    /// and are verified with [Receipt::verify_exit_code] instead, e.g. to show
+    /// that a program fails on some input.
+    ///
+    /// Panics only make a receipt if they are handled by `risc0-zkvm`, as in
+    /// `no_std` guests.
+    pub fn with_failure_receipts(self, failure_receipts: bool) -> Self {
+        Self {
+            failure_receipts,
+            ..self
+        }
+    }
+
     /// EXPERIMENTAL: If this and skip_seal are both true, run using
     /// preflight instead of with the circuit.  This feature is not
     /// yet complete.  Alternatively, enable preflight by setting the
@@ -120,6 +164,29 @@ impl<'a> ProverOpts<'a> {
         Self { preflight, ..self }
     }
 
+    /// Set the [ProofParams] used to generate the seal, e.g. to batch Merkle
+    /// openings or change the Merkle cap. Note that [Receipt::verify] only
+    /// accepts the default inverse rate, and requires at least the
+    /// conjectured security of the default parameters.
+    pub fn with_proof_params(self, proof_params: ProofParams) -> Self {
+        Self {
+            proof_params,
+            ..self
+        }
+    }
+
+    /// Record a transcript of the prover's IOP and write it to `path` once
+    /// the seal is generated. Comparing it with the transcript of
+    /// [Receipt::verify_with_transcript] shows where the prover and verifier
+    /// diverge when a seal fails to verify; the `iop_diff` tool in
+    /// `risc0-tools` does this for two transcript files.
+    pub fn with_iop_transcript(self, path: impl Into<PathBuf>) -> Self {
+        Self {
+            iop_transcript: Some(path.into()),
+            ..self
+        }
+    }
+
     /// Add a handler for a syscall which inputs and outputs a slice
     /// of plain old data..  The guest can call these by invoking
     /// `risc0_zkvm::guest::env::send_recv_slice`
@@ -138,14 +205,32 @@ impl<'a> ProverOpts<'a> {
         self.with_slice_io(syscall, io::slice_io_from_fn(f))
     }
 
+    /// Serve an RPC [Service] to the guest, such as the `Server` generated by
+    /// [rpc](macro@crate::rpc) for a trait.
+    pub fn with_rpc<S: Service + 'a>(self, service: S) -> Self {
+        self.with_slice_io(S::SYSCALL, RpcIo(service))
+    }
+
     /// Add a handler for a raw syscall implementation.  The guest can
     /// invoke these using the risc0_zkvm_platform::syscall!  macro.
+    ///
+    /// A handler for `SYS_RANDOM`, `SYS_HEAP_STATS`, `SYS_REGION_ENTER` or
+    /// `SYS_REGION_EXIT` replaces the prover's own, in which case the
+    /// corresponding part of [Prover::stats] is left empty.
     pub fn with_syscall(mut self, syscall: SyscallName, handler: impl Syscall + 'a) -> Self {
         self.syscall_handlers
             .insert(syscall.as_str().to_string(), Box::new(handler));
         self
     }
 
+    // Add a handler unless one was already added for the syscall.
+    fn with_default_syscall(mut self, syscall: SyscallName, handler: impl Syscall + 'a) -> Self {
+        self.syscall_handlers
+            .entry(syscall.as_str().to_string())
+            .or_insert_with(|| Box::new(handler));
+        self
+    }
+
     /// Add a callback handler for raw trace messages.
     pub fn with_trace_callback(
         mut self,
@@ -168,20 +253,123 @@ impl<'a> ProverOpts<'a> {
         self
     }
 
+    /// Stream the journal to `writer` as the guest commits it, rather than
+    /// keeping it in memory.
+    ///
+    /// The [Receipt] is then made with an empty journal, so verify it with
+    /// [Receipt::verify_with_journal_digest] and the SHA-256 digest of the
+    /// streamed journal, or put the streamed journal back into
+    /// [Receipt::journal].
+    pub fn with_journal_writer(self, writer: impl Write + 'a) -> Self {
+        Self {
+            journal_writer: Some(Box::new(writer)),
+            ..self
+        }
+    }
+
+    /// Write the output which the guest streams with
+    /// `risc0_zkvm::guest::env::stream` or `env::commit_digest` to `writer`.
+    /// The output is discarded by default. Check it against the digest which
+    /// the guest committed with [crate::receipt::verify_stream].
+    pub fn with_stream_writer(self, writer: impl Write + 'a) -> Self {
+        self.with_write_fd(fileno::STREAM, writer)
+    }
+
     /// Add an environment variable to the guest environment
     pub fn with_env_var(mut self, name: &str, val: &str) -> Self {
         self.env_vars.insert(name.to_string(), val.to_string());
         self
     }
 
+    /// Set the guest's command-line arguments, which it reads with
+    /// `risc0_zkvm::guest::env::args`, or `std::env::args` with the `std`
+    /// feature. As on other platforms, the first argument is conventionally
+    /// the program name.
+    pub fn with_args<S: Into<String>>(self, args: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            args: args.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    /// Serve a [VirtualFs] to the guest, which opens its files with
+    /// `risc0_zkvm::guest::env::open` and `env::create`. Keep a clone of
+    /// `vfs` to see which files the guest opened once it has run.
+    pub fn with_vfs(self, vfs: VirtualFs<'a>) -> Self {
+        Self {
+            vfs: Some(vfs),
+            ..self
+        }
+    }
+
+    /// Pass the records which the guest logs with `risc0_zkvm::guest::logger`
+    /// to `logger`, rather than to the host's global logger. The guest then
+    /// sends records at every level, and `logger` decides which to keep.
+    pub fn with_guest_logger(self, logger: impl log::Log + 'a) -> Self {
+        Self {
+            guest_logger: Some(Rc::new(logger)),
+            ..self
+        }
+    }
+
+    /// Serve the guest's random words, which it reads with `sys_rand` or
+    /// `getrandom`, from `rng` rather than the host's operating system.
+    pub fn with_rng(self, rng: impl RngCore + 'a) -> Self {
+        self.with_random_fn(random::from_rng(rng))
+    }
+
+    /// Serve the guest's random words from a ChaCha20 generator seeded with
+    /// `seed`, so that runs of a randomized guest are reproducible.
+    pub fn with_rng_seed(self, seed: u64) -> Self {
+        self.with_rng(ChaCha20Rng::seed_from_u64(seed))
+    }
+
+    /// Replay a recorded stream of random words to the guest. The guest
+    /// fails if it reads more words than were recorded.
+    pub fn with_random_words(self, words: impl IntoIterator<Item = u32> + 'a) -> Self {
+        self.with_random_fn(random::from_words(words))
+    }
+
+    /// Serve the guest's random words from a callback, which fills the
+    /// buffer the guest asked for.
+    pub fn with_random_callback(self, callback: impl FnMut(&mut [u32]) -> Result<()> + 'a) -> Self {
+        self.with_random_fn(Box::new(callback))
+    }
+
+    fn with_random_fn(self, random: RandomFn<'a>) -> Self {
+        assert!(self.random.is_none(), "Duplicate random source");
+        Self {
+            random: Some(random),
+            ..self
+        }
+    }
+
     /// Add late-binding handlers for constructed environment.
     pub(crate) fn finalize(mut self) -> Self {
         let io = Rc::new(take(&mut self.io));
         let getenv = Getenv(take(&mut self.env_vars));
+        let args = Rc::new(Args(take(&mut self.args)));
+        let guest_log = GuestLog {
+            logger: self.guest_logger.take(),
+        };
+        if self.failure_receipts {
+            self = self.with_syscall(SYS_PANIC, PanicToReceipt);
+        }
+        if let Some(vfs) = self.vfs.take() {
+            let io = io.clone();
+            let vfs = VfsSyscall { vfs, io };
+            self = self
+                .with_syscall(SYS_OPEN, vfs.clone())
+                .with_syscall(SYS_CLOSE, vfs);
+        }
         self.with_syscall(SYS_READ, io.clone())
             .with_syscall(SYS_READ_AVAIL, io.clone())
             .with_syscall(SYS_WRITE, io)
             .with_syscall(SYS_GETENV, getenv)
+            .with_syscall(SYS_ARGC, args.clone())
+            .with_syscall(SYS_ARGV, args)
+            .with_syscall(SYS_LOG_LEVEL, guest_log.clone())
+            .with_syscall(SYS_LOG_RECORD, guest_log)
     }
 }
 
@@ -216,6 +404,48 @@ impl Syscall for DefaultSyscall {
     }
 }
 
+// Let the guest go on to halt with a panicked exit code, rather than failing.
+struct PanicToReceipt;
+
+impl Syscall for PanicToReceipt {
+    fn syscall(
+        &self,
+        _syscall: &str,
+        ctx: &dyn SyscallContext,
+        _to_guest: &mut [u32],
+    ) -> Result<(u32, u32)> {
+        let buf_ptr = ctx.load_register(REG_A3);
+        let buf_len = ctx.load_register(REG_A4);
+        let from_guest = ctx.load_region(buf_ptr, buf_len);
+        let msg = from_utf8(&from_guest)?;
+        log::warn!("Guest panicked: {msg}");
+        Ok((0, 0))
+    }
+}
+
+struct Args(Vec<String>);
+impl Syscall for Rc<Args> {
+    fn syscall(
+        &self,
+        syscall: &str,
+        ctx: &dyn SyscallContext,
+        to_guest: &mut [u32],
+    ) -> Result<(u32, u32)> {
+        if syscall == SYS_ARGC.as_str() {
+            return Ok((self.0.len() as u32, 0));
+        }
+        let index = ctx.load_register(REG_A3) as usize;
+        let arg = match self.0.get(index) {
+            Some(arg) => arg.as_bytes(),
+            None => bail!("Guest read argument {index} of {}", self.0.len()),
+        };
+        let nbytes = min(to_guest.len() * WORD_SIZE, arg.len());
+        let to_guest_u8s: &mut [u8] = bytemuck::cast_slice_mut(to_guest);
+        to_guest_u8s[0..nbytes].clone_from_slice(&arg[0..nbytes]);
+        Ok((arg.len() as u32, 0))
+    }
+}
+
 struct Getenv(HashMap<String, String>);
 impl Syscall for Getenv {
     fn syscall(
@@ -245,16 +475,25 @@ impl<'a> Default for ProverOpts<'a> {
     fn default() -> ProverOpts<'a> {
         ProverOpts {
             io: PosixIo::new(),
+            journal_writer: None,
             skip_seal: false,
             skip_verify: false,
+            failure_receipts: false,
             syscall_handlers: HashMap::new(),
             env_vars: HashMap::new(),
+            args: Vec::new(),
+            vfs: None,
+            guest_logger: None,
+            random: None,
             trace_callback: None,
             preflight: std::env::var("RISC0_EXPERIMENTAL_PREFLIGHT").is_ok(),
+            proof_params: ProofParams::default(),
+            iop_transcript: None,
         }
         .with_read_fd(fileno::STDIN, BufReader::new(stdin()))
         .with_write_fd(fileno::STDOUT, stdout())
         .with_write_fd(fileno::STDERR, stderr())
+        .with_write_fd(fileno::STREAM, sink())
         .with_syscall(SYS_PANIC, DefaultSyscall)
         .with_syscall(SYS_LOG, DefaultSyscall)
         .with_syscall(SYS_CYCLE_COUNT, DefaultSyscall)
@@ -303,6 +542,17 @@ pub struct Prover<'a> {
     /// called. Note that this is privately shared with the host; it is not
     /// present in the [Receipt].
     pub cycles: usize,
+    /// What the guest reported about its execution.
+    ///
+    /// Empty until [Prover::run] is called. Like [Prover::cycles], this is
+    /// not present in the [Receipt].
+    pub stats: RunStats,
+    /// How the guest exited.
+    ///
+    /// Initialized to `None` by [Prover::new], then set when [Prover::run]
+    /// is called if the guest halts. This is also committed in the seal of
+    /// the [Receipt]; see [ExitCode].
+    pub exit_code: Option<ExitCode>,
 }
 
 cfg_if::cfg_if! {
@@ -400,6 +650,8 @@ impl<'a> Prover<'a> {
             inner: ProverImpl::new(opts),
             image_id: image_id.into(),
             cycles: 0,
+            stats: RunStats::default(),
+            exit_code: None,
         })
     }
 
@@ -469,6 +721,7 @@ impl<'a> Prover<'a> {
         self.inner.opts = take(&mut self.inner.opts).finalize();
         let skip_seal = self.inner.opts.skip_seal || insecure_skip_seal();
 
+        let failure_receipts = self.inner.opts.failure_receipts;
         if self.inner.opts.preflight {
             if skip_seal {
                 let image = MemoryImage::new(&self.elf, PAGE_SIZE as u32);
@@ -481,6 +734,9 @@ impl<'a> Prover<'a> {
                 while !preflight.is_halted() {
                     preflight.step().unwrap()
                 }
+                self.stats = self.inner.stats.report();
+                let exit_word = preflight.output()[DIGEST_WORDS];
+                check_exit_word(exit_word, failure_receipts, &mut self.exit_code)?;
                 return Ok(Receipt {
                     journal: self.inner.journal.buf.take(),
                     seal: Vec::new(),
@@ -490,11 +746,30 @@ impl<'a> Prover<'a> {
             }
         }
 
+        let proof_params = self.inner.opts.proof_params;
+        let iop_transcript = self.inner.opts.iop_transcript.clone();
+        let stats = self.inner.stats.clone();
         let mut executor = exec::RV32Executor::new(&CIRCUIT, &self.elf, &mut self.inner);
         self.cycles = executor.run()?;
+        self.stats = stats.report();
+
+        // The output after the journal digest is the exit code.
+        let exit_word = {
+            let io = executor.executor.io.as_slice();
+            let offset = WORD_SIZE + DIGEST_BYTES + 2 * DIGEST_WORDS;
+            u32::from(io[offset]) | u32::from(io[offset + 1]) << 16
+        };
+        let exit_code = check_exit_word(exit_word, failure_receipts, &mut self.exit_code)?;
 
         let mut adapter = ProveAdapter::new(&mut executor.executor);
-        let mut prover = risc0_zkp::prove::Prover::new(hal, CIRCUIT.get_taps());
+        let mut prover = match iop_transcript {
+            Some(_) => {
+                risc0_zkp::prove::Prover::new_with_transcript(hal, CIRCUIT.get_taps(), proof_params)
+            }
+            None => {
+                risc0_zkp::prove::Prover::new_with_params(hal, CIRCUIT.get_taps(), proof_params)
+            }
+        };
 
         adapter.execute(prover.iop());
 
@@ -520,7 +795,11 @@ impl<'a> Prover<'a> {
             let mix = hal.copy_from_elem("mix", &adapter.get_mix().as_slice());
             let out = hal.copy_from_elem("out", &adapter.get_io().as_slice());
 
-            prover.finalize(&[&mix, &out], eval)
+            let (seal, transcript) = prover.finalize_with_transcript(&[&mix, &out], eval);
+            if let Some(path) = &iop_transcript {
+                fs::write(path, transcript.to_string())?;
+            }
+            seal
         };
 
         // Attach the full version of the output journal & construct receipt object
@@ -531,13 +810,40 @@ impl<'a> Prover<'a> {
 
         if !skip_seal && !self.inner.opts.skip_verify {
             // Verify receipt to make sure it works
-            receipt.verify_with_hash::<H::HashSuite, _>(&self.image_id)?;
+            let hal = risc0_zkp::verify::CpuVerifyHal::<BabyBear, H::HashSuite, _>::new(&CIRCUIT);
+            let journal_digest = self.inner.journal_digest();
+            let journal = match &journal_digest {
+                Some(digest) => JournalRef::Digest(digest),
+                None => JournalRef::Bytes(&receipt.journal),
+            };
+            let verified_exit_code =
+                verify_seal(&hal, &self.image_id, &receipt.seal, journal, None)?;
+            if verified_exit_code != exit_code {
+                bail!("Receipt has exit code {verified_exit_code:?}, expected {exit_code:?}");
+            }
         }
 
         Ok(receipt)
     }
 }
 
+// Decodes the exit code from the output word which the guest writes after the
+// journal digest, records it in `exit_code`, and fails unless the guest
+// succeeded or failure receipts were requested.
+fn check_exit_word(
+    exit_word: u32,
+    failure_receipts: bool,
+    exit_code: &mut Option<ExitCode>,
+) -> Result<ExitCode> {
+    let code =
+        ExitCode::from_u32(exit_word).ok_or_else(|| anyhow!("Invalid exit code: {exit_word}"))?;
+    *exit_code = Some(code);
+    if !code.is_success() && !failure_receipts {
+        bail!("Guest {code}");
+    }
+    Ok(code)
+}
+
 // Capture the journal output in a buffer that we can access afterwards.
 #[derive(Clone, Default)]
 pub(crate) struct Journal {
@@ -553,22 +859,155 @@ impl Write for Journal {
     }
 }
 
+// Stream the journal output to a writer, keeping only its digest.
+struct StreamedJournal<'a> {
+    writer: Box<dyn Write + 'a>,
+    hasher: Rc<RefCell<Sha256>>,
+}
+
+impl<'a> Write for StreamedJournal<'a> {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        let len = self.writer.write(bytes)?;
+        self.hasher.borrow_mut().update(&bytes[..len]);
+        Ok(len)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Heap usage of the guest, in bytes.
+///
+/// With the default guest allocator, which never frees memory, all three
+/// values are the same. With the `free_list_alloc` feature of the guest's
+/// `risc0-zkvm` dependency, freed memory is reused, so `reserved` only grows
+/// as far as needed to hold the most memory the guest used at once. Sizes are
+/// counted after rounding up to the allocator's block sizes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HeapStats {
+    /// Bytes allocated and not yet freed when the guest exited.
+    pub in_use: usize,
+    /// The most bytes allocated and not yet freed at any one time.
+    pub peak_in_use: usize,
+    /// Bytes of the guest's heap region used by the allocator; this is the
+    /// high-water mark of the heap region.
+    pub reserved: usize,
+}
+
+/// What a guest reported about its execution, as collected by [Prover::run].
+#[derive(Clone, Debug, Default)]
+pub struct RunStats {
+    /// The guest's heap usage, as reported by its allocator when it exits,
+    /// or `None` if the guest didn't exit normally.
+    pub heap_stats: Option<HeapStats>,
+    /// The cycles spent in each region which the guest marked with
+    /// `risc0_zkvm::guest::env::region`.
+    pub regions: Vec<RegionStats>,
+    /// How many random words the guest read with `sys_rand`, whether from
+    /// the host's operating system or the source set in [ProverOpts]. A guest
+    /// which reads none did not depend on host randomness.
+    pub random_words: usize,
+}
+
+// The state of the syscall handlers which a [RunStats] is collected from,
+// shared with the handlers installed in the [ProverOpts].
+#[derive(Clone)]
+struct RunStatsHandles {
+    heap_stats: Rc<Cell<Option<HeapStats>>>,
+    regions: Rc<RefCell<RegionTracker>>,
+    random_words: Rc<Cell<usize>>,
+}
+
+impl RunStatsHandles {
+    fn report(&self) -> RunStats {
+        RunStats {
+            heap_stats: self.heap_stats.get(),
+            regions: self.regions.borrow().report(),
+            random_words: self.random_words.get(),
+        }
+    }
+}
+
+// Record the heap usage which the guest reports when it exits.
+#[derive(Clone, Default)]
+struct HeapStatsHandler(Rc<Cell<Option<HeapStats>>>);
+
+impl Syscall for HeapStatsHandler {
+    fn syscall(
+        &self,
+        _syscall: &str,
+        ctx: &dyn SyscallContext,
+        _to_guest: &mut [u32],
+    ) -> Result<(u32, u32)> {
+        let buf_ptr = ctx.load_register(REG_A3);
+        let buf_len = ctx.load_register(REG_A4);
+        let from_guest = ctx.load_region(buf_ptr, buf_len);
+        if from_guest.len() != 3 * WORD_SIZE {
+            bail!("Invalid heap stats from guest: {} bytes", from_guest.len());
+        }
+        let words: Vec<u32> = from_guest
+            .chunks_exact(WORD_SIZE)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        let stats = HeapStats {
+            in_use: words[0] as usize,
+            peak_in_use: words[1] as usize,
+            reserved: words[2] as usize,
+        };
+        log::debug!("SYS_HEAP_STATS: {stats:?}");
+        self.0.set(Some(stats));
+        Ok((0, 0))
+    }
+}
+
 struct ProverImpl<'a> {
     pub input: Vec<u8>,
     pub journal: Journal,
+    pub stats: RunStatsHandles,
+    pub journal_hasher: Option<Rc<RefCell<Sha256>>>,
     pub opts: ProverOpts<'a>,
 }
 
 impl<'a> ProverImpl<'a> {
-    fn new(opts: ProverOpts<'a>) -> Self {
+    fn new(mut opts: ProverOpts<'a>) -> Self {
         let journal = Journal::default();
-        let opts = opts.with_write_fd(fileno::JOURNAL, journal.clone());
+        let heap_stats = HeapStatsHandler::default();
+        let regions = RegionHandler::default();
+        let random = RandomHandler::new(opts.random.take());
+        let random_words = random.words.clone();
+        let mut journal_hasher = None;
+        let opts = match opts.journal_writer.take() {
+            Some(writer) => {
+                let hasher = Rc::new(RefCell::new(Sha256::new()));
+                journal_hasher = Some(hasher.clone());
+                opts.with_write_fd(fileno::JOURNAL, StreamedJournal { writer, hasher })
+            }
+            None => opts.with_write_fd(fileno::JOURNAL, journal.clone()),
+        };
+        let opts = opts
+            .with_default_syscall(SYS_HEAP_STATS, heap_stats.clone())
+            .with_default_syscall(SYS_REGION_ENTER, regions.clone())
+            .with_default_syscall(SYS_REGION_EXIT, regions.clone())
+            .with_default_syscall(SYS_RANDOM, random);
         Self {
             input: Vec::new(),
             journal,
+            stats: RunStatsHandles {
+                heap_stats: heap_stats.0,
+                regions: regions.0,
+                random_words,
+            },
+            journal_hasher,
             opts,
         }
     }
+
+    /// The digest of the journal, if it was streamed to a writer.
+    fn journal_digest(&self) -> Option<Digest> {
+        let hasher = self.journal_hasher.as_ref()?;
+        let digest = hasher.borrow().clone().finalize();
+        Some(Digest::try_from(digest.as_slice()).unwrap())
+    }
 }
 
 impl<'a> exec::HostHandler for ProverImpl<'a> {
@@ -598,13 +1037,6 @@ impl<'a> exec::HostHandler for ProverImpl<'a> {
                     .clone_from_slice(&self.input[..copy_bytes]);
                 Ok((self.input.len() as u32, 0))
             }
-            "SYS_RANDOM" => {
-                log::debug!("SYS_RANDOM: {}", to_guest.len());
-                let mut rand_buf = vec![0u8; to_guest.len() * WORD_SIZE];
-                getrandom::getrandom(rand_buf.as_mut_slice())?;
-                bytemuck::cast_slice_mut(to_guest).clone_from_slice(rand_buf.as_slice());
-                Ok((0, 0))
-            }
             _ => bail!("Unknown syscall: {syscall}"),
         }
     }
@@ -92,6 +92,9 @@ pub struct Preflight<'a> {
     // State that rsslib neads
     hart_state: HartState,
 
+    // Words written with sys_output: the journal digest, then the exit code
+    output: [u32; DIGEST_WORDS + 1],
+
     halted: bool,
 }
 
@@ -110,6 +113,7 @@ impl<'a> Preflight<'a> {
             mem: MemState::new(mem_init),
             cycles: Vec::new(),
             hart_state,
+            output: [0; DIGEST_WORDS + 1],
             halted: false,
             opts,
             initial_input,
@@ -121,6 +125,11 @@ impl<'a> Preflight<'a> {
         self.halted
     }
 
+    /// Returns the words which the guest wrote with sys_output
+    pub fn output(&self) -> &[u32] {
+        &self.output
+    }
+
     /// Execute one instruction
     pub fn step(&mut self) -> Result<()> {
         assert!(!self.halted);
@@ -190,7 +199,12 @@ impl<'a> Preflight<'a> {
                 })
             }
             ecall::OUTPUT => {
-                // TODO
+                let id = self.mem.load_register(REG_A0) as usize;
+                let value = self.mem.load_register(REG_A1);
+                match self.output.get_mut(id) {
+                    Some(word) => *word = value,
+                    None => bail!("Invalid output ID {id}"),
+                }
                 Ok(Cycle {
                     new_pc,
                     circuit_cycles: 1,
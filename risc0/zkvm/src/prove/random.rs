@@ -0,0 +1,133 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The random words which the guest reads with `sys_rand`.
+
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use anyhow::{bail, Result};
+use rand::RngCore;
+
+use super::io::{Syscall, SyscallContext};
+
+/// Fills the buffer with the next random words for the guest.
+pub(crate) type RandomFn<'a> = Box<dyn FnMut(&mut [u32]) -> Result<()> + 'a>;
+
+/// Serves random words from the host's operating system.
+pub(crate) fn os_random(words: &mut [u32]) -> Result<()> {
+    getrandom::getrandom(bytemuck::cast_slice_mut(words))?;
+    Ok(())
+}
+
+/// Serves random words from an [RngCore].
+pub(crate) fn from_rng<'a>(mut rng: impl RngCore + 'a) -> RandomFn<'a> {
+    Box::new(move |words: &mut [u32]| {
+        for word in words.iter_mut() {
+            *word = rng.next_u32();
+        }
+        Ok(())
+    })
+}
+
+/// Replays a recorded stream of words, failing once it is used up.
+pub(crate) fn from_words<'a>(words: impl IntoIterator<Item = u32> + 'a) -> RandomFn<'a> {
+    let mut words = words.into_iter();
+    let mut served = 0;
+    Box::new(move |buf: &mut [u32]| {
+        for word in buf.iter_mut() {
+            match words.next() {
+                Some(next) => *word = next,
+                None => bail!("Guest read more than the {served} recorded random words"),
+            }
+            served += 1;
+        }
+        Ok(())
+    })
+}
+
+/// Handles `SYS_RANDOM`, counting the words served to the guest.
+pub(crate) struct RandomHandler<'a> {
+    source: RefCell<RandomFn<'a>>,
+    pub words: Rc<Cell<usize>>,
+}
+
+impl<'a> RandomHandler<'a> {
+    pub fn new(source: Option<RandomFn<'a>>) -> Self {
+        Self {
+            source: RefCell::new(source.unwrap_or_else(|| Box::new(os_random))),
+            words: Rc::default(),
+        }
+    }
+
+    fn fill(&self, words: &mut [u32]) -> Result<()> {
+        (self.source.borrow_mut())(words)?;
+        self.words.set(self.words.get() + words.len());
+        Ok(())
+    }
+}
+
+impl<'a> Syscall for RandomHandler<'a> {
+    fn syscall(
+        &self,
+        _syscall: &str,
+        _ctx: &dyn SyscallContext,
+        to_guest: &mut [u32],
+    ) -> Result<(u32, u32)> {
+        log::debug!("SYS_RANDOM: {}", to_guest.len());
+        self.fill(to_guest)?;
+        Ok((0, 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    use super::{from_rng, from_words, RandomHandler};
+
+    fn draw(handler: &RandomHandler, len: usize) -> Vec<u32> {
+        let mut words = vec![0; len];
+        handler.fill(&mut words).unwrap();
+        words
+    }
+
+    #[test]
+    fn seeded() {
+        let seeded = || RandomHandler::new(Some(from_rng(ChaCha20Rng::seed_from_u64(7))));
+        let (a, b) = (seeded(), seeded());
+        assert_eq!(draw(&a, 5), draw(&b, 5));
+        assert_eq!(draw(&a, 3), draw(&b, 3));
+        assert_eq!(a.words.get(), 8);
+    }
+
+    #[test]
+    fn recorded() {
+        let handler = RandomHandler::new(Some(from_words([1, 2, 3])));
+        assert_eq!(draw(&handler, 2), [1, 2]);
+        assert_eq!(draw(&handler, 1), [3]);
+        assert!(handler.fill(&mut [0]).is_err());
+        assert_eq!(handler.words.get(), 3);
+    }
+
+    #[test]
+    fn os() {
+        let handler = RandomHandler::new(None);
+        assert_ne!(draw(&handler, 4), [0; 4]);
+        assert_eq!(handler.words.get(), 4);
+    }
+}
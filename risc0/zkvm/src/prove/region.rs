@@ -0,0 +1,209 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cycle counts of the regions which the guest marks with `env::region`.
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use anyhow::{bail, Result};
+use risc0_zkvm_platform::syscall::{
+    nr::SYS_REGION_ENTER,
+    reg_abi::{REG_A3, REG_A4},
+};
+
+use super::io::{Syscall, SyscallContext};
+
+/// The cycles spent in a region of the guest, as marked by
+/// `risc0_zkvm::guest::env::region`.
+///
+/// Regions are counted separately for each place they are nested, so a region
+/// `"hash"` entered both at the top level and within `"parse"` has the two
+/// paths `["hash"]` and `["parse", "hash"]`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RegionStats {
+    /// The names of the region and the regions it is nested in, outermost
+    /// first.
+    pub path: Vec<String>,
+    /// How many times the region was entered and exited.
+    pub calls: usize,
+    /// Cycles spent in the region, including those in nested regions.
+    pub inclusive_cycles: usize,
+    /// Cycles spent in the region but not in any nested region.
+    pub exclusive_cycles: usize,
+}
+
+impl RegionStats {
+    /// The name of the region.
+    pub fn name(&self) -> &str {
+        self.path.last().map_or("", String::as_str)
+    }
+
+    /// How many regions this region is nested in.
+    pub fn depth(&self) -> usize {
+        self.path.len() - 1
+    }
+}
+
+struct OpenRegion {
+    index: usize,
+    start_cycle: usize,
+    nested_cycles: usize,
+}
+
+/// Accumulates [RegionStats] as the guest enters and exits regions.
+#[derive(Default)]
+pub(crate) struct RegionTracker {
+    open: Vec<OpenRegion>,
+    stats: Vec<RegionStats>,
+    indices: HashMap<Vec<String>, usize>,
+}
+
+impl RegionTracker {
+    fn enter(&mut self, name: &str, cycle: usize) {
+        let mut path = match self.open.last() {
+            Some(parent) => self.stats[parent.index].path.clone(),
+            None => Vec::new(),
+        };
+        path.push(name.to_string());
+        let stats = &mut self.stats;
+        let index = *self.indices.entry(path).or_insert_with_key(|path| {
+            stats.push(RegionStats {
+                path: path.clone(),
+                ..Default::default()
+            });
+            stats.len() - 1
+        });
+        self.open.push(OpenRegion {
+            index,
+            start_cycle: cycle,
+            nested_cycles: 0,
+        });
+    }
+
+    fn exit(&mut self, cycle: usize) -> Result<()> {
+        let region = match self.open.pop() {
+            Some(region) => region,
+            None => bail!("Guest exited a region which it had not entered"),
+        };
+        let cycles = cycle - region.start_cycle;
+        let stats = &mut self.stats[region.index];
+        stats.calls += 1;
+        stats.inclusive_cycles += cycles;
+        stats.exclusive_cycles += cycles - region.nested_cycles;
+        if let Some(parent) = self.open.last_mut() {
+            parent.nested_cycles += cycles;
+        }
+        Ok(())
+    }
+
+    /// Returns the stats of the regions which the guest exited, in the order
+    /// it first entered them.
+    pub fn report(&self) -> Vec<RegionStats> {
+        for region in &self.open {
+            let path = self.stats[region.index].path.join("/");
+            log::warn!("Guest did not exit region {path}");
+        }
+        self.stats
+            .iter()
+            .filter(|stats| stats.calls > 0)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Handles SYS_REGION_ENTER and SYS_REGION_EXIT.
+#[derive(Clone, Default)]
+pub(crate) struct RegionHandler(pub Rc<RefCell<RegionTracker>>);
+
+impl Syscall for RegionHandler {
+    fn syscall(
+        &self,
+        syscall: &str,
+        ctx: &dyn SyscallContext,
+        _to_guest: &mut [u32],
+    ) -> Result<(u32, u32)> {
+        let cycle = ctx.get_cycle();
+        if syscall == SYS_REGION_ENTER.as_str() {
+            let buf_ptr = ctx.load_register(REG_A3);
+            let buf_len = ctx.load_register(REG_A4);
+            let name = String::from_utf8(ctx.load_region(buf_ptr, buf_len))?;
+            self.0.borrow_mut().enter(&name, cycle);
+        } else {
+            self.0.borrow_mut().exit(cycle)?;
+        }
+        Ok((0, 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RegionStats, RegionTracker};
+
+    fn stats(path: &[&str], calls: usize, inclusive: usize, exclusive: usize) -> RegionStats {
+        RegionStats {
+            path: path.iter().map(|name| name.to_string()).collect(),
+            calls,
+            inclusive_cycles: inclusive,
+            exclusive_cycles: exclusive,
+        }
+    }
+
+    #[test]
+    fn nesting() {
+        let mut tracker = RegionTracker::default();
+        tracker.enter("parse", 0);
+        tracker.enter("hash", 10);
+        tracker.exit(15).unwrap();
+        tracker.enter("hash", 20);
+        tracker.exit(30).unwrap();
+        tracker.enter("lex", 30);
+        tracker.exit(31).unwrap();
+        tracker.exit(40).unwrap();
+        tracker.enter("hash", 50);
+        tracker.exit(52).unwrap();
+        tracker.enter("done", 60);
+
+        let report = tracker.report();
+        assert_eq!(
+            report,
+            [
+                stats(&["parse"], 1, 40, 24),
+                stats(&["parse", "hash"], 2, 15, 15),
+                stats(&["parse", "lex"], 1, 1, 1),
+                stats(&["hash"], 1, 2, 2),
+            ]
+        );
+        assert_eq!(report[1].name(), "hash");
+        assert_eq!(report[1].depth(), 1);
+    }
+
+    #[test]
+    fn recursion() {
+        let mut tracker = RegionTracker::default();
+        tracker.enter("fib", 0);
+        tracker.enter("fib", 1);
+        tracker.exit(3).unwrap();
+        tracker.exit(4).unwrap();
+        assert_eq!(
+            tracker.report(),
+            [stats(&["fib"], 1, 4, 2), stats(&["fib", "fib"], 1, 2, 2)]
+        );
+    }
+
+    #[test]
+    fn unbalanced() {
+        let mut tracker = RegionTracker::default();
+        assert!(tracker.exit(0).is_err());
+    }
+}
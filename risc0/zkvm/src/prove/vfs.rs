@@ -0,0 +1,489 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A virtual file system which the host serves to the guest.
+//!
+//! A [VirtualFs] maps names to in-memory files, readers, or directories on
+//! the host, and is given to the prover with
+//! [ProverOpts::with_vfs](crate::prove::ProverOpts::with_vfs). The guest
+//! opens files with `env::open` and `env::create`, and reads or writes them
+//! like any other file descriptor. The file system is read-only unless
+//! [VirtualFs::with_write_capture] is set, in which case files which the
+//! guest creates are kept in memory, and are never written to the host's own
+//! file system.
+//!
+//! Every file the guest opens is recorded, along with the SHA-256 digest of
+//! the data it read or wrote, so that the host can tell a verifier which
+//! inputs the guest consumed:
+//! ```
+//! use risc0_zkvm::prove::vfs::{OpenMode, VirtualFs};
+//!
+//! let vfs = VirtualFs::new().with_file("config.json", r#"{"rounds": 3}"#);
+//! // ... run a guest with ProverOpts::default().with_vfs(vfs.clone()) ...
+//! for file in vfs.served() {
+//!     assert_eq!(file.mode, OpenMode::Read);
+//!     println!("{} {} bytes {}", file.name, file.len, file.digest);
+//! }
+//! ```
+//! Note that the host only records what it served; a guest which needs to
+//! prove which inputs it read should commit their digests to the journal.
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fs::File,
+    io::{BufRead, BufReader, Cursor, Read, Write},
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+    str::from_utf8,
+};
+
+use anyhow::Result;
+use risc0_zkp::core::sha::Digest;
+use risc0_zkvm_platform::syscall::{
+    nr::SYS_CLOSE,
+    open_mode,
+    reg_abi::{REG_A3, REG_A4, REG_A5},
+};
+use sha2::{Digest as _, Sha256};
+
+use super::io::{PosixIo, Syscall, SyscallContext};
+
+/// The first file descriptor given to opened files, leaving lower ones for
+/// [ProverOpts::with_read_fd](crate::prove::ProverOpts::with_read_fd) and
+/// [ProverOpts::with_write_fd](crate::prove::ProverOpts::with_write_fd).
+const FIRST_FD: u32 = 0x1000;
+
+/// Whether the guest opened a file for reading or writing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpenMode {
+    /// The guest opened the file with `env::open`.
+    Read,
+    /// The guest created the file with `env::create`.
+    Write,
+}
+
+/// A file which the guest opened.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServedFile {
+    /// The name the guest opened.
+    pub name: String,
+    /// Whether it was opened for reading or writing.
+    pub mode: OpenMode,
+    /// The number of bytes the guest read or wrote.
+    pub len: usize,
+    /// The SHA-256 digest of the bytes the guest read or wrote.
+    pub digest: Digest,
+}
+
+enum Entry<'a> {
+    Blob(Rc<[u8]>),
+    Reader(Option<Box<dyn BufRead + 'a>>),
+}
+
+// The bytes passed through an opened file, shared with the reader or writer
+// registered for it.
+#[derive(Default)]
+struct Usage {
+    len: usize,
+    hasher: Sha256,
+}
+
+struct Opened {
+    name: String,
+    mode: OpenMode,
+    usage: Rc<RefCell<Usage>>,
+}
+
+#[derive(Default)]
+struct Inner<'a> {
+    files: BTreeMap<String, Entry<'a>>,
+    dirs: Vec<(String, PathBuf)>,
+    write_capture: bool,
+    opened: Vec<Opened>,
+    captured: BTreeMap<String, Rc<RefCell<Vec<u8>>>>,
+    next_fd: u32,
+}
+
+/// A virtual file system which the host serves to the guest.
+///
+/// Clones share the same files and record, so keep a clone to inspect
+/// [VirtualFs::served] and [VirtualFs::captured] after running the guest.
+/// See [the module-level documentation](self) for details.
+#[derive(Clone, Default)]
+pub struct VirtualFs<'a> {
+    inner: Rc<RefCell<Inner<'a>>>,
+}
+
+impl<'a> VirtualFs<'a> {
+    /// Returns an empty, read-only file system.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file with the given contents, which the guest can open any
+    /// number of times.
+    pub fn with_file(self, name: &str, contents: impl AsRef<[u8]>) -> Self {
+        let blob = Entry::Blob(contents.as_ref().into());
+        self.inner.borrow_mut().files.insert(name.to_string(), blob);
+        self
+    }
+
+    /// Adds a file which is read from `reader`, which the guest can only open
+    /// once.
+    pub fn with_reader(self, name: &str, reader: impl BufRead + 'a) -> Self {
+        let reader = Entry::Reader(Some(Box::new(reader)));
+        self.inner
+            .borrow_mut()
+            .files
+            .insert(name.to_string(), reader);
+        self
+    }
+
+    /// Serves the files under the host directory `path` as `prefix/...`. Names
+    /// which would leave the directory, such as those containing `..` or
+    /// symbolic links to files outside it, are not served.
+    pub fn with_dir(self, prefix: &str, path: impl Into<PathBuf>) -> Self {
+        let prefix = prefix.trim_end_matches('/').to_string();
+        self.inner.borrow_mut().dirs.push((prefix, path.into()));
+        self
+    }
+
+    /// If true, the guest can create files, whose contents are kept in memory
+    /// and can be read with [VirtualFs::captured]. Otherwise, the file system
+    /// is read-only.
+    pub fn with_write_capture(self, write_capture: bool) -> Self {
+        self.inner.borrow_mut().write_capture = write_capture;
+        self
+    }
+
+    /// Returns the files which the guest opened, in the order it opened them.
+    pub fn served(&self) -> Vec<ServedFile> {
+        self.inner
+            .borrow()
+            .opened
+            .iter()
+            .map(|opened| {
+                let usage = opened.usage.borrow();
+                let digest = usage.hasher.clone().finalize();
+                ServedFile {
+                    name: opened.name.clone(),
+                    mode: opened.mode,
+                    len: usage.len,
+                    digest: Digest::try_from(digest.as_slice()).unwrap(),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the contents of a file which the guest created, if any.
+    pub fn captured(&self, name: &str) -> Option<Vec<u8>> {
+        let inner = self.inner.borrow();
+        inner.captured.get(name).map(|data| data.borrow().clone())
+    }
+
+    /// Returns a reader for the named file, if it can be opened.
+    fn open_reader(&self, name: &str) -> Option<Box<dyn BufRead + 'a>> {
+        let mut inner = self.inner.borrow_mut();
+        match inner.files.get_mut(name) {
+            Some(Entry::Blob(blob)) => return Some(Box::new(Cursor::new(blob.clone()))),
+            Some(Entry::Reader(reader)) => return reader.take(),
+            None => {}
+        }
+        let path = inner.dirs.iter().find_map(|(prefix, dir)| {
+            let rest = name.strip_prefix(prefix.as_str())?.strip_prefix('/')?;
+            let rest = Path::new(rest);
+            if !rest
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+            {
+                return None;
+            }
+            // Resolve symbolic links, which could otherwise lead out of the
+            // directory.
+            let dir = dir.canonicalize().ok()?;
+            let path = dir.join(rest).canonicalize().ok()?;
+            path.starts_with(&dir).then_some(path)
+        })?;
+        let file = File::open(path).ok()?;
+        Some(Box::new(BufReader::new(file)))
+    }
+
+    /// Opens the named file and registers it with `io`, returning its file
+    /// descriptor, or None if the file cannot be opened in `mode`.
+    fn open(&self, io: &PosixIo<'a>, name: &str, mode: OpenMode) -> Option<u32> {
+        let usage = Rc::new(RefCell::new(Usage::default()));
+        let fd = match mode {
+            OpenMode::Read => {
+                let reader = self.open_reader(name)?;
+                let fd = self.next_fd();
+                io.add_read_fd(
+                    fd,
+                    RecordingReader {
+                        inner: reader,
+                        usage: usage.clone(),
+                    },
+                );
+                fd
+            }
+            OpenMode::Write => {
+                if !self.inner.borrow().write_capture {
+                    return None;
+                }
+                let data = Rc::new(RefCell::new(Vec::new()));
+                self.inner
+                    .borrow_mut()
+                    .captured
+                    .insert(name.to_string(), data.clone());
+                let fd = self.next_fd();
+                io.add_write_fd(
+                    fd,
+                    CaptureWriter {
+                        data,
+                        usage: usage.clone(),
+                    },
+                );
+                fd
+            }
+        };
+        self.inner.borrow_mut().opened.push(Opened {
+            name: name.to_string(),
+            mode,
+            usage,
+        });
+        Some(fd)
+    }
+
+    /// Closes a file descriptor returned by [VirtualFs::open], returning
+    /// whether it was open. Descriptors which the file system didn't open are
+    /// left alone.
+    fn close(&self, io: &PosixIo<'a>, fd: u32) -> bool {
+        fd >= FIRST_FD && io.close_fd(fd)
+    }
+
+    fn next_fd(&self) -> u32 {
+        let mut inner = self.inner.borrow_mut();
+        let fd = FIRST_FD + inner.next_fd;
+        inner.next_fd += 1;
+        fd
+    }
+}
+
+// Passes on reads, recording the bytes which the guest consumes.
+struct RecordingReader<'a> {
+    inner: Box<dyn BufRead + 'a>,
+    usage: Rc<RefCell<Usage>>,
+}
+
+impl<'a> Read for RecordingReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let nread = self.inner.read(buf)?;
+        let mut usage = self.usage.borrow_mut();
+        usage.len += nread;
+        usage.hasher.update(&buf[..nread]);
+        Ok(nread)
+    }
+}
+
+impl<'a> BufRead for RecordingReader<'a> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Ok(buf) = self.inner.fill_buf() {
+            let mut usage = self.usage.borrow_mut();
+            usage.len += amt;
+            usage.hasher.update(&buf[..amt]);
+        }
+        self.inner.consume(amt)
+    }
+}
+
+// Keeps what the guest writes in memory.
+struct CaptureWriter {
+    data: Rc<RefCell<Vec<u8>>>,
+    usage: Rc<RefCell<Usage>>,
+}
+
+impl Write for CaptureWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.data.borrow_mut().extend_from_slice(buf);
+        let mut usage = self.usage.borrow_mut();
+        usage.len += buf.len();
+        usage.hasher.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Handles SYS_OPEN by opening files from a [VirtualFs] onto new file
+/// descriptors of the guest's [PosixIo], and SYS_CLOSE by releasing them.
+#[derive(Clone)]
+pub(crate) struct VfsSyscall<'a> {
+    pub vfs: VirtualFs<'a>,
+    pub io: Rc<PosixIo<'a>>,
+}
+
+impl<'a> Syscall for VfsSyscall<'a> {
+    fn syscall(
+        &self,
+        syscall: &str,
+        ctx: &dyn SyscallContext,
+        _to_guest: &mut [u32],
+    ) -> Result<(u32, u32)> {
+        if syscall == SYS_CLOSE.as_str() {
+            let fd = ctx.load_register(REG_A3);
+            let closed = self.vfs.close(&self.io, fd);
+            log::debug!("SYS_CLOSE: {fd} -> {closed}");
+            return Ok((0, 0));
+        }
+        let buf_ptr = ctx.load_register(REG_A3);
+        let buf_len = ctx.load_register(REG_A4);
+        let name = ctx.load_region(buf_ptr, buf_len);
+        let name = from_utf8(&name)?;
+        let mode = match ctx.load_register(REG_A5) {
+            open_mode::READ => OpenMode::Read,
+            open_mode::WRITE => OpenMode::Write,
+            mode => anyhow::bail!("Invalid open mode {mode}"),
+        };
+        let fd = self.vfs.open(&self.io, name, mode);
+        log::debug!("SYS_OPEN: {name} ({mode:?}) -> {fd:?}");
+        Ok((fd.unwrap_or(u32::MAX), 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, Read, Write};
+
+    use super::{OpenMode, VirtualFs};
+    use crate::{
+        prove::io::PosixIo,
+        sha::{Impl, Sha256},
+    };
+
+    #[test]
+    fn open_and_record() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("data.txt"), "from disk").unwrap();
+        let vfs = VirtualFs::new()
+            .with_file("blob", "in memory")
+            .with_reader("once", "streamed".as_bytes())
+            .with_dir("dir", dir.path());
+        let io = PosixIo::new();
+
+        let read = |fd: u32| {
+            let mut read_fds = io.read_fds.borrow_mut();
+            let mut data = String::new();
+            read_fds
+                .get_mut(&fd)
+                .unwrap()
+                .read_to_string(&mut data)
+                .unwrap();
+            data
+        };
+        let fd = vfs.open(&io, "blob", OpenMode::Read).unwrap();
+        assert_eq!(read(fd), "in memory");
+        let fd = vfs.open(&io, "blob", OpenMode::Read).unwrap();
+        assert_eq!(read(fd), "in memory");
+        let fd = vfs.open(&io, "once", OpenMode::Read).unwrap();
+        assert_eq!(read(fd), "streamed");
+        assert_eq!(vfs.open(&io, "once", OpenMode::Read), None);
+        let fd = vfs.open(&io, "dir/data.txt", OpenMode::Read).unwrap();
+        assert_eq!(read(fd), "from disk");
+        assert_eq!(vfs.open(&io, "dir/../data.txt", OpenMode::Read), None);
+        assert_eq!(vfs.open(&io, "dir/missing", OpenMode::Read), None);
+        assert_eq!(vfs.open(&io, "missing", OpenMode::Read), None);
+
+        let served = vfs.served();
+        assert_eq!(served.len(), 4);
+        assert_eq!(served[3].name, "dir/data.txt");
+        assert_eq!(served[3].len, 9);
+        assert_eq!(served[3].digest, *Impl::hash_bytes(b"from disk"));
+    }
+
+    #[test]
+    fn partial_reads_are_recorded() {
+        let vfs = VirtualFs::new().with_file("blob", "0123456789");
+        let io = PosixIo::new();
+        let fd = vfs.open(&io, "blob", OpenMode::Read).unwrap();
+        {
+            let mut read_fds = io.read_fds.borrow_mut();
+            let reader = read_fds.get_mut(&fd).unwrap();
+            assert_eq!(reader.fill_buf().unwrap().len(), 10);
+            let mut buf = [0; 4];
+            reader.read_exact(&mut buf).unwrap();
+        }
+        let served = vfs.served();
+        assert_eq!(served[0].len, 4);
+        assert_eq!(served[0].digest, *Impl::hash_bytes(b"0123"));
+    }
+
+    #[test]
+    fn write_capture() {
+        let io = PosixIo::new();
+        let vfs = VirtualFs::new();
+        assert_eq!(vfs.open(&io, "out", OpenMode::Write), None);
+
+        let vfs = VirtualFs::new().with_write_capture(true);
+        let fd = vfs.open(&io, "out", OpenMode::Write).unwrap();
+        io.write_fds
+            .borrow_mut()
+            .get_mut(&fd)
+            .unwrap()
+            .write_all(b"result")
+            .unwrap();
+        assert_eq!(vfs.captured("out").unwrap(), b"result");
+        assert_eq!(vfs.served()[0].mode, OpenMode::Write);
+        assert_eq!(vfs.served()[0].len, 6);
+    }
+
+    #[test]
+    fn close() {
+        let io = PosixIo::new().with_read_fd(0, "stdin".as_bytes());
+        let vfs = VirtualFs::new().with_file("blob", "in memory");
+        let fd = vfs.open(&io, "blob", OpenMode::Read).unwrap();
+        assert!(vfs.close(&io, fd));
+        assert!(!io.read_fds.borrow().contains_key(&fd));
+        assert!(!vfs.close(&io, fd));
+        // Only files opened through the file system can be closed.
+        assert!(!vfs.close(&io, 0));
+        assert!(io.read_fds.borrow().contains_key(&0));
+        // Closing a file keeps its record.
+        assert_eq!(vfs.served()[0].name, "blob");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_stay_in_dir() {
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret"), "outside").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("data.txt"), "inside").unwrap();
+        std::os::unix::fs::symlink(outside.path().join("secret"), dir.path().join("file")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("subdir")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("data.txt"), dir.path().join("alias")).unwrap();
+
+        let vfs = VirtualFs::new().with_dir("dir", dir.path());
+        let io = PosixIo::new();
+        assert_eq!(vfs.open(&io, "dir/file", OpenMode::Read), None);
+        assert_eq!(vfs.open(&io, "dir/subdir/secret", OpenMode::Read), None);
+        // Links which stay in the directory are still followed.
+        assert!(vfs.open(&io, "dir/alias", OpenMode::Read).is_some());
+    }
+}
@@ -76,17 +76,32 @@
 //! alternative serialization approach which is more restrictive but may have
 //! better performance for some use cases. If you prefer, you can also directly
 //! access the [Receipt::journal] as a `Vec<u8>`.
+//!
+//! Alongside the journal, the seal commits to the guest's [ExitCode].
+//! [Receipt::verify] only accepts receipts of guests which exited successfully.
+//! A [Receipt] proving that a guest panicked or exited with a nonzero code,
+//! made with [crate::prove::ProverOpts::with_failure_receipts], can be
+//! verified with [Receipt::verify_exit_code], which returns how it exited.
+//!
+//! A guest can also stream output which is too large for the journal to the
+//! host with [crate::guest::env::stream], committing only its digest. After
+//! verifying the [Receipt], check the streamed bytes against that digest with
+//! [verify_stream].
 
 use alloc::vec::Vec;
+use core::{cell::Cell, fmt};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 #[cfg(not(target_os = "zkvm"))]
 use risc0_core::field::baby_bear::BabyBear;
 use risc0_core::field::baby_bear::BabyBearElem;
 use risc0_zeroio::{Deserialize as ZeroioDeserialize, Serialize as ZeroioSerialize};
 #[cfg(not(target_os = "zkvm"))]
 use risc0_zkp::core::config::{HashSuite, HashSuiteSha256};
-use risc0_zkp::{core::sha::Digest, verify::VerificationError, MIN_CYCLES_PO2};
+use risc0_zkp::{
+    core::sha::Digest, params::ProofParams, transcript::Transcript, verify::VerificationError,
+    INV_RATE, MIN_CYCLES_PO2,
+};
 use risc0_zkvm_platform::{
     syscall::{DIGEST_BYTES, DIGEST_WORDS},
     WORD_SIZE,
@@ -98,6 +113,65 @@ use crate::{
     ControlIdLocator, CIRCUIT,
 };
 
+/// The word which the guest outputs as its exit code when it halts with code
+/// zero; other codes are added to it. Guests written before exit codes were
+/// committed always output [DIGEST_BYTES] here, so their receipts still verify
+/// as successful.
+const HALTED: u32 = DIGEST_BYTES as u32;
+
+/// The word which the guest outputs as its exit code when it panics, outside
+/// the range used by [ExitCode::Halted].
+const PANICKED: u32 = 0x200;
+
+/// How the guest exited, which is committed in the seal of its [Receipt].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ExitCode {
+    /// The guest returned from its entry point, or called
+    /// [crate::guest::env::exit], with the given code. Zero means success.
+    Halted(u8),
+
+    /// The guest panicked.
+    Panicked,
+}
+
+impl ExitCode {
+    /// Returns true if the guest halted with exit code zero.
+    pub fn is_success(&self) -> bool {
+        *self == ExitCode::Halted(0)
+    }
+
+    /// Encodes this exit code as the output word which the guest writes
+    /// after the journal digest.
+    #[cfg(any(target_os = "zkvm", test))]
+    pub(crate) const fn into_u32(self) -> u32 {
+        match self {
+            ExitCode::Halted(code) => HALTED + code as u32,
+            ExitCode::Panicked => PANICKED,
+        }
+    }
+
+    /// Decodes an exit code from the output word which the guest writes
+    /// after the journal digest.
+    pub(crate) fn from_u32(word: u32) -> Option<Self> {
+        match word {
+            PANICKED => Some(ExitCode::Panicked),
+            word => word
+                .checked_sub(HALTED)
+                .and_then(|code| u8::try_from(code).ok())
+                .map(ExitCode::Halted),
+        }
+    }
+}
+
+impl fmt::Display for ExitCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExitCode::Halted(code) => write!(f, "exited with code {code}"),
+            ExitCode::Panicked => write!(f, "panicked"),
+        }
+    }
+}
+
 /// Reports whether the zkVM is in the insecure seal skipping mode
 ///
 /// Returns `true` when in the insecure seal skipping mode. Returns `false` when
@@ -166,17 +240,79 @@ pub struct Receipt {
 /// This verifies that this receipt was constructed by running code whose
 /// ImageID is `image_id`. Returns `Ok(())` if this is true. If the code used to
 /// generate this Receipt has a different ImageID, or if it was generated by an
-/// insecure or malicious prover, this will return an `Err`.
+/// insecure or malicious prover, this will return an `Err`. It also returns
+/// an `Err` if the guest did not exit successfully; see [ExitCode].
 ///
 /// This function allows the user to specify the Hardware Abstraction Layer
 /// (HAL) to be used for verification with the `hal` parameter.
 pub fn verify_with_hal<'a, H, D>(hal: &H, image_id: D, seal: &[u32], journal: &[u8]) -> Result<()>
+where
+    H: risc0_zkp::verify::VerifyHal<Elem = BabyBearElem>,
+    H::Hash: ControlIdLocator,
+    &'a Digest: From<D>,
+{
+    check_success(verify_seal(
+        hal,
+        image_id,
+        seal,
+        JournalRef::Bytes(journal),
+        None,
+    )?)
+}
+
+/// Checks that `stream` holds the bytes which the guest streamed with
+/// [crate::guest::env::stream] or [crate::guest::env::commit_digest], given
+/// the digest it committed to the journal.
+///
+/// The streamed bytes are not themselves part of the [Receipt], so verify the
+/// receipt first and take `digest` from its journal.
+#[cfg(feature = "std")]
+pub fn verify_stream(mut stream: impl std::io::Read, digest: &Digest) -> Result<()> {
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        match stream.read(&mut buf)? {
+            0 => break,
+            len => hasher.update(&buf[..len]),
+        }
+    }
+    let actual = Digest::try_from(hasher.finalize().as_slice())?;
+    if &actual != digest {
+        bail!("Streamed output has digest {actual}, but the guest committed {digest}");
+    }
+    Ok(())
+}
+
+/// The journal which a seal is checked against.
+#[derive(Clone, Copy)]
+pub(crate) enum JournalRef<'a> {
+    /// The bytes of the journal.
+    Bytes(&'a [u8]),
+    /// The SHA-256 digest of a journal which the prover streamed elsewhere.
+    Digest(&'a Digest),
+}
+
+fn check_success(exit_code: ExitCode) -> Result<()> {
+    if !exit_code.is_success() {
+        bail!("Verification failed: guest {exit_code}");
+    }
+    Ok(())
+}
+
+pub(crate) fn verify_seal<'a, H, D>(
+    hal: &H,
+    image_id: D,
+    seal: &[u32],
+    journal: JournalRef,
+    transcript: Option<&mut Transcript>,
+) -> Result<ExitCode>
 where
     H: risc0_zkp::verify::VerifyHal<Elem = BabyBearElem>,
     H::Hash: ControlIdLocator,
     &'a Digest: From<D>,
 {
     let image_id: &Digest = image_id.into();
+    let exit_code = Cell::new(None);
     let check_globals = |io: &[BabyBearElem]| -> Result<(), VerificationError> {
         // verify the image_id
         // Convert to u32 first
@@ -202,31 +338,57 @@ where
         let slice = &io[WORD_SIZE + DIGEST_BYTES..];
         let outputs: Vec<u32> = slice.chunks_exact(2).map(|x| x[0] | x[1] << 16).collect();
 
-        let digest = Sha256::digest(journal);
-        let digest_words: &[u32] = bytemuck::cast_slice(digest.as_slice());
-        let seal_words = &outputs[..DIGEST_WORDS];
-        let is_journal_valid = || {
-            (journal.is_empty() && seal_words.iter().all(|x| *x == 0)) || digest_words == seal_words
+        let (digest, is_empty) = match journal {
+            JournalRef::Bytes(bytes) => {
+                let digest = Sha256::digest(bytes);
+                (
+                    Digest::try_from(digest.as_slice()).unwrap(),
+                    bytes.is_empty(),
+                )
+            }
+            JournalRef::Digest(digest) => (*digest, false),
         };
+        let digest_words = digest.as_words();
+        let seal_words = &outputs[..DIGEST_WORDS];
+        let is_journal_valid =
+            || (is_empty && seal_words.iter().all(|x| *x == 0)) || digest_words == seal_words;
         if !is_journal_valid() {
             #[cfg(not(target_os = "zkvm"))]
             log::debug!(
                 "journal: \"{}\", digest: 0x{}, seal: 0x{}",
-                hex::encode(journal),
+                match journal {
+                    JournalRef::Bytes(bytes) => hex::encode(bytes),
+                    JournalRef::Digest(_) => "<streamed>".into(),
+                },
                 hex::encode(bytemuck::cast_slice(digest_words)),
                 hex::encode(bytemuck::cast_slice(seal_words))
             );
             return Err(VerificationError::JournalSealRootMismatch);
         }
 
+        // The output after the journal digest is the exit code.
+        exit_code.set(Some(
+            ExitCode::from_u32(outputs[DIGEST_WORDS])
+                .ok_or(VerificationError::ReceiptFormatError)?,
+        ));
+
         Ok(())
     };
 
     #[cfg(any(feature = "std", target_os = "zkvm"))]
     if insecure_skip_seal() {
-        return Ok(());
+        return Ok(ExitCode::Halted(0));
     }
 
+    // The control IDs are only valid for the default rate, and we require
+    // at least the security level of the default parameters.
+    let check_params = |params: &ProofParams| -> Result<(), VerificationError> {
+        if params.inv_rate() != INV_RATE {
+            return Err(VerificationError::InvalidProofParams);
+        }
+        params.check_security(ProofParams::default().security_bits())
+    };
+
     let control_id = H::Hash::get_control_id();
     let check_code = |po2: u32, merkle_root: &Digest| -> Result<(), VerificationError> {
         let po2 = po2 as usize;
@@ -240,8 +402,28 @@ where
         Ok(())
     };
 
-    risc0_zkp::verify::verify(hal, &CIRCUIT, seal, check_code, check_globals)
-        .map_err(|err| anyhow!("Verification failed: {}", err))
+    match transcript {
+        Some(transcript) => risc0_zkp::verify::verify_with_transcript(
+            hal,
+            &CIRCUIT,
+            seal,
+            transcript,
+            check_params,
+            check_code,
+            check_globals,
+        ),
+        None => {
+            risc0_zkp::verify::verify(hal, &CIRCUIT, seal, check_params, check_code, check_globals)
+        }
+    }
+    .map_err(|err| anyhow!("Verification failed: {}", err))?;
+
+    exit_code.get().ok_or_else(|| {
+        anyhow!(
+            "Verification failed: {}",
+            VerificationError::ReceiptFormatError
+        )
+    })
 }
 
 impl Receipt {
@@ -316,6 +498,106 @@ impl Receipt {
         verify_with_hal(hal, image_id, &self.seal, &self.journal)
     }
 
+    /// Verifies a SHA-256 receipt using CPU, recording the [Transcript] of the
+    /// verifier's IOP.
+    ///
+    /// The transcript can be compared with one recorded by the prover (see
+    /// [crate::prove::ProverOpts::with_iop_transcript]) using
+    /// [risc0_zkp::transcript::diff] to find where a failing proof diverges.
+    #[cfg(not(target_os = "zkvm"))]
+    pub fn verify_with_transcript<'a, D>(
+        &self,
+        image_id: D,
+        transcript: &mut Transcript,
+    ) -> Result<()>
+    where
+        &'a Digest: From<D>,
+    {
+        let hal = risc0_zkp::verify::CpuVerifyHal::<
+            BabyBear,
+            HashSuiteSha256<BabyBear, crate::sha::Impl>,
+            _,
+        >::new(&crate::CIRCUIT);
+        check_success(verify_seal(
+            &hal,
+            image_id,
+            &self.seal,
+            JournalRef::Bytes(&self.journal),
+            Some(transcript),
+        )?)
+    }
+
+    /// Verifies a SHA-256 receipt using CPU, accepting any [ExitCode], and
+    /// returns how the guest exited.
+    ///
+    /// Unlike [Receipt::verify], this also accepts receipts proving that the
+    /// guest panicked or exited with a nonzero code, which can be made with
+    /// [crate::prove::ProverOpts::with_failure_receipts]. In the insecure seal
+    /// skipping mode, this returns a successful exit without checking the
+    /// seal.
+    #[cfg(not(target_os = "zkvm"))]
+    pub fn verify_exit_code<'a, D>(&self, image_id: D) -> Result<ExitCode>
+    where
+        &'a Digest: From<D>,
+    {
+        let hal = risc0_zkp::verify::CpuVerifyHal::<
+            BabyBear,
+            HashSuiteSha256<BabyBear, crate::sha::Impl>,
+            _,
+        >::new(&crate::CIRCUIT);
+        self.verify_exit_code_with_hal(&hal, image_id)
+    }
+
+    /// Verifies a receipt with a user-specified HAL, accepting any
+    /// [ExitCode], and returns how the guest exited.
+    ///
+    /// This is like [Receipt::verify_exit_code], but uses the given HAL, as
+    /// with [Receipt::verify_with_hal].
+    pub fn verify_exit_code_with_hal<'a, H, D>(&self, hal: &H, image_id: D) -> Result<ExitCode>
+    where
+        H: risc0_zkp::verify::VerifyHal<Elem = BabyBearElem>,
+        H::Hash: ControlIdLocator,
+        &'a Digest: From<D>,
+    {
+        verify_seal(
+            hal,
+            image_id,
+            &self.seal,
+            JournalRef::Bytes(&self.journal),
+            None,
+        )
+    }
+
+    /// Verifies a SHA-256 receipt using CPU, given the SHA-256 digest of its
+    /// journal rather than the journal itself.
+    ///
+    /// This is for receipts whose journal was streamed elsewhere with
+    /// [crate::prove::ProverOpts::with_journal_writer], which leaves
+    /// [Receipt::journal] empty. Like [Receipt::verify], it only accepts
+    /// receipts of guests which exited successfully.
+    #[cfg(not(target_os = "zkvm"))]
+    pub fn verify_with_journal_digest<'a, D>(
+        &self,
+        image_id: D,
+        journal_digest: &Digest,
+    ) -> Result<()>
+    where
+        &'a Digest: From<D>,
+    {
+        let hal = risc0_zkp::verify::CpuVerifyHal::<
+            BabyBear,
+            HashSuiteSha256<BabyBear, crate::sha::Impl>,
+            _,
+        >::new(&crate::CIRCUIT);
+        check_success(verify_seal(
+            &hal,
+            image_id,
+            &self.seal,
+            JournalRef::Digest(journal_digest),
+            None,
+        )?)
+    }
+
     /// Extracts the journal from the receipt, as a series of bytes.
     pub fn get_journal_bytes(&self) -> &[u8] {
         bytemuck::cast_slice(self.journal.as_slice())
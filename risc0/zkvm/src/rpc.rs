@@ -0,0 +1,90 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Typed RPC between the guest and the host.
+//!
+//! The [rpc](macro@crate::rpc) attribute turns a trait into a client, which
+//! the guest uses to call the host, and a server, which the host registers
+//! with [ProverOpts::with_rpc](crate::prove::ProverOpts::with_rpc) to dispatch
+//! those calls to an implementation of the trait. Arguments and return values
+//! are encoded with [risc0_zkvm::serde](crate::serde):
+//! ```ignore
+//! #[risc0_zkvm::rpc]
+//! pub trait KeyValue {
+//!     fn get(&self, key: String) -> Result<u32, KeyValueError>;
+//! }
+//!
+//! // In the guest:
+//! let value = KeyValueClient.get("answer".into());
+//!
+//! // On the host:
+//! let opts = ProverOpts::default().with_rpc(KeyValueServer(MyStore::new()));
+//! ```
+//!
+//! The functions in this module are used by the generated code, and are not
+//! usually called directly.
+
+use alloc::vec::Vec;
+
+pub use risc0_zkvm_platform::syscall::SyscallName;
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(target_os = "zkvm"))]
+use crate::serde::{from_slice, to_vec};
+
+/// A call's serialized return value, sent to the guest.
+pub type Response = Vec<u32>;
+
+/// Calls method number `method` of the host's service on `syscall`, passing
+/// `args`, and returns the host's reply.
+#[cfg(any(target_os = "zkvm", doc))]
+pub fn call<A: Serialize, R: Deserialize<'static>>(
+    syscall: SyscallName,
+    method: u32,
+    args: &A,
+) -> R {
+    let request = crate::serde::to_vec(&(method, args)).unwrap();
+    let response = crate::guest::env::send_recv_slice::<u32, u32>(syscall, &request);
+    crate::serde::from_slice(response).unwrap()
+}
+
+/// A host-side implementation of an RPC trait, generated by
+/// [rpc](macro@crate::rpc).
+#[cfg(not(target_os = "zkvm"))]
+pub trait Service {
+    /// The syscall which carries calls to this service.
+    const SYSCALL: SyscallName;
+
+    /// Calls method number `method` with the serialized `args`, and returns
+    /// the serialized result.
+    fn dispatch(&self, method: u32, args: &[u32]) -> anyhow::Result<Response>;
+}
+
+/// Decodes the arguments of a call.
+#[cfg(not(target_os = "zkvm"))]
+pub fn decode<'a, T: Deserialize<'a>>(args: &'a [u32]) -> anyhow::Result<T> {
+    from_slice(args).map_err(|err| anyhow::anyhow!("Invalid RPC arguments: {err}"))
+}
+
+/// Encodes the result of a call.
+#[cfg(not(target_os = "zkvm"))]
+pub fn encode<T: Serialize>(result: &T) -> anyhow::Result<Response> {
+    to_vec(result).map_err(|err| anyhow::anyhow!("Invalid RPC result: {err}"))
+}
+
+/// Returns the error for a call to a method which `service` does not have.
+#[cfg(not(target_os = "zkvm"))]
+pub fn unknown_method(service: &str, method: u32) -> anyhow::Result<Response> {
+    anyhow::bail!("Unknown RPC method {method} of {service}")
+}
@@ -19,6 +19,7 @@ use serde::de::{Deserialize, DeserializeSeed, IntoDeserializer, Visitor};
 use super::{
     align_up,
     err::{Error, Result},
+    Format,
 };
 
 /// Deserialize a slice into the specified type.
@@ -27,13 +28,27 @@ use super::{
 /// possible, such as if `slice` is not the serialized form of an object of type
 /// `T`.
 pub fn from_slice<'a, T: Deserialize<'a>, P: Pod>(slice: &'a [P]) -> Result<T> {
-    let mut deserializer = Deserializer::new(bytemuck::cast_slice(slice));
+    from_slice_with_format(slice, Format::Word)
+}
+
+/// Deserialize a slice in the given [Format] into the specified type.
+///
+/// This is like [from_slice], for data serialized in `format`.
+pub fn from_slice_with_format<'a, T: Deserialize<'a>, P: Pod>(
+    slice: &'a [P],
+    format: Format,
+) -> Result<T> {
+    let mut deserializer = Deserializer::with_format(bytemuck::cast_slice(slice), format);
     T::deserialize(&mut deserializer)
 }
 
 /// Enables deserializing from a slice
 pub struct Deserializer<'de> {
     slice: &'de [u8],
+    format: Format,
+    // The rest of a byte of packed bools in the compact format.
+    bools: u8,
+    nbools: u32,
 }
 
 struct SeqAccess<'a, 'de> {
@@ -93,7 +108,7 @@ impl<'de, 'a> serde::de::EnumAccess<'de> for &'a mut Deserializer<'de> {
     type Variant = Self;
 
     fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
-        let tag = self.try_take_word()?;
+        let tag = self.try_take_u32()?;
         let val = DeserializeSeed::deserialize(seed, tag.into_deserializer())?;
         Ok((val, self))
     }
@@ -133,7 +148,20 @@ impl<'de> Deserializer<'de> {
     ///
     /// Creates a deserializer for deserializing the contents of `slice`
     pub fn new(slice: &'de [u8]) -> Self {
-        Deserializer { slice }
+        Self::with_format(slice, Format::Word)
+    }
+
+    /// Construct a Deserializer for the given [Format]
+    ///
+    /// Creates a deserializer for deserializing the contents of `slice`,
+    /// which was serialized in `format`.
+    pub fn with_format(slice: &'de [u8], format: Format) -> Self {
+        Deserializer {
+            slice,
+            format,
+            bools: 0,
+            nbools: 0,
+        }
     }
 
     fn try_take_word(&mut self) -> Result<u32> {
@@ -157,17 +185,80 @@ impl<'de> Deserializer<'de> {
     }
 
     fn try_take_n_bytes(&mut self, len: usize) -> Result<&'de [u8]> {
+        if self.format == Format::Compact {
+            return self.read_bytes(len);
+        }
         let padded_len = align_up(len, WORD_SIZE);
         let bytes = self.read_bytes(padded_len)?;
         Ok(&bytes[..len])
     }
 
+    fn try_take_byte(&mut self) -> Result<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn try_take_varint(&mut self) -> Result<u64> {
+        let mut value = 0;
+        for shift in (0..u64::BITS).step_by(7) {
+            let byte = self.try_take_byte()?;
+            let bits = (byte & 0x7f) as u64;
+            if bits << shift >> shift != bits {
+                return Err(Error::DeserializeBadVarint);
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(Error::DeserializeBadVarint)
+    }
+
+    fn try_take_varint_u32(&mut self) -> Result<u32> {
+        self.try_take_varint()?
+            .try_into()
+            .map_err(|_| Error::DeserializeBadVarint)
+    }
+
+    fn try_take_signed(&mut self) -> Result<i64> {
+        let value = self.try_take_varint()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    // Take a u16, u32, length or enum tag, which is a word in the word format
+    // and a varint in the compact format.
+    fn try_take_u32(&mut self) -> Result<u32> {
+        match self.format {
+            Format::Word => self.try_take_word(),
+            Format::Compact => self.try_take_varint_u32(),
+        }
+    }
+
+    fn try_take_i32(&mut self) -> Result<i32> {
+        match self.format {
+            Format::Word => Ok(self.try_take_word()? as i32),
+            Format::Compact => self
+                .try_take_signed()?
+                .try_into()
+                .map_err(|_| Error::DeserializeBadVarint),
+        }
+    }
+
+    // Take a u8, or an Option or bool tag in the word format.
+    fn try_take_small(&mut self) -> Result<u32> {
+        match self.format {
+            Format::Word => self.try_take_word(),
+            Format::Compact => Ok(self.try_take_byte()? as u32),
+        }
+    }
+
     /// Read bytes, removing them from the Deserializer and returning them
     ///
     /// Returns the first `len` bytes and remove them from the Deserializer.
     /// If there are fewer than `len` bytes in the Deserializer, return an
     /// `Err`.
     pub fn read_bytes(&mut self, len: usize) -> Result<&'de [u8]> {
+        // Any other data ends a run of packed bools.
+        self.nbools = 0;
         if self.slice.len() >= len {
             let (head, tail) = self.slice.split_at(len);
             self.slice = tail;
@@ -196,6 +287,16 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
+        if self.format == Format::Compact {
+            if self.nbools == 0 {
+                self.bools = self.try_take_byte()?;
+                self.nbools = u8::BITS;
+            }
+            let val = self.bools & 1 == 1;
+            self.bools >>= 1;
+            self.nbools -= 1;
+            return visitor.visit_bool(val);
+        }
         let val = match self.try_take_word()? {
             0 => false,
             1 => true,
@@ -208,56 +309,65 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        visitor.visit_i32(self.try_take_word()? as i32)
+        match self.format {
+            Format::Word => visitor.visit_i32(self.try_take_word()? as i32),
+            Format::Compact => visitor.visit_i8(self.try_take_byte()? as i8),
+        }
     }
 
     fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_i32(self.try_take_word()? as i32)
+        visitor.visit_i32(self.try_take_i32()?)
     }
 
     fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_i32(self.try_take_word()? as i32)
+        visitor.visit_i32(self.try_take_i32()?)
     }
 
     fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_i64(self.try_take_dword()? as i64)
+        match self.format {
+            Format::Word => visitor.visit_i64(self.try_take_dword()? as i64),
+            Format::Compact => visitor.visit_i64(self.try_take_signed()?),
+        }
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u32(self.try_take_word()?)
+        visitor.visit_u32(self.try_take_small()?)
     }
 
     fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u32(self.try_take_word()?)
+        visitor.visit_u32(self.try_take_u32()?)
     }
 
     fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u32(self.try_take_word()?)
+        visitor.visit_u32(self.try_take_u32()?)
     }
 
     fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u64(self.try_take_dword()?)
+        match self.format {
+            Format::Word => visitor.visit_u64(self.try_take_dword()?),
+            Format::Compact => visitor.visit_u64(self.try_take_varint()?),
+        }
     }
 
     fn deserialize_f32<V>(self, _visitor: V) -> Result<V::Value>
@@ -278,6 +388,11 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
+        if self.format == Format::Compact {
+            let character =
+                char::from_u32(self.try_take_varint_u32()?).ok_or(Error::DeserializeBadChar)?;
+            return visitor.visit_char(character);
+        }
         let len_bytes = self.try_take_word()? as usize;
         if len_bytes > 4 {
             return Err(Error::DeserializeBadChar);
@@ -299,7 +414,7 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let len_bytes = self.try_take_word()? as usize;
+        let len_bytes = self.try_take_u32()? as usize;
         let bytes = self.try_take_n_bytes(len_bytes)?;
         let str = core::str::from_utf8(bytes).map_err(|_| Error::DeserializeBadUtf8)?;
         visitor.visit_borrowed_str(str)
@@ -316,7 +431,7 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let len_bytes = self.try_take_word()? as usize;
+        let len_bytes = self.try_take_u32()? as usize;
         let bytes = self.try_take_n_bytes(len_bytes)?;
         visitor.visit_borrowed_bytes(bytes)
     }
@@ -332,7 +447,7 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        match self.try_take_word()? {
+        match self.try_take_small()? {
             0 => visitor.visit_none(),
             1 => visitor.visit_some(self),
             _ => Err(Error::DeserializeBadOption),
@@ -364,7 +479,7 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let len = self.try_take_word()? as usize;
+        let len = self.try_take_u32()? as usize;
         visitor.visit_seq(SeqAccess {
             deserializer: self,
             len,
@@ -397,7 +512,7 @@ impl<'de, 'a> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let len = self.try_take_word()? as usize;
+        let len = self.try_take_u32()? as usize;
         visitor.visit_map(MapAccess {
             deserializer: self,
             len,
@@ -23,6 +23,7 @@ pub enum Error {
     DeserializeBadChar,
     DeserializeBadOption,
     DeserializeBadUtf8,
+    DeserializeBadVarint,
     DeserializeUnexpectedEnd,
     NotSupported,
     SerializeBufferFull,
@@ -39,6 +40,7 @@ impl Display for Error {
             Self::DeserializeBadChar => "Found an invalid unicode char",
             Self::DeserializeBadOption => "Found an Option discriminant that wasn't 0 or 1",
             Self::DeserializeBadUtf8 => "Tried to parse invalid utf-8",
+            Self::DeserializeBadVarint => "Found a varint too large for its type",
             Self::DeserializeUnexpectedEnd => "Unexpected end during deserialization",
             Self::NotSupported => "Not supported",
             Self::SerializeBufferFull => "The serialize buffer is full",
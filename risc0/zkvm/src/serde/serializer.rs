@@ -22,19 +22,28 @@ use serde::Serialize;
 use super::{
     align_up,
     err::{Error, Result},
+    Format,
 };
 
 /// Serialize to a vector of u32 words
 pub fn to_vec<'a, T>(value: &'a T) -> Result<Vec<u32>>
+where
+    T: Serialize + ?Sized,
+{
+    to_vec_with_format(value, Format::Word)
+}
+
+/// Serialize to a vector of u32 words in the given [Format]
+pub fn to_vec_with_format<'a, T>(value: &'a T, format: Format) -> Result<Vec<u32>>
 where
     T: Serialize + ?Sized,
 {
     // Use the in-memory size of the value as a guess for the length
     // of the serialized value.
     let vec = AllocVec::with_capacity(mem::size_of_val(value));
-    let mut serializer = Serializer::new(vec);
+    let mut serializer = Serializer::with_format(vec, format);
     value.serialize(&mut serializer)?;
-    serializer.stream.release()
+    serializer.release()
 }
 
 /// Serialize to a vector of u32 words with size hinting
@@ -48,7 +57,7 @@ where
     let vec = AllocVec::with_capacity(cap);
     let mut serializer = Serializer::new(vec);
     value.serialize(&mut serializer)?;
-    serializer.stream.release()
+    serializer.release()
 }
 
 /// `StreamWriter`s can have data written to them in a streamed manner
@@ -85,6 +94,12 @@ pub trait StreamWriter {
 /// Enables serializing to a stream
 pub struct Serializer<W: StreamWriter> {
     stream: W,
+    format: Format,
+    // Bytes written in the compact format, to pad the output to a word.
+    len: usize,
+    // A run of bools which the compact format packs into the next byte.
+    bools: u8,
+    nbools: u32,
 }
 
 impl<W: StreamWriter> Serializer<W> {
@@ -92,20 +107,89 @@ impl<W: StreamWriter> Serializer<W> {
     ///
     /// Creates a serializer that writes to `stream`.
     pub fn new(stream: W) -> Self {
-        Serializer { stream }
+        Self::with_format(stream, Format::Word)
+    }
+
+    /// Construct a Serializer for the given [Format]
+    ///
+    /// Creates a serializer that writes to `stream` in `format`. With
+    /// [Format::Compact], call [Serializer::release] once done to write out
+    /// the last packed bools and pad the output to a whole word.
+    pub fn with_format(stream: W, format: Format) -> Self {
+        Serializer {
+            stream,
+            format,
+            len: 0,
+            bools: 0,
+            nbools: 0,
+        }
     }
 
     /// Returns the contents of the Serializer stream.
     pub fn release(&mut self) -> Result<W::Output> {
+        if self.format == Format::Compact {
+            self.flush_bools()?;
+            let padding = align_up(self.len, WORD_SIZE) - self.len;
+            self.write_compact(&[0; WORD_SIZE][..padding])?;
+        }
         self.stream.release()
     }
 
     fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
         static ZEROS: [u8; WORD_SIZE] = [0, 0, 0, 0];
+        if self.format == Format::Compact {
+            return self.write_compact(bytes);
+        }
         self.stream.write_slice(bytes)?;
         let padding = align_up(bytes.len(), WORD_SIZE) - bytes.len();
         self.stream.write_slice(&ZEROS[..padding])
     }
+
+    fn write_compact(&mut self, bytes: &[u8]) -> Result<()> {
+        self.flush_bools()?;
+        self.len += bytes.len();
+        self.stream.write_slice(bytes)
+    }
+
+    fn flush_bools(&mut self) -> Result<()> {
+        if self.nbools > 0 {
+            let bools = mem::take(&mut self.bools);
+            self.nbools = 0;
+            self.len += 1;
+            self.stream.write_slice(&[bools])?;
+        }
+        Ok(())
+    }
+
+    fn write_varint(&mut self, mut value: u64) -> Result<()> {
+        let mut buf = [0u8; 10];
+        let mut len = 0;
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                buf[len] = byte;
+                len += 1;
+                break;
+            }
+            buf[len] = byte | 0x80;
+            len += 1;
+        }
+        self.write_compact(&buf[..len])
+    }
+
+    fn write_signed(&mut self, value: i64) -> Result<()> {
+        // Zigzag encoding keeps small negative numbers small.
+        self.write_varint(((value << 1) ^ (value >> 63)) as u64)
+    }
+
+    // Write a length or enum tag.
+    fn write_tag(&mut self, value: u32) -> Result<()> {
+        match self.format {
+            Format::Word => self.stream.write_u32(value),
+            Format::Compact => self.write_varint(value as u64),
+        }
+    }
 }
 
 impl<'a, W: StreamWriter> serde::ser::Serializer for &'a mut Serializer<W> {
@@ -131,11 +215,22 @@ impl<'a, W: StreamWriter> serde::ser::Serializer for &'a mut Serializer<W> {
     }
 
     fn serialize_bool(self, v: bool) -> Result<()> {
-        self.serialize_u8(if v { 1 } else { 0 })
+        if self.format == Format::Word {
+            return self.serialize_u8(if v { 1 } else { 0 });
+        }
+        if self.nbools == u8::BITS {
+            self.flush_bools()?;
+        }
+        self.bools |= (v as u8) << self.nbools;
+        self.nbools += 1;
+        Ok(())
     }
 
     fn serialize_i8(self, v: i8) -> Result<()> {
-        self.serialize_i32(v as i32)
+        match self.format {
+            Format::Word => self.serialize_i32(v as i32),
+            Format::Compact => self.write_compact(&[v as u8]),
+        }
     }
 
     fn serialize_i16(self, v: i16) -> Result<()> {
@@ -143,15 +238,24 @@ impl<'a, W: StreamWriter> serde::ser::Serializer for &'a mut Serializer<W> {
     }
 
     fn serialize_i32(self, v: i32) -> Result<()> {
-        self.stream.write_u32(v as u32)
+        match self.format {
+            Format::Word => self.stream.write_u32(v as u32),
+            Format::Compact => self.write_signed(v as i64),
+        }
     }
 
     fn serialize_i64(self, v: i64) -> Result<()> {
-        self.stream.write_u64(v as u64)
+        match self.format {
+            Format::Word => self.stream.write_u64(v as u64),
+            Format::Compact => self.write_signed(v),
+        }
     }
 
     fn serialize_u8(self, v: u8) -> Result<()> {
-        self.serialize_u32(v as u32)
+        match self.format {
+            Format::Word => self.serialize_u32(v as u32),
+            Format::Compact => self.write_compact(&[v]),
+        }
     }
 
     fn serialize_u16(self, v: u16) -> Result<()> {
@@ -159,11 +263,17 @@ impl<'a, W: StreamWriter> serde::ser::Serializer for &'a mut Serializer<W> {
     }
 
     fn serialize_u32(self, v: u32) -> Result<()> {
-        self.stream.write_u32(v)
+        match self.format {
+            Format::Word => self.stream.write_u32(v),
+            Format::Compact => self.write_varint(v as u64),
+        }
     }
 
     fn serialize_u64(self, v: u64) -> Result<()> {
-        self.stream.write_u64(v)
+        match self.format {
+            Format::Word => self.stream.write_u64(v),
+            Format::Compact => self.write_varint(v),
+        }
     }
 
     fn serialize_f32(self, _v: f32) -> Result<()> {
@@ -175,13 +285,16 @@ impl<'a, W: StreamWriter> serde::ser::Serializer for &'a mut Serializer<W> {
     }
 
     fn serialize_char(self, v: char) -> Result<()> {
+        if self.format == Format::Compact {
+            return self.write_varint(v as u64);
+        }
         let mut buf = [0u8; WORD_SIZE];
         let str = v.encode_utf8(&mut buf);
         str.serialize(self)
     }
 
     fn serialize_str(self, v: &str) -> Result<()> {
-        self.stream.write_u32(v.len() as u32)?;
+        self.write_tag(v.len() as u32)?;
         self.write_bytes(v.as_bytes())?;
         Ok(())
     }
@@ -196,19 +309,19 @@ impl<'a, W: StreamWriter> serde::ser::Serializer for &'a mut Serializer<W> {
     // B) Use the experimental Rust specialization
     //    features.
     fn serialize_bytes(self, v: &[u8]) -> Result<()> {
-        self.stream.write_u32(v.len() as u32)?;
+        self.write_tag(v.len() as u32)?;
         self.write_bytes(v)
     }
 
     fn serialize_none(self) -> Result<()> {
-        self.serialize_u32(0)
+        self.serialize_u8(0)
     }
 
     fn serialize_some<T>(self, value: &T) -> Result<()>
     where
         T: serde::Serialize + ?Sized,
     {
-        self.serialize_u32(1)?;
+        self.serialize_u8(1)?;
         value.serialize(self)
     }
 
@@ -226,7 +339,7 @@ impl<'a, W: StreamWriter> serde::ser::Serializer for &'a mut Serializer<W> {
         variant_index: u32,
         _variant: &'static str,
     ) -> Result<()> {
-        self.stream.write_u32(variant_index)
+        self.write_tag(variant_index)
     }
 
     fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
@@ -246,14 +359,14 @@ impl<'a, W: StreamWriter> serde::ser::Serializer for &'a mut Serializer<W> {
     where
         T: serde::Serialize + ?Sized,
     {
-        self.stream.write_u32(variant_index)?;
+        self.write_tag(variant_index)?;
         value.serialize(self)
     }
 
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
         match len {
             Some(val) => {
-                self.stream.write_u32(val.try_into().unwrap())?;
+                self.write_tag(val.try_into().unwrap())?;
                 Ok(self)
             }
             None => Err(Error::NotSupported),
@@ -279,14 +392,14 @@ impl<'a, W: StreamWriter> serde::ser::Serializer for &'a mut Serializer<W> {
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        self.stream.write_u32(variant_index)?;
+        self.write_tag(variant_index)?;
         Ok(self)
     }
 
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
         match len {
             Some(val) => {
-                self.stream.write_u32(val.try_into().unwrap())?;
+                self.write_tag(val.try_into().unwrap())?;
                 Ok(self)
             }
             None => Err(Error::NotSupported),
@@ -304,7 +417,7 @@ impl<'a, W: StreamWriter> serde::ser::Serializer for &'a mut Serializer<W> {
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        self.stream.write_u32(variant_index)?;
+        self.write_tag(variant_index)?;
         Ok(self)
     }
 }
@@ -460,9 +573,13 @@ impl StreamWriter for AllocVec {
     }
 
     fn release(&mut self) -> Result<Self::Output> {
-        let mut out = Vec::new();
-        out.extend_from_slice(bytemuck::cast_slice(self.0.as_slice()));
-        Ok(out)
+        // The byte buffer is not necessarily word-aligned, e.g. when it is
+        // empty, so copy it word by word rather than casting it.
+        Ok(self
+            .0
+            .chunks_exact(WORD_SIZE)
+            .map(|word| u32::from_ne_bytes(word.try_into().unwrap()))
+            .collect())
     }
 }
 
@@ -532,4 +649,47 @@ mod tests {
         };
         assert_eq!(expected, to_vec(&input).unwrap().as_slice());
     }
+
+    #[test]
+    fn test_compact() {
+        #[derive(Serialize, PartialEq, Debug)]
+        struct Test {
+            bool: bool,
+            i8: i8,
+            u8: u8,
+            i16: i16,
+            u16: u16,
+            i32: i32,
+            u32: u32,
+            i64: i64,
+            u64: u64,
+            str: String,
+        }
+
+        let expected = [0x0904fc01, 0x0d060b05, 0x62610307, 0x00000063];
+
+        let input = Test {
+            bool: true,
+            i8: -4,
+            u8: 4,
+            i16: -5,
+            u16: 5,
+            i32: -6,
+            u32: 6,
+            i64: -7,
+            u64: 7,
+            str: "abc".into(),
+        };
+        assert_eq!(
+            expected,
+            to_vec_with_format(&input, Format::Compact)
+                .unwrap()
+                .as_slice()
+        );
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(to_vec(&()).unwrap(), Vec::<u32>::new());
+    }
 }
@@ -18,17 +18,32 @@ use anyhow::Result;
 use risc0_zeroio::to_vec;
 use risc0_zkp::core::blake2b::{Blake2bCpuImpl, HashSuiteBlake2bCpu};
 use risc0_zkp::core::sha::Digest;
+use risc0_zkp::{
+    params::ProofParams,
+    transcript::{diff, Transcript},
+    verify::VerificationError,
+};
 use risc0_zkvm_methods::{
     multi_test::{MultiTestSpec, SYS_MULTI_TEST},
-    HELLO_COMMIT_ELF, HELLO_COMMIT_ID, MULTI_TEST_ELF, MULTI_TEST_ID, SLICE_IO_ELF, SLICE_IO_ID,
-    STANDARD_LIB_ELF, STANDARD_LIB_ID,
+    HEAP_BUMP_ELF, HEAP_BUMP_ID, HEAP_FREE_ELF, HEAP_FREE_ID, HELLO_COMMIT_ELF, HELLO_COMMIT_ID,
+    MERKLE_ORACLE_ELF, MERKLE_ORACLE_ID, MULTI_TEST_ELF, MULTI_TEST_ID, SHA2_ACCEL_ELF,
+    SHA2_ACCEL_ID, SHA2_SOFT_ELF, SHA2_SOFT_ID, SLICE_IO_ELF, SLICE_IO_ID, STANDARD_LIB_ELF,
+    STANDARD_LIB_ID,
+};
+use risc0_zkvm_platform::{
+    fileno,
+    memory::HEAP,
+    syscall::{nr::SYS_RANDOM, DIGEST_BYTES},
+    WORD_SIZE,
 };
-use risc0_zkvm_platform::{fileno, memory::HEAP, WORD_SIZE};
 use serial_test::serial;
 use test_log::test;
 
-use super::{Prover, ProverOpts, Receipt};
-use crate::prove::TraceEvent;
+use super::{ExitCode, Prover, ProverOpts, Receipt};
+use crate::prove::{
+    io::{Syscall, SyscallContext},
+    HeapStats, TraceEvent,
+};
 
 #[test]
 #[serial]
@@ -155,6 +170,52 @@ fn receipt_serde_with_seal() {
     de.verify(&MULTI_TEST_ID).unwrap();
 }
 
+#[test]
+#[cfg_attr(feature = "cuda", serial)]
+#[cfg_attr(feature = "insecure_skip_seal", ignore)]
+fn batched_merkle_seal() {
+    let default = run_do_nothing(ProverOpts::default()).unwrap();
+    for merkle_cap in [None, Some(0), Some(4)] {
+        let params = ProofParams::default()
+            .with_merkle_batch(true)
+            .with_merkle_cap(merkle_cap);
+        let receipt = run_do_nothing(ProverOpts::default().with_proof_params(params)).unwrap();
+        receipt.verify(&MULTI_TEST_ID).unwrap();
+        log::info!(
+            "merkle_cap: {merkle_cap:?}, seal: {} -> {} words",
+            default.seal.len(),
+            receipt.seal.len()
+        );
+        assert!(receipt.seal.len() < default.seal.len());
+    }
+}
+
+#[test]
+fn iop_transcript() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("prover.txt");
+    let mut receipt = run_do_nothing(ProverOpts::default().with_iop_transcript(&path)).unwrap();
+    let prover = Transcript::parse(&std::fs::read_to_string(&path).unwrap()).unwrap();
+
+    let mut verifier = Transcript::new();
+    receipt
+        .verify_with_transcript(&MULTI_TEST_ID, &mut verifier)
+        .unwrap();
+    assert_eq!(diff(&prover, verifier.entries()), None);
+
+    // A truncated seal fails to verify, and the verifier's transcript shows
+    // where it diverged from the prover's.
+    receipt.seal.truncate(receipt.seal.len() / 2);
+    let mut verifier = Transcript::new();
+    let err = receipt
+        .verify_with_transcript(&MULTI_TEST_ID, &mut verifier)
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains(&VerificationError::ReceiptFormatError.to_string()));
+    assert!(diff(&prover, verifier.entries()).is_some());
+}
+
 #[test]
 #[cfg_attr(feature = "insecure_skip_seal", ignore)]
 fn receipt_serde_no_seal() {
@@ -178,6 +239,82 @@ fn fail() {
         .contains("MultiTestSpec::Fail invoked"));
 }
 
+#[test]
+fn exit_code() {
+    let run = |failure_receipts: bool, preflight: bool| {
+        let opts = ProverOpts::default()
+            .with_skip_seal(true)
+            .with_failure_receipts(failure_receipts)
+            .with_preflight(preflight);
+        let mut prover = Prover::new_with_opts(MULTI_TEST_ELF, MULTI_TEST_ID, opts).unwrap();
+        let spec = MultiTestSpec::Exit {
+            journal: 42,
+            code: 3,
+        };
+        prover.add_input_u32_slice(&to_vec(&spec).unwrap());
+        let receipt = prover.run();
+        assert_eq!(prover.exit_code, Some(ExitCode::Halted(3)));
+        receipt
+    };
+
+    for preflight in [false, true] {
+        assert!(unwrap_err(run(false, preflight))
+            .to_string()
+            .contains("Guest exited with code 3"));
+
+        let receipt = run(true, preflight).unwrap();
+        let journal: u32 = crate::serde::from_slice(&receipt.journal).unwrap();
+        assert_eq!(journal, 42);
+    }
+}
+
+#[test]
+fn exit_code_words() {
+    for code in [
+        ExitCode::Halted(0),
+        ExitCode::Halted(3),
+        ExitCode::Halted(255),
+        ExitCode::Panicked,
+    ] {
+        assert_eq!(ExitCode::from_u32(code.into_u32()), Some(code));
+    }
+    // Guests which predate exit codes always output DIGEST_BYTES after the
+    // journal digest.
+    assert_eq!(
+        ExitCode::from_u32(DIGEST_BYTES as u32),
+        Some(ExitCode::Halted(0))
+    );
+    assert_eq!(ExitCode::from_u32(0), None);
+    assert_eq!(ExitCode::from_u32(DIGEST_BYTES as u32 + 256), None);
+}
+
+#[test]
+#[cfg_attr(feature = "insecure_skip_seal", ignore)]
+#[cfg_attr(feature = "cuda", serial)]
+fn panic_receipt() {
+    let opts = ProverOpts::default().with_failure_receipts(true);
+    let mut prover = Prover::new_with_opts(MULTI_TEST_ELF, MULTI_TEST_ID, opts).unwrap();
+    prover.add_input_u32_slice(&to_vec(&MultiTestSpec::Fail).unwrap());
+    let receipt = prover.run().unwrap();
+    assert_eq!(prover.exit_code, Some(ExitCode::Panicked));
+
+    assert_eq!(
+        receipt.verify_exit_code(&MULTI_TEST_ID).unwrap(),
+        ExitCode::Panicked
+    );
+    assert!(unwrap_err(receipt.verify(&MULTI_TEST_ID))
+        .to_string()
+        .contains("guest panicked"));
+
+    // A successful run has a receipt accepted by both.
+    let receipt = run_do_nothing(ProverOpts::default()).unwrap();
+    receipt.verify(&MULTI_TEST_ID).unwrap();
+    assert_eq!(
+        receipt.verify_exit_code(&MULTI_TEST_ID).unwrap(),
+        ExitCode::Halted(0)
+    );
+}
+
 #[test]
 #[cfg_attr(feature = "insecure_skip_seal", ignore)]
 #[cfg_attr(feature = "cuda", serial)]
@@ -214,6 +351,66 @@ fn do_random() {
     let mut prover = Prover::new(MULTI_TEST_ELF, MULTI_TEST_ID).unwrap();
     prover.add_input_u32_slice(&to_vec(&MultiTestSpec::DoRandom).unwrap());
     prover.run().expect("Could not get receipt");
+    assert_eq!(prover.stats.random_words, 2);
+}
+
+#[test]
+fn random_source() {
+    let run = |opts: ProverOpts<'static>| {
+        let mut prover =
+            Prover::new_with_opts(MULTI_TEST_ELF, MULTI_TEST_ID, opts.with_skip_seal(true))
+                .unwrap();
+        prover.add_input_u32_slice(&to_vec(&MultiTestSpec::DoRandom).unwrap());
+        let receipt = prover.run()?;
+        assert_eq!(prover.stats.random_words, 2);
+        Ok::<_, anyhow::Error>(receipt.journal)
+    };
+
+    let seeded = run(ProverOpts::default().with_rng_seed(42)).unwrap();
+    assert_eq!(
+        seeded,
+        run(ProverOpts::default().with_rng_seed(42)).unwrap()
+    );
+    assert_ne!(
+        seeded,
+        run(ProverOpts::default().with_rng_seed(43)).unwrap()
+    );
+
+    let recorded = ProverOpts::default().with_random_words([0x04030201, 0x08070605]);
+    assert_eq!(run(recorded).unwrap(), [1, 2, 3, 4, 5, 6, 7]);
+    assert!(run(ProverOpts::default().with_random_words([1])).is_err());
+
+    let callback = ProverOpts::default().with_random_callback(|words| {
+        words.fill(0x11111111);
+        Ok(())
+    });
+    assert_eq!(run(callback).unwrap(), [0x11; 7]);
+}
+
+#[test]
+fn random_syscall_override() {
+    struct FixedRandom;
+
+    impl Syscall for FixedRandom {
+        fn syscall(
+            &self,
+            _syscall: &str,
+            _ctx: &dyn SyscallContext,
+            to_guest: &mut [u32],
+        ) -> Result<(u32, u32)> {
+            to_guest.fill(0x22222222);
+            Ok((0, 0))
+        }
+    }
+
+    let opts = ProverOpts::default()
+        .with_skip_seal(true)
+        .with_syscall(SYS_RANDOM, FixedRandom);
+    let mut prover = Prover::new_with_opts(MULTI_TEST_ELF, MULTI_TEST_ID, opts).unwrap();
+    prover.add_input_u32_slice(&to_vec(&MultiTestSpec::DoRandom).unwrap());
+    let receipt = prover.run().unwrap();
+    assert_eq!(receipt.journal, [0x22; 7]);
+    assert_eq!(prover.stats.random_words, 0);
 }
 
 #[test]
@@ -277,6 +474,178 @@ fn host_syscall_callback_panic() {
     prover.run().unwrap();
 }
 
+#[test]
+fn guest_log() {
+    use std::sync::{Arc, Mutex};
+
+    use log::{Level, LevelFilter, Log, Metadata, Record};
+
+    // Keeps the records for targets starting with `module`, up to `level`.
+    struct CaptureLogger {
+        module: &'static str,
+        level: LevelFilter,
+        records: Arc<Mutex<Vec<(Level, String, String)>>>,
+    }
+
+    impl Log for CaptureLogger {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.target().starts_with(self.module) && metadata.level() <= self.level
+        }
+
+        fn log(&self, record: &Record) {
+            if self.enabled(record.metadata()) {
+                self.records.lock().unwrap().push((
+                    record.level(),
+                    record.target().to_string(),
+                    record.args().to_string(),
+                ));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    let run = |module: &'static str, level: LevelFilter| {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let logger = CaptureLogger {
+            module,
+            level,
+            records: records.clone(),
+        };
+        let opts = ProverOpts::default()
+            .with_skip_seal(true)
+            .with_guest_logger(logger);
+        let mut prover = Prover::new_with_opts(MULTI_TEST_ELF, MULTI_TEST_ID, opts).unwrap();
+        prover.add_input_u32_slice(&to_vec(&MultiTestSpec::Log).unwrap());
+        let receipt = prover.run().unwrap();
+
+        // The guest sends every level, and the host's logger filters them.
+        let guest_level: u32 = crate::serde::from_slice(&receipt.journal).unwrap();
+        assert_eq!(guest_level, LevelFilter::Trace as u32);
+        let records = records.lock().unwrap().clone();
+        records
+    };
+
+    let records = run("multi_test", LevelFilter::Info);
+    assert_eq!(
+        records,
+        [
+            (
+                Level::Error,
+                "multi_test".to_string(),
+                "error from the guest".to_string()
+            ),
+            (
+                Level::Info,
+                "multi_test".to_string(),
+                "info from the guest".to_string()
+            ),
+        ]
+    );
+
+    let records = run("multi_test", LevelFilter::Trace);
+    assert_eq!(records.len(), 3);
+    assert!(records[2].2.starts_with("trace from the guest: "));
+
+    // Records from the guest's module are dropped by a filter for another.
+    assert!(run("other_module", LevelFilter::Trace).is_empty());
+}
+
+#[test]
+fn regions() {
+    let opts = ProverOpts::default().with_skip_seal(true);
+    let mut prover = Prover::new_with_opts(MULTI_TEST_ELF, MULTI_TEST_ID, opts).unwrap();
+    prover.add_input_u32_slice(&to_vec(&MultiTestSpec::Regions { count: 5 }).unwrap());
+    prover.run().unwrap();
+
+    let regions = &prover.stats.regions;
+    let paths: Vec<_> = regions.iter().map(|stats| stats.path.join("/")).collect();
+    assert_eq!(paths, ["outer", "outer/inner", "last"]);
+    let (outer, inner, last) = (&regions[0], &regions[1], &regions[2]);
+    assert_eq!((outer.calls, inner.calls, last.calls), (1, 5, 1));
+    assert_eq!(inner.depth(), 1);
+    assert_eq!(inner.inclusive_cycles, inner.exclusive_cycles);
+    assert_eq!(
+        outer.inclusive_cycles,
+        outer.exclusive_cycles + inner.inclusive_cycles
+    );
+    assert!(outer.exclusive_cycles > 0);
+    assert!(last.inclusive_cycles > 0);
+    assert!(outer.inclusive_cycles + last.inclusive_cycles < prover.cycles);
+}
+
+#[test]
+fn rpc() {
+    use std::{cell::RefCell, collections::BTreeMap};
+
+    use risc0_zkvm_methods::multi_test::{KeyValue, KeyValueError, KeyValueServer};
+
+    #[derive(Default)]
+    struct Store(RefCell<BTreeMap<String, u32>>);
+
+    impl KeyValue for &Store {
+        fn get(&self, key: String) -> Result<u32, KeyValueError> {
+            let value = self.0.borrow().get(&key).copied();
+            value.ok_or(KeyValueError::NotFound(key))
+        }
+
+        fn put(&self, key: String, value: u32) {
+            self.0.borrow_mut().insert(key, value);
+        }
+    }
+
+    let store = Store::default();
+    let opts = ProverOpts::default()
+        .with_skip_seal(true)
+        .with_rpc(KeyValueServer(&store));
+    let mut prover = Prover::new_with_opts(MULTI_TEST_ELF, MULTI_TEST_ID, opts).unwrap();
+    prover.add_input_u32_slice(&to_vec(&MultiTestSpec::Rpc).unwrap());
+    let receipt = prover.run().unwrap();
+    drop(prover);
+
+    let answer: u32 = crate::serde::from_slice(&receipt.journal).unwrap();
+    assert_eq!(answer, 42);
+    assert_eq!(*store.0.borrow(), BTreeMap::from([("answer".into(), 42)]));
+}
+
+#[test]
+#[cfg_attr(feature = "insecure_skip_seal", ignore)]
+#[cfg_attr(feature = "cuda", serial)]
+fn stream_digest() {
+    use sha2::{Digest as _, Sha256};
+
+    use crate::receipt::verify_stream;
+
+    let mut journal = Vec::new();
+    let mut stream = Vec::new();
+    let opts = ProverOpts::default()
+        .with_journal_writer(&mut journal)
+        .with_stream_writer(&mut stream);
+    let mut prover = Prover::new_with_opts(MULTI_TEST_ELF, MULTI_TEST_ID, opts).unwrap();
+    prover.add_input_u32_slice(&to_vec(&MultiTestSpec::StreamDigest { len: 1000 }).unwrap());
+    let receipt = prover.run().unwrap();
+    drop(prover);
+
+    // The journal was streamed, so the receipt is verified by its digest.
+    assert!(receipt.journal.is_empty());
+    let journal_digest = Digest::try_from(Sha256::digest(&journal).as_slice()).unwrap();
+    receipt
+        .verify_with_journal_digest(MULTI_TEST_ID, &journal_digest)
+        .unwrap();
+    assert!(receipt
+        .verify_with_journal_digest(MULTI_TEST_ID, &Digest::default())
+        .is_err());
+
+    // The guest streamed the bytes twice, committing the same digest each time.
+    let (len, digest, again): (u32, Digest, Digest) = crate::serde::from_slice(&journal).unwrap();
+    assert_eq!(digest, again);
+    let bytes: Vec<u8> = (0..len).map(|i| i as u8).collect();
+    assert_eq!(stream, [bytes.as_slice(), bytes.as_slice()].concat());
+    verify_stream(&stream[..bytes.len()], &digest).unwrap();
+    verify_stream(&stream[bytes.len()..], &digest).unwrap();
+    assert!(verify_stream(&stream[1..bytes.len()], &digest).is_err());
+}
+
 #[test]
 fn sha_accel() {
     let opts = ProverOpts::default().with_skip_seal(true);
@@ -293,6 +662,141 @@ fn sha_cycle_count() {
     prover.run().unwrap();
 }
 
+#[test]
+fn merkle_oracle() {
+    use crate::merkle::{MerkleOracle, SYS_VECTOR_ORACLE};
+
+    let elements: Vec<(u32, u64)> = (0..1000).map(|i| (i, u64::from(i) * 3)).collect();
+    let oracle = MerkleOracle::new(&elements);
+    let root = oracle.root();
+    let indices: Vec<u32> = vec![0, 999, 500, 500, 17];
+
+    let opts = ProverOpts::default()
+        .with_skip_seal(true)
+        .with_slice_io(SYS_VECTOR_ORACLE, oracle);
+    let mut prover = Prover::new_with_opts(MERKLE_ORACLE_ELF, MERKLE_ORACLE_ID, opts).unwrap();
+    prover.add_input_u32_slice(&crate::serde::to_vec(&root).unwrap());
+    prover.add_input_u32_slice(&crate::serde::to_vec(&indices).unwrap());
+    let receipt = prover.run().unwrap();
+
+    let output: Vec<(u32, u64)> = crate::serde::from_slice(&receipt.journal).unwrap();
+    let expected: Vec<(u32, u64)> = indices.iter().map(|&i| elements[i as usize]).collect();
+    assert_eq!(output, expected);
+}
+
+// The guest should reject elements which aren't in the tree it was given the
+// root of.
+#[test]
+fn merkle_oracle_wrong_root() {
+    use crate::merkle::{MerkleOracle, SYS_VECTOR_ORACLE};
+
+    let oracle = MerkleOracle::new(&[(1u32, 2u64), (3, 4)]);
+    let root = MerkleOracle::new(&[(1u32, 2u64), (3, 5)]).root();
+    let opts = ProverOpts::default()
+        .with_skip_seal(true)
+        .with_slice_io(SYS_VECTOR_ORACLE, oracle);
+    let mut prover = Prover::new_with_opts(MERKLE_ORACLE_ELF, MERKLE_ORACLE_ID, opts).unwrap();
+    prover.add_input_u32_slice(&crate::serde::to_vec(&root).unwrap());
+    prover.add_input_u32_slice(&crate::serde::to_vec(&vec![1u32]).unwrap());
+    assert!(unwrap_err(prover.run())
+        .to_string()
+        .contains("Invalid Merkle proof"));
+}
+
+// Reading past the end of the vector should stop the guest with an error,
+// rather than panic on the host.
+#[test]
+fn merkle_oracle_out_of_bounds() {
+    use crate::merkle::{MerkleOracle, SYS_VECTOR_ORACLE};
+
+    let oracle = MerkleOracle::new(&[(1u32, 2u64), (3, 4)]);
+    let root = oracle.root();
+    let opts = ProverOpts::default()
+        .with_skip_seal(true)
+        .with_slice_io(SYS_VECTOR_ORACLE, oracle);
+    let mut prover = Prover::new_with_opts(MERKLE_ORACLE_ELF, MERKLE_ORACLE_ID, opts).unwrap();
+    prover.add_input_u32_slice(&crate::serde::to_vec(&root).unwrap());
+    prover.add_input_u32_slice(&crate::serde::to_vec(&vec![2u32]).unwrap());
+    assert!(unwrap_err(prover.run())
+        .to_string()
+        .contains("out of bounds"));
+}
+
+// The same guest is built with and without the accelerated `sha2` crate
+// patched in. Both should compute the same digests, but the patched build
+// should take far fewer cycles.
+#[test]
+fn sha2_patch_cycle_count() {
+    use hmac::{Hmac, Mac};
+    use sha2::{Digest, Sha256};
+
+    let key = b"key".to_vec();
+    let msg: Vec<u8> = (0..4096).map(|i| i as u8).collect();
+    let run = |elf: &[u8], image_id: [u32; 8]| {
+        let opts = ProverOpts::default().with_skip_seal(true);
+        let mut prover = Prover::new_with_opts(elf, image_id, opts).unwrap();
+        prover.add_input_u32_slice(&crate::serde::to_vec(&key).unwrap());
+        prover.add_input_u32_slice(&crate::serde::to_vec(&msg).unwrap());
+        let receipt = prover.run().unwrap();
+        let output: ([u8; 32], [u8; 32]) = crate::serde::from_slice(&receipt.journal).unwrap();
+        (output, prover.cycles)
+    };
+    let (accel, accel_cycles) = run(SHA2_ACCEL_ELF, SHA2_ACCEL_ID);
+    let (soft, soft_cycles) = run(SHA2_SOFT_ELF, SHA2_SOFT_ID);
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(&key).unwrap();
+    mac.update(&msg);
+    let expected: ([u8; 32], [u8; 32]) = (
+        Sha256::digest(&msg).into(),
+        mac.finalize().into_bytes().into(),
+    );
+    assert_eq!(accel, expected);
+    assert_eq!(soft, expected);
+    log::info!("sha2 cycles: {accel_cycles} accelerated, {soft_cycles} in software");
+    assert!(
+        accel_cycles * 4 < soft_cycles,
+        "{accel_cycles} accelerated cycles, {soft_cycles} software cycles"
+    );
+}
+
+// The same guest is built with the free list allocator and with the default
+// bump allocator. It allocates and frees a 1 MiB buffer many times, which
+// exhausts the heap unless freed memory is reused.
+#[test]
+fn heap_free_reuses_memory() {
+    const SIZE: u32 = 1024 * 1024;
+    let iterations = 2 * HEAP.len_bytes() as u32 / SIZE;
+    let run = |elf: &[u8], image_id: [u32; 8], iterations: u32| -> Result<(u32, HeapStats)> {
+        let opts = ProverOpts::default().with_skip_seal(true);
+        let mut prover = Prover::new_with_opts(elf, image_id, opts).unwrap();
+        prover.add_input_u32_slice(&crate::serde::to_vec(&iterations).unwrap());
+        prover.add_input_u32_slice(&crate::serde::to_vec(&SIZE).unwrap());
+        let receipt = prover.run()?;
+        let sum: u32 = crate::serde::from_slice(&receipt.journal).unwrap();
+        Ok((sum, prover.stats.heap_stats.unwrap()))
+    };
+    let expected = (0..iterations).fold(0u32, |sum, i| {
+        (0..100).fold(sum.wrapping_add(i & 0xff), |sum, j| sum.wrapping_add(i ^ j))
+    });
+
+    let (sum, stats) = run(HEAP_FREE_ELF, HEAP_FREE_ID, iterations).unwrap();
+    assert_eq!(sum, expected);
+    log::info!("free list allocator: {stats:?}");
+    assert!(stats.peak_in_use < 2 * SIZE as usize, "{stats:?}");
+    assert!(stats.reserved < 4 * SIZE as usize, "{stats:?}");
+
+    assert!(unwrap_err(run(HEAP_BUMP_ELF, HEAP_BUMP_ID, iterations))
+        .to_string()
+        .contains("Out of memory"));
+
+    // With few enough iterations, the bump allocator succeeds, and reports
+    // that it never freed anything.
+    let (_, stats) = run(HEAP_BUMP_ELF, HEAP_BUMP_ID, 4).unwrap();
+    assert!(stats.reserved > 4 * SIZE as usize, "{stats:?}");
+    assert_eq!(stats.in_use, stats.reserved);
+    assert_eq!(stats.peak_in_use, stats.reserved);
+}
+
 #[test]
 fn test_poseidon_proof() {
     use risc0_zkp::core::config::HashSuitePoseidon;
@@ -669,3 +1173,91 @@ ENV_VAR2=
 "
     );
 }
+
+#[test]
+fn args() {
+    let mut stdout = Vec::new();
+    let opts = ProverOpts::default()
+        .with_skip_seal(true)
+        .with_env_var("TEST_MODE", "ARGS")
+        .with_args(["standard_lib", "--flag", "two words"])
+        .with_write_fd(fileno::STDOUT, &mut stdout);
+    let mut prover = Prover::new_with_opts(STANDARD_LIB_ELF, STANDARD_LIB_ID, opts).unwrap();
+    prover.run().unwrap();
+    drop(prover);
+
+    assert_eq!(
+        from_utf8(&stdout).unwrap(),
+        "standard_lib\n--flag\ntwo words\n"
+    );
+}
+
+#[test]
+fn guest_io() {
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let mut fd100 = Vec::new();
+    let opts = ProverOpts::default()
+        .with_skip_seal(true)
+        .with_env_var("TEST_MODE", "GUEST_IO")
+        .with_read_fd(fileno::STDIN, Cursor::new("first\nsecond\nthird"))
+        .with_write_fd(fileno::STDOUT, &mut stdout)
+        .with_write_fd(fileno::STDERR, &mut stderr)
+        .with_write_fd(100, &mut fd100);
+    let mut prover = Prover::new_with_opts(STANDARD_LIB_ELF, STANDARD_LIB_ID, opts).unwrap();
+    prover.run().unwrap();
+    drop(prover);
+
+    assert_eq!(from_utf8(&stdout).unwrap(), "FIRST\nSECOND\nTHIRD\n");
+    assert_eq!(from_utf8(&stderr).unwrap(), "3 lines");
+    assert_eq!(fd100, [3]);
+}
+
+#[test]
+fn vfs() {
+    use crate::{
+        prove::vfs::{OpenMode, VirtualFs},
+        sha::{Impl, Sha256},
+    };
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("b.txt"), "3 4 5").unwrap();
+    let run = |vfs: VirtualFs<'static>| {
+        let opts = ProverOpts::default()
+            .with_skip_seal(true)
+            .with_env_var("TEST_MODE", "VFS")
+            .with_read_fd(fileno::STDIN, Cursor::new("a.txt\ndir/b.txt"))
+            .with_vfs(vfs);
+        let mut prover = Prover::new_with_opts(STANDARD_LIB_ELF, STANDARD_LIB_ID, opts).unwrap();
+        prover.run().unwrap().journal
+    };
+
+    let vfs = VirtualFs::new()
+        .with_file("a.txt", "1 2")
+        .with_dir("dir", dir.path())
+        .with_write_capture(true);
+    assert!(run(vfs.clone()).is_empty());
+    assert_eq!(vfs.captured("sum.txt").unwrap(), b"15");
+    let served = vfs.served();
+    let names: Vec<_> = served
+        .iter()
+        .map(|file| (file.name.as_str(), file.mode))
+        .collect();
+    assert_eq!(
+        names,
+        [
+            ("a.txt", OpenMode::Read),
+            ("dir/b.txt", OpenMode::Read),
+            ("sum.txt", OpenMode::Write),
+        ]
+    );
+    assert_eq!(served[1].len, 5);
+    assert_eq!(served[1].digest, *Impl::hash_bytes(b"3 4 5"));
+
+    let vfs = VirtualFs::new()
+        .with_file("a.txt", "1 2")
+        .with_dir("dir", dir.path());
+    assert_eq!(run(vfs.clone()), b"15");
+    assert_eq!(vfs.captured("sum.txt"), None);
+    assert_eq!(vfs.served().len(), 2);
+}